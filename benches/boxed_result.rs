@@ -0,0 +1,78 @@
+//! Compares the cost of returning a [`BoxedResult`] with that of a std result
+//! and of a [`propagate::Result`] carrying its trace inline.
+//!
+//! Run with `cargo bench --bench boxed_result`.
+
+#![feature(test)]
+
+extern crate test;
+
+use propagate::result::BoxedResult;
+use test::{black_box, Bencher};
+
+const DEPTH: u32 = 8;
+
+#[inline(never)]
+fn std_recurse(depth: u32, fail: bool) -> Result<u64, &'static str> {
+    if depth == 0 {
+        return if fail { Err("bottom") } else { Ok(1) };
+    }
+    let n = std_recurse(depth - 1, fail)?;
+    Ok(black_box(n) + 1)
+}
+
+#[inline(never)]
+fn inline_recurse(depth: u32, fail: bool) -> propagate::Result<u64, &'static str> {
+    if depth == 0 {
+        return if fail {
+            propagate::Result::new_err("bottom")
+        } else {
+            propagate::Ok(1)
+        };
+    }
+    let n = inline_recurse(depth - 1, fail)?;
+    propagate::Ok(black_box(n) + 1)
+}
+
+#[inline(never)]
+fn boxed_recurse(depth: u32, fail: bool) -> BoxedResult<u64, &'static str> {
+    if depth == 0 {
+        return if fail {
+            propagate::Result::new_err("bottom")
+        } else {
+            propagate::Ok(1)
+        };
+    }
+    let n = boxed_recurse(depth - 1, fail)?;
+    propagate::Ok(black_box(n) + 1)
+}
+
+#[bench]
+fn ok_std(b: &mut Bencher) {
+    b.iter(|| std_recurse(black_box(DEPTH), black_box(false)));
+}
+
+#[bench]
+fn ok_inline_trace(b: &mut Bencher) {
+    b.iter(|| inline_recurse(black_box(DEPTH), black_box(false)));
+}
+
+#[bench]
+fn ok_boxed_trace(b: &mut Bencher) {
+    b.iter(|| boxed_recurse(black_box(DEPTH), black_box(false)));
+}
+
+#[bench]
+fn err_std(b: &mut Bencher) {
+    b.iter(|| std_recurse(black_box(DEPTH), black_box(true)));
+}
+
+#[bench]
+fn err_inline_trace(b: &mut Bencher) {
+    b.iter(|| inline_recurse(black_box(DEPTH), black_box(true)));
+}
+
+#[bench]
+fn err_boxed_trace(b: &mut Bencher) {
+    b.iter(|| boxed_recurse(black_box(DEPTH), black_box(true)));
+}
@@ -0,0 +1,97 @@
+//! Measures the success path of `?`, which should cost the same for
+//! [`propagate::Result`] as for std results since the trace is only touched on
+//! the cold error path.
+//!
+//! Run with `cargo bench --bench propagation`. `ok_propagate` should report
+//! the same time as `ok_std_with_trace`, within noise: the std result of the
+//! latter carries an [`ErrorTrace`] in its error, so both are returned through
+//! memory and their loops compile to the same instructions. Any difference
+//! with `ok_std` is the cost of returning the larger result, not of `?`.
+
+#![feature(test)]
+
+extern crate test;
+
+use propagate::ErrorTrace;
+use test::{black_box, Bencher};
+
+const VALUES: u64 = 1000;
+
+#[inline(never)]
+fn std_check(n: u64) -> Result<u64, &'static str> {
+    if n == u64::MAX {
+        Err("overflow")
+    } else {
+        Ok(n + 1)
+    }
+}
+
+#[inline(never)]
+fn std_check_with_trace(n: u64) -> Result<u64, (&'static str, ErrorTrace)> {
+    if n == u64::MAX {
+        Err(("overflow", ErrorTrace::default()))
+    } else {
+        Ok(n + 1)
+    }
+}
+
+#[inline(never)]
+fn propagate_check(n: u64) -> propagate::Result<u64, &'static str> {
+    if n == u64::MAX {
+        propagate::Result::new_err("overflow")
+    } else {
+        propagate::Ok(n + 1)
+    }
+}
+
+#[inline(never)]
+fn std_sum(count: u64) -> Result<u64, &'static str> {
+    let mut sum = 0;
+    for n in 0..count {
+        sum += std_check(black_box(n))?;
+    }
+    Ok(sum)
+}
+
+#[inline(never)]
+fn std_sum_with_trace(count: u64) -> Result<u64, (&'static str, ErrorTrace)> {
+    let mut sum = 0;
+    for n in 0..count {
+        sum += std_check_with_trace(black_box(n))?;
+    }
+    Ok(sum)
+}
+
+#[inline(never)]
+fn propagate_sum(count: u64) -> propagate::Result<u64, &'static str> {
+    let mut sum = 0;
+    for n in 0..count {
+        sum += propagate_check(black_box(n))?;
+    }
+    propagate::Ok(sum)
+}
+
+#[bench]
+fn ok_std(b: &mut Bencher) {
+    b.iter(|| std_sum(black_box(VALUES)));
+}
+
+#[bench]
+fn ok_std_with_trace(b: &mut Bencher) {
+    b.iter(|| std_sum_with_trace(black_box(VALUES)));
+}
+
+#[bench]
+fn ok_propagate(b: &mut Bencher) {
+    b.iter(|| propagate_sum(black_box(VALUES)));
+}
+
+#[bench]
+fn err_std(b: &mut Bencher) {
+    b.iter(|| std_check(black_box(u64::MAX)));
+}
+
+#[bench]
+fn err_propagate(b: &mut Bencher) {
+    b.iter(|| propagate_check(black_box(u64::MAX)));
+}
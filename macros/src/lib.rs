@@ -0,0 +1,383 @@
+//! Procedural macros for [`propagate`](https://docs.rs/propagate).
+//!
+//! This crate is not meant to be used directly; enable the `derive` feature of
+//! `propagate` and use the macros re-exported from there.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, quote_spanned};
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    parse_macro_input, parse_quote_spanned, Data, DeriveInput, Expr, Fields, FnArg, Item, ItemFn,
+    Lifetime, Pat, ReturnType,
+};
+
+/// Generates traced constructors for each variant of an error enum.
+///
+/// See the documentation of `propagate::Constructors` for more information.
+#[proc_macro_derive(Constructors)]
+pub fn derive_constructors(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand_constructors(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn expand_constructors(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let data = match &input.data {
+        Data::Enum(data) => data,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`Constructors` can only be derived for enums",
+            ))
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let constructors = data.variants.iter().map(|variant| {
+        let variant_name = &variant.ident;
+        let snake = snake_case(&variant_name.to_string());
+        let err_fn = Ident::new(&format!("{}_err", snake), variant_name.span());
+        let traced_fn = Ident::new(&format!("{}_traced", snake), variant_name.span());
+
+        let (params, value) = match &variant.fields {
+            Fields::Named(fields) => {
+                let names: Vec<_> = fields.named.iter().map(|f| &f.ident).collect();
+                let types = fields.named.iter().map(|f| &f.ty);
+                (
+                    quote! { #(#names: #types),* },
+                    quote! { #name::#variant_name { #(#names),* } },
+                )
+            }
+            Fields::Unnamed(fields) => {
+                let names: Vec<_> = (0..fields.unnamed.len())
+                    .map(|i| format_ident!("field{}", i, span = Span::call_site()))
+                    .collect();
+                let types = fields.unnamed.iter().map(|f| &f.ty);
+                (
+                    quote! { #(#names: #types),* },
+                    quote! { #name::#variant_name(#(#names),*) },
+                )
+            }
+            Fields::Unit => (quote! {}, quote! { #name::#variant_name }),
+        };
+
+        let err_doc = format!(
+            "Returns a new error result holding [`{}::{}`], starting a trace at the caller.",
+            name, variant_name
+        );
+        let traced_doc = format!(
+            "Returns a new [`TracedError`](::propagate::TracedError) holding [`{}::{}`], \
+             starting a trace at the caller.",
+            name, variant_name
+        );
+
+        quote! {
+            #[doc = #err_doc]
+            #[inline]
+            #[track_caller]
+            pub fn #err_fn<__T>(#params) -> ::propagate::Result<__T, Self> {
+                ::propagate::Result::new_err(#value)
+            }
+
+            #[doc = #traced_doc]
+            #[inline]
+            #[track_caller]
+            pub fn #traced_fn(#params) -> ::propagate::TracedError<Self> {
+                ::propagate::Result::<::core::convert::Infallible, Self>::new_err(#value).into_err()
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics #name #ty_generics #where_clause {
+            #(#constructors)*
+        }
+    })
+}
+
+/// Records the function in the trace of the errors it returns.
+///
+/// See the documentation of `propagate::traced` for more information.
+#[proc_macro_attribute]
+pub fn traced(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut options = TracedOptions::default();
+    let parser = syn::meta::parser(|meta| options.parse(meta));
+    parse_macro_input!(attr with parser);
+    let function = parse_macro_input!(item as ItemFn);
+    match expand_traced(&options, function) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+/// The options of a `#[traced]` attribute.
+#[derive(Default)]
+struct TracedOptions {
+    /// The arguments to record, and whether each one is redacted.
+    args: Vec<(Ident, bool)>,
+}
+
+impl TracedOptions {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta<'_>) -> syn::Result<()> {
+        if !meta.path.is_ident("args") {
+            return Err(meta.error("unsupported `traced` option, expected `args(...)`"));
+        }
+        meta.parse_nested_meta(|arg| {
+            let redacted = arg.path.is_ident("redact") && arg.input.peek(syn::token::Paren);
+            if redacted {
+                arg.parse_nested_meta(|inner| {
+                    self.args.push((path_ident(&inner.path)?, true));
+                    Ok(())
+                })
+            } else {
+                self.args.push((path_ident(&arg.path)?, false));
+                Ok(())
+            }
+        })
+    }
+}
+
+fn path_ident(path: &syn::Path) -> syn::Result<Ident> {
+    path.get_ident()
+        .cloned()
+        .ok_or_else(|| syn::Error::new_spanned(path, "expected the name of an argument"))
+}
+
+fn expand_traced(options: &TracedOptions, function: ItemFn) -> syn::Result<TokenStream2> {
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = function;
+
+    if let ReturnType::Default = &sig.output {
+        return Err(syn::Error::new_spanned(
+            &sig,
+            "`traced` functions must return a `propagate::Result`",
+        ));
+    }
+
+    for (arg, _) in &options.args {
+        let found = sig.inputs.iter().any(|input| match input {
+            FnArg::Typed(typed) => matches!(&*typed.pat, Pat::Ident(pat) if pat.ident == *arg),
+            FnArg::Receiver(_) => arg == "self",
+        });
+        if !found {
+            return Err(syn::Error::new_spanned(
+                arg,
+                format!("`{}` has no argument named `{}`", sig.ident, arg),
+            ));
+        }
+    }
+
+    // The message is rendered before the body runs, since the body may move
+    // the arguments.
+    let name = sig.ident.to_string();
+    let context = if options.args.is_empty() {
+        quote! { ::core::option::Option::Some(#name) }
+    } else {
+        let mut template = format!("{}(", name);
+        let mut values = Vec::new();
+        for (index, (arg, redacted)) in options.args.iter().enumerate() {
+            if index > 0 {
+                template.push_str(", ");
+            }
+            if *redacted {
+                template.push_str(&format!("{} = [redacted]", arg));
+            } else {
+                template.push_str(&format!("{} = {{:?}}", arg));
+                values.push(arg);
+            }
+        }
+        template.push(')');
+        quote! {
+            if ::propagate::trace::capture_enabled() {
+                ::core::option::Option::Some(::std::format!(#template, #(&#values),*))
+            } else {
+                ::core::option::Option::None
+            }
+        }
+    };
+
+    // The body is expanded in place, rather than in a closure, so that
+    // `#[track_caller]` and `impl Trait` in the signature keep working. Its
+    // `?`s and `return`s become breaks out of a labeled block, so that the
+    // frame is also pushed to the errors that they return.
+    let mut block = block;
+    let mut returns = Returns {
+        label: Lifetime::new("'__propagate_body", Span::call_site()),
+        found: false,
+    };
+    returns.visit_block_mut(&mut block);
+    let stmts = &block.stmts;
+    let body = if returns.found {
+        let label = &returns.label;
+        quote! { #label: { #(#stmts)* } }
+    } else {
+        quote! { { #(#stmts)* } }
+    };
+
+    // The context is pushed at the location of the function's name.
+    let push = quote_spanned! {sig.ident.span()=>
+        ::propagate::result::traced_return(__propagate_result, __propagate_context)
+    };
+
+    Ok(quote! {
+        #(#attrs)*
+        #vis #sig {
+            let __propagate_context = #context;
+            let __propagate_result = #body;
+            #push
+        }
+    })
+}
+
+/// Turns the `?`s and `return`s of the body of a `#[traced]` function into
+/// breaks out of the block labeled `label`, leaving those of the closures,
+/// async blocks and items that it contains alone.
+///
+/// The operands of `?` are converted by the same `Try` implementations as
+/// usual, so the location of each `?` is still pushed to the traces of the
+/// errors that it propagates.
+struct Returns {
+    label: Lifetime,
+    found: bool,
+}
+
+impl VisitMut for Returns {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let label = self.label.clone();
+        let replacement: Expr = match expr {
+            Expr::Closure(_) | Expr::Async(_) | Expr::TryBlock(_) | Expr::Const(_) => return,
+            Expr::Return(ret) => {
+                if let Some(value) = &mut ret.expr {
+                    self.visit_expr_mut(value);
+                }
+                let value = &ret.expr;
+                parse_quote_spanned! {ret.return_token.span=> break #label #value }
+            }
+            Expr::Try(question) => {
+                self.visit_expr_mut(&mut question.expr);
+                let operand = &question.expr;
+                parse_quote_spanned! {question.question_token.span=>
+                    match ::propagate::result::traced_branch(#operand) {
+                        ::core::ops::ControlFlow::Continue(__propagate_value) => __propagate_value,
+                        ::core::ops::ControlFlow::Break(__propagate_residual) => {
+                            let __propagate_error =
+                                ::propagate::result::traced_residual(__propagate_residual);
+                            break #label __propagate_error
+                        }
+                    }
+                }
+            }
+            _ => return visit_mut::visit_expr_mut(self, expr),
+        };
+        *expr = replacement;
+        self.found = true;
+    }
+
+    fn visit_item_mut(&mut self, _: &mut Item) {}
+}
+
+/// Converts an `UpperCamelCase` variant name to `snake_case`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c.is_uppercase() {
+            if prev_lower {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+            prev_lower = false;
+        } else {
+            snake.push(c);
+            prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use quote::quote;
+    use syn::parse::Parser;
+
+    fn expand(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+        let mut options = TracedOptions::default();
+        syn::meta::parser(|meta| options.parse(meta)).parse2(attr)?;
+        expand_traced(&options, syn::parse2(item)?)
+    }
+
+    fn expand_err(attr: TokenStream2, item: TokenStream2) -> String {
+        expand(attr, item).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unknown_args_are_rejected() {
+        let item = quote! {
+            fn load(user_id: u32) -> propagate::Result<(), ()> { propagate::Ok(()) }
+        };
+        assert_eq!(
+            expand_err(quote!(args(user)), item.clone()),
+            "`load` has no argument named `user`"
+        );
+        assert_eq!(
+            expand_err(quote!(args(redact(token))), item.clone()),
+            "`load` has no argument named `token`"
+        );
+        assert_eq!(
+            expand_err(quote!(args(self)), item.clone()),
+            "`load` has no argument named `self`"
+        );
+        assert!(expand(quote!(args(user_id)), item).is_ok());
+    }
+
+    #[test]
+    fn unsupported_options_are_rejected() {
+        let item = quote! {
+            fn load() -> propagate::Result<(), ()> { propagate::Ok(()) }
+        };
+        assert_eq!(
+            expand_err(quote!(level = "debug"), item),
+            "unsupported `traced` option, expected `args(...)`"
+        );
+    }
+
+    #[test]
+    fn functions_without_results_are_rejected() {
+        let item = quote! {
+            fn load() {}
+        };
+        assert_eq!(
+            expand_err(quote!(), item),
+            "`traced` functions must return a `propagate::Result`"
+        );
+    }
+
+    #[test]
+    fn question_marks_of_closures_are_left_alone() {
+        let mut block: syn::Block = syn::parse_quote! {{
+            let parse = |text: &str| -> Option<u32> { text.parse().ok()? };
+            parse("1")?
+        }};
+        let mut returns = Returns {
+            label: Lifetime::new("'body", Span::call_site()),
+            found: false,
+        };
+        returns.visit_block_mut(&mut block);
+        assert!(returns.found);
+
+        let expanded = quote!(#block).to_string();
+        assert_eq!(expanded.matches("traced_branch").count(), 1);
+        assert!(expanded.contains("text . parse () . ok () ?"));
+    }
+}
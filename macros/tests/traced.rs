@@ -0,0 +1,151 @@
+use propagate::{traced, CodeLocation, ErrorTrace, Result};
+
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+/// Runs a future that never waits to completion.
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut cx = Context::from_waker(Waker::noop());
+    match pin!(future).poll(&mut cx) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("the future is not ready"),
+    }
+}
+
+fn messages(trace: &ErrorTrace) -> Vec<&str> {
+    trace.0.iter().filter_map(|frame| frame.message()).collect()
+}
+
+fn fails() -> Result<u32, &'static str> {
+    Result::new_err("Nothing here")
+}
+
+#[traced]
+fn propagates() -> Result<u32, &'static str> {
+    let n = fails()?;
+    propagate::Ok(n + 1)
+}
+
+#[test]
+fn question_mark_pushes_its_location_then_the_frame() {
+    let (_, trace) = propagates().err_trace().unwrap();
+    assert_eq!(trace.0.len(), 3);
+    assert_eq!(trace.0[1].line(), trace.0[2].line() + 1);
+    assert_eq!(messages(&trace), ["propagates"]);
+}
+
+#[traced(args(n))]
+fn returns_early(n: u32) -> Result<u32, &'static str> {
+    if n > 2 {
+        return Result::new_err("too big");
+    }
+    propagate::Ok(n)
+}
+
+#[test]
+fn early_return_pushes_the_frame() {
+    assert_eq!(returns_early(1).ok(), Some(1));
+    let (err, trace) = returns_early(3).err_trace().unwrap();
+    assert_eq!(err, "too big");
+    assert_eq!(messages(&trace), ["returns_early(n = 3)"]);
+}
+
+#[traced]
+fn keeps_closures(values: &[&str]) -> Result<Vec<u32>, std::num::ParseIntError> {
+    let parse = |value: &str| -> std::result::Result<u32, std::num::ParseIntError> {
+        let n = value.parse()?;
+        std::result::Result::Ok(n)
+    };
+    let mut parsed = Vec::new();
+    for value in values {
+        parsed.push(parse(value)?);
+    }
+    propagate::Ok(parsed)
+}
+
+#[test]
+fn question_marks_in_closures_return_from_the_closure() {
+    assert_eq!(keeps_closures(&["1", "2"]).ok(), Some(vec![1, 2]));
+    let (_, trace) = keeps_closures(&["1", "x"]).err_trace().unwrap();
+    assert_eq!(messages(&trace), ["keeps_closures"]);
+}
+
+#[traced(args(id))]
+async fn fetch(id: u32) -> Result<String, &'static str> {
+    async {}.await;
+    let n = fails()?;
+    propagate::Ok(format!("{}: {}", id, n))
+}
+
+#[test]
+fn async_functions_push_the_frame() {
+    let (_, trace) = block_on(fetch(7)).err_trace().unwrap();
+    assert_eq!(trace.0.len(), 3);
+    assert_eq!(messages(&trace), ["fetch(id = 7)"]);
+}
+
+#[derive(Debug)]
+struct Store {
+    name: &'static str,
+}
+
+impl Store {
+    #[traced(args(self, key))]
+    fn get(&self, key: &str) -> Result<u32, &'static str> {
+        let _ = self.name;
+        propagate::Ok(fails()? + key.len() as u32)
+    }
+
+    #[traced]
+    fn into_value(self) -> Result<u32, &'static str> {
+        let value = self.get("a")?;
+        propagate::Ok(value)
+    }
+}
+
+#[test]
+fn methods_push_the_frame() {
+    let store = Store { name: "main" };
+    let (_, trace) = store.get("a").err_trace().unwrap();
+    assert_eq!(
+        messages(&trace),
+        [r#"get(self = Store { name: "main" }, key = "a")"#]
+    );
+
+    let (_, trace) = store.into_value().err_trace().unwrap();
+    assert_eq!(
+        messages(&trace),
+        [r#"get(self = Store { name: "main" }, key = "a")"#, "into_value"]
+    );
+}
+
+#[traced]
+#[track_caller]
+fn fails_at_caller() -> Result<(), &'static str> {
+    Result::new_err("Nothing here")
+}
+
+#[test]
+fn track_caller_records_the_caller() {
+    let caller = CodeLocation::here().down_by(1);
+    let (_, trace) = fails_at_caller().err_trace().unwrap();
+    assert_eq!(trace.0[0], caller);
+    assert_eq!(trace.0[1], caller.with_message("fails_at_caller"));
+}
+
+#[traced]
+fn opaque(fail: bool) -> Result<impl Display, &'static str> {
+    if fail {
+        fails()?;
+    }
+    propagate::Ok(42)
+}
+
+#[test]
+fn impl_trait_results_are_supported() {
+    assert_eq!(opaque(false).unwrap().to_string(), "42");
+    let (_, trace) = opaque(true).err_trace().unwrap();
+    assert_eq!(messages(&trace), ["opaque"]);
+}
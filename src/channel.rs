@@ -0,0 +1,224 @@
+//! Helpers for sending traced results across channels.
+//!
+//! A traced result sent through a channel keeps its trace, but by default
+//! nothing records the fact that it moved from one thread to another. The
+//! helpers in this module push a boundary frame (see
+//! [`Traced::trace_boundary()`]) at the location where the result is received.
+//!
+//! Receive adapters are provided for [`std::sync::mpsc`] receivers, as well as
+//! for [`crossbeam_channel`] receivers when the `crossbeam` feature is enabled.
+//!
+//! [`crossbeam_channel`]: https://docs.rs/crossbeam-channel
+
+use crate::result::Result::{self, Err, Ok};
+use crate::trace::Traced;
+
+use std::panic;
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Marks a result as having been received from a channel, consuming `self`.
+///
+/// If `result` is an error, a boundary frame is pushed to its trace for the
+/// location of the caller. This is useful in places where the receive
+/// operation is not performed by a [`TracedReceiver`], such as the arms of a
+/// `select!`.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::mpsc;
+///
+/// let (tx, rx) = mpsc::channel();
+/// tx.send(propagate::Result::<(), &str>::new_err("oops")).unwrap();
+///
+/// let result = propagate::channel::received(rx.recv().unwrap());
+/// match result {
+///     propagate::Ok(_) => unreachable!(),
+///     propagate::Err(_, trace) => assert_eq!(trace.0.len(), 2),
+/// }
+/// ```
+#[inline]
+#[track_caller]
+pub fn received<T, E, S: Traced>(result: Result<T, E, S>) -> Result<T, E, S> {
+    crossed(result, panic::Location::caller(), "channel")
+}
+
+#[inline]
+fn crossed<T, E, S: Traced>(
+    result: Result<T, E, S>,
+    location: &'static panic::Location<'static>,
+    label: &str,
+) -> Result<T, E, S> {
+    match result {
+        Ok(t) => Ok(t),
+        Err(err, mut trace) => {
+            crate::result::push_boundary::<E, S>(&mut trace, location, label);
+            Err(err, trace)
+        }
+    }
+}
+
+/// Receive adapters for channels that carry traced results.
+///
+/// Each method behaves like its counterpart on the underlying receiver, except
+/// that a received error has a boundary frame pushed to its trace for the
+/// location of the caller.
+pub trait TracedReceiver<T, E, S> {
+    /// The error returned by [`recv_traced()`][TracedReceiver::recv_traced].
+    type RecvError;
+    /// The error returned by [`try_recv_traced()`][TracedReceiver::try_recv_traced].
+    type TryRecvError;
+    /// The error returned by [`recv_timeout_traced()`][TracedReceiver::recv_timeout_traced].
+    type RecvTimeoutError;
+
+    /// Blocks until a result is received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::channel::TracedReceiver;
+    /// use std::sync::mpsc;
+    /// use std::thread;
+    ///
+    /// let (tx, rx) = mpsc::channel();
+    /// thread::spawn(move || {
+    ///     tx.send(propagate::Result::<(), &str>::new_err("oops")).unwrap();
+    /// });
+    ///
+    /// match rx.recv_traced().unwrap() {
+    ///     propagate::Ok(_) => unreachable!(),
+    ///     propagate::Err(_, trace) => assert_eq!(trace.0.len(), 2),
+    /// }
+    /// ```
+    #[track_caller]
+    fn recv_traced(&self) -> std::result::Result<Result<T, E, S>, Self::RecvError>;
+
+    /// Attempts to receive a result without blocking.
+    #[track_caller]
+    fn try_recv_traced(&self) -> std::result::Result<Result<T, E, S>, Self::TryRecvError>;
+
+    /// Blocks until a result is received or the timeout elapses.
+    #[track_caller]
+    fn recv_timeout_traced(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<T, E, S>, Self::RecvTimeoutError>;
+}
+
+impl<T, E, S: Traced> TracedReceiver<T, E, S> for mpsc::Receiver<Result<T, E, S>> {
+    type RecvError = mpsc::RecvError;
+    type TryRecvError = mpsc::TryRecvError;
+    type RecvTimeoutError = mpsc::RecvTimeoutError;
+
+    #[inline]
+    #[track_caller]
+    fn recv_traced(&self) -> std::result::Result<Result<T, E, S>, Self::RecvError> {
+        let location = panic::Location::caller();
+        self.recv()
+            .map(|result| crossed(result, location, "mpsc channel"))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn try_recv_traced(&self) -> std::result::Result<Result<T, E, S>, Self::TryRecvError> {
+        let location = panic::Location::caller();
+        self.try_recv()
+            .map(|result| crossed(result, location, "mpsc channel"))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn recv_timeout_traced(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<T, E, S>, Self::RecvTimeoutError> {
+        let location = panic::Location::caller();
+        self.recv_timeout(timeout)
+            .map(|result| crossed(result, location, "mpsc channel"))
+    }
+}
+
+#[cfg(feature = "crossbeam")]
+impl<T, E, S: Traced> TracedReceiver<T, E, S> for crossbeam_channel::Receiver<Result<T, E, S>> {
+    type RecvError = crossbeam_channel::RecvError;
+    type TryRecvError = crossbeam_channel::TryRecvError;
+    type RecvTimeoutError = crossbeam_channel::RecvTimeoutError;
+
+    #[inline]
+    #[track_caller]
+    fn recv_traced(&self) -> std::result::Result<Result<T, E, S>, Self::RecvError> {
+        let location = panic::Location::caller();
+        self.recv()
+            .map(|result| crossed(result, location, "crossbeam channel"))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn try_recv_traced(&self) -> std::result::Result<Result<T, E, S>, Self::TryRecvError> {
+        let location = panic::Location::caller();
+        self.try_recv()
+            .map(|result| crossed(result, location, "crossbeam channel"))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn recv_timeout_traced(
+        &self,
+        timeout: Duration,
+    ) -> std::result::Result<Result<T, E, S>, Self::RecvTimeoutError> {
+        let location = panic::Location::caller();
+        self.recv_timeout(timeout)
+            .map(|result| crossed(result, location, "crossbeam channel"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::Fixture;
+    use crate::CodeLocation;
+    use std::thread;
+
+    fn send_error(fix: &mut Fixture) -> mpsc::Receiver<Result<(), String>> {
+        let (tx, rx) = mpsc::channel();
+        fix.tag_location("origin", CodeLocation::here().down_by(1));
+        let result = Result::new_err("oops");
+        thread::spawn(move || tx.send(result).unwrap())
+            .join()
+            .unwrap();
+        rx
+    }
+
+    #[test]
+    fn recv_traced_pushes_boundary() {
+        let mut fix = Fixture::default();
+        let rx = send_error(&mut fix);
+
+        let here = CodeLocation::here().down_by(1);
+        let (_, trace) = rx.recv_traced().unwrap().err_trace().unwrap();
+        assert_eq!(trace.0[0], *fix.get_location("origin"));
+        assert_eq!(trace.0[1], here.labeled("mpsc channel"));
+    }
+
+    #[test]
+    fn received_pushes_boundary() {
+        let mut fix = Fixture::default();
+        let rx = send_error(&mut fix);
+
+        let here = CodeLocation::here().down_by(1);
+        let (_, trace) = received(rx.recv().unwrap()).err_trace().unwrap();
+        assert_eq!(trace.0[1], here.labeled("channel"));
+    }
+
+    #[cfg(feature = "crossbeam")]
+    #[test]
+    fn crossbeam_recv_traced_pushes_boundary() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        tx.send(Result::<(), &str>::new_err("oops")).unwrap();
+
+        let here = CodeLocation::here().down_by(1);
+        let (_, trace) = rx.recv_traced().unwrap().err_trace().unwrap();
+        assert_eq!(trace.0[1], here.labeled("crossbeam channel"));
+    }
+}
@@ -0,0 +1,221 @@
+//! Stopping in a debugger when errors are created or propagated.
+//!
+//! When a [`BreakFilter`] is installed with [`set_break_filter()`], every new
+//! error trace whose origin matches the filter (by file, line, and type of the
+//! error) calls [`error_matched()`]. That function is never inlined, so a
+//! debugger breakpoint can be set on it, e.g.:
+//!
+//! ```txt
+//! (gdb) break propagate::debug::error_matched
+//! ```
+//!
+//! Alternatively, the filter can be made to fail a `debug_assert!` when it
+//! matches, which stops a test or a debug build right where the error is
+//! created, with a backtrace pointing at the culprit. Nothing is checked until
+//! a filter is installed.
+//!
+//! # Hook Symbols
+//!
+//! Independently of any filter, every error trace that is started calls
+//! [`propagate_error_created()`], and every location pushed by the `?`
+//! operator calls [`propagate_error_propagated()`]. These functions are never
+//! inlined and their symbol names are not mangled, so breakpoints, gdb
+//! scripts, or dynamic tracing tools such as dtrace and bpftrace can attach to
+//! the flow of errors without modifying the program:
+//!
+//! ```txt
+//! (gdb) break propagate_error_created
+//! $ bpftrace -e 'uprobe:./app:propagate_error_propagated { @[str(arg0, arg1), arg2] = count(); }'
+//! ```
+//!
+//! Both take the file name of the location (as a pointer to UTF-8 bytes and a
+//! length) and its line number, using the C calling convention.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::debug::{self, BreakFilter};
+//!
+//! debug::set_break_filter(BreakFilter::new().file("parser.rs").error_type("ParseIntError"));
+//!
+//! let x: propagate::Result<u32, &str> = propagate::Result::new_err("Nothing here");
+//! # let _ = x;
+//!
+//! debug::clear_break_filter();
+//! ```
+
+use std::any;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// Selects which newly created errors stop the program.
+///
+/// A filter with no criteria matches every error. Each criterion that is set
+/// must match.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BreakFilter {
+    file: Option<String>,
+    line: Option<u32>,
+    error_type: Option<String>,
+    assert: bool,
+}
+
+impl BreakFilter {
+    /// Constructs a filter that matches every error.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches errors created in a file whose path ends with `file`,
+    /// consuming and returning `self`.
+    pub fn file(self, file: impl Into<String>) -> Self {
+        Self {
+            file: Some(file.into()),
+            ..self
+        }
+    }
+
+    /// Only matches errors created on `line`, consuming and returning `self`.
+    pub fn line(self, line: u32) -> Self {
+        Self {
+            line: Some(line),
+            ..self
+        }
+    }
+
+    /// Only matches errors whose type name (as given by
+    /// [`std::any::type_name()`]) contains `error_type`, consuming and
+    /// returning `self`.
+    pub fn error_type(self, error_type: impl Into<String>) -> Self {
+        Self {
+            error_type: Some(error_type.into()),
+            ..self
+        }
+    }
+
+    /// Sets whether a matching error fails a `debug_assert!`, in addition to
+    /// calling [`error_matched()`], consuming and returning `self`.
+    pub fn assert(self, assert: bool) -> Self {
+        Self { assert, ..self }
+    }
+
+    /// Returns `true` if an error of type `error_type` created at `location`
+    /// matches the filter.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::debug::BreakFilter;
+    ///
+    /// let filter = BreakFilter::new().file("src/lib.rs").line(10);
+    /// let location = std::panic::Location::caller();
+    /// assert!(!filter.matches(location, "std::io::error::Error"));
+    /// ```
+    pub fn matches(&self, location: &panic::Location<'_>, error_type: &str) -> bool {
+        self.file
+            .as_ref()
+            .is_none_or(|file| location.file().ends_with(file.as_str()))
+            && self.line.is_none_or(|line| location.line() == line)
+            && self
+                .error_type
+                .as_ref()
+                .is_none_or(|name| error_type.contains(name.as_str()))
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static BREAK_FILTER: RwLock<Option<BreakFilter>> = RwLock::new(None);
+
+/// Installs `filter` to select the errors that stop the program, replacing
+/// any previously installed filter.
+pub fn set_break_filter(filter: BreakFilter) {
+    *BREAK_FILTER.write().unwrap_or_else(|e| e.into_inner()) = Some(filter);
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Removes the installed filter, if any.
+pub fn clear_break_filter() {
+    ENABLED.store(false, Ordering::Release);
+    *BREAK_FILTER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Called when an error matching the installed [`BreakFilter`] is created at
+/// `location`.
+///
+/// This does nothing by itself; it exists so that a debugger breakpoint can be
+/// set on it.
+#[inline(never)]
+pub fn error_matched(location: &'static panic::Location<'static>, error_type: &'static str) {
+    // Keep the call from being optimized away.
+    std::hint::black_box((location, error_type));
+}
+
+/// Called when a new error trace is started at the location in the file named
+/// by the `file_len` bytes at `file`, on `line`.
+///
+/// This does nothing by itself; see [Hook Symbols](self#hook-symbols).
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn propagate_error_created(file: *const u8, file_len: usize, line: u32) {
+    std::hint::black_box((file, file_len, line));
+}
+
+/// Called when the `?` operator pushes the location in the file named by the
+/// `file_len` bytes at `file`, on `line`, to an error trace.
+///
+/// This does nothing by itself; see [Hook Symbols](self#hook-symbols).
+#[no_mangle]
+#[inline(never)]
+pub extern "C" fn propagate_error_propagated(file: *const u8, file_len: usize, line: u32) {
+    std::hint::black_box((file, file_len, line));
+}
+
+/// Reports a new error of type `E` created at `location` to the hook symbol,
+/// and to the installed filter, if any.
+#[inline]
+pub(crate) fn check_created<E>(location: &'static panic::Location<'static>) {
+    let file = location.file();
+    propagate_error_created(file.as_ptr(), file.len(), location.line());
+    if ENABLED.load(Ordering::Acquire) {
+        check(location, any::type_name::<E>());
+    }
+}
+
+/// Calls the hook symbol for an error propagated through `location`.
+#[inline]
+pub(crate) fn propagated(location: &'static panic::Location<'static>) {
+    let file = location.file();
+    propagate_error_propagated(file.as_ptr(), file.len(), location.line());
+}
+
+#[cold]
+fn check(location: &'static panic::Location<'static>, error_type: &'static str) {
+    let assert = match &*BREAK_FILTER.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(filter) if filter.matches(location, error_type) => filter.assert,
+        _ => return,
+    };
+
+    error_matched(location, error_type);
+    debug_assert!(
+        !assert,
+        "created an error of type `{}` at {}, which matches the break filter",
+        error_type, location
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Result;
+
+    #[test]
+    #[should_panic(expected = "created an error of type `&str` at src/debug.rs:")]
+    fn matching_error_fails_assertion() {
+        set_break_filter(BreakFilter::new().file("src/debug.rs").assert(true));
+        let result =
+            panic::catch_unwind(|| -> Result<(), &str> { Result::new_err("Nothing here") });
+        clear_break_filter();
+        panic::resume_unwind(result.unwrap_err());
+    }
+}
@@ -0,0 +1,410 @@
+//! Frame-by-frame comparison of error traces.
+//!
+//! The derived [`Debug`] output of a trace is hard to read when an assertion
+//! on it fails. A [`TraceDiff`] lines up the frames of an expected and an
+//! actual trace and renders them one per line, marking the frames that differ.
+//! The [`assert_trace_eq!`] macro uses it to report failures.
+//!
+//! To compare traces whose frames may have shifted, such as the propagation
+//! paths of the same failure in two versions of a program, [`TraceChanges`]
+//! lines up the frames the traces have in common and reports which frames were
+//! added, removed, or moved.
+//!
+//! [`Debug`]: fmt::Debug
+//! [`assert_trace_eq!`]: crate::assert_trace_eq
+
+use crate::trace::CodeLocation;
+
+use std::fmt;
+
+/// The comparison of one frame of a [`TraceDiff`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameDiff {
+    /// Both traces have the same frame at this position.
+    Same(CodeLocation),
+    /// The traces have different frames at this position.
+    Changed {
+        /// The frame of the expected trace.
+        expected: CodeLocation,
+        /// The frame of the actual trace.
+        actual: CodeLocation,
+    },
+    /// Only the expected trace has a frame at this position.
+    Missing(CodeLocation),
+    /// Only the actual trace has a frame at this position.
+    Unexpected(CodeLocation),
+}
+
+impl FrameDiff {
+    /// Returns `true` if both traces have the same frame at this position.
+    #[inline]
+    pub const fn is_same(&self) -> bool {
+        matches!(self, FrameDiff::Same(_))
+    }
+}
+
+/// A frame-by-frame comparison of an expected and an actual trace, oldest
+/// frame first.
+///
+/// Its [`Display`][fmt::Display] implementation renders one frame per line,
+/// prefixing frames that are only in the expected trace with `-`, and frames
+/// that are only in the actual trace with `+`.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::diff::TraceDiff;
+/// use propagate::CodeLocation;
+///
+/// let expected = [CodeLocation::new("src/a.rs", 1), CodeLocation::new("src/b.rs", 2)];
+/// let actual = [CodeLocation::new("src/a.rs", 1), CodeLocation::new("src/c.rs", 3)];
+///
+/// let diff = TraceDiff::new(&expected, &actual);
+/// assert!(!diff.is_empty());
+/// assert_eq!(
+///     diff.to_string(),
+///     "    0: src/a.rs:1\n  - 1: src/b.rs:2\n  + 1: src/c.rs:3\n",
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceDiff {
+    frames: Vec<FrameDiff>,
+}
+
+impl TraceDiff {
+    /// Compares the frames of `expected` and `actual`, position by position.
+    pub fn new(expected: &[CodeLocation], actual: &[CodeLocation]) -> Self {
+        let len = expected.len().max(actual.len());
+        let frames = (0..len)
+            .map(|index| match (expected.get(index), actual.get(index)) {
+                (Some(e), Some(a)) if e == a => FrameDiff::Same(e.clone()),
+                (Some(e), Some(a)) => FrameDiff::Changed {
+                    expected: e.clone(),
+                    actual: a.clone(),
+                },
+                (Some(e), None) => FrameDiff::Missing(e.clone()),
+                (None, Some(a)) => FrameDiff::Unexpected(a.clone()),
+                (None, None) => unreachable!(),
+            })
+            .collect();
+
+        Self { frames }
+    }
+
+    /// Returns `true` if the traces are identical.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.frames.iter().all(FrameDiff::is_same)
+    }
+
+    /// Returns the comparison of each frame, oldest first.
+    #[inline]
+    pub fn frames(&self) -> &[FrameDiff] {
+        &self.frames
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, frame) in self.frames.iter().enumerate() {
+            match frame {
+                FrameDiff::Same(location) => writeln!(f, "    {}: {}", index, location)?,
+                FrameDiff::Changed { expected, actual } => {
+                    writeln!(f, "  - {}: {}", index, expected)?;
+                    writeln!(f, "  + {}: {}", index, actual)?;
+                }
+                FrameDiff::Missing(location) => writeln!(f, "  - {}: {}", index, location)?,
+                FrameDiff::Unexpected(location) => writeln!(f, "  + {}: {}", index, location)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// How one frame changed between an old and a new trace (see
+/// [`TraceChanges`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameChange {
+    /// The frame is in both traces, in the same order relative to the other
+    /// common frames.
+    Kept(CodeLocation),
+    /// The frame is only in the new trace, at `index`.
+    Added {
+        /// The position of the frame in the new trace.
+        index: usize,
+        /// The added frame.
+        location: CodeLocation,
+    },
+    /// The frame is only in the old trace, at `index`.
+    Removed {
+        /// The position of the frame in the old trace.
+        index: usize,
+        /// The removed frame.
+        location: CodeLocation,
+    },
+    /// The frame is in both traces, but in a different order relative to the
+    /// other common frames.
+    Moved {
+        /// The position of the frame in the old trace.
+        from: usize,
+        /// The position of the frame in the new trace.
+        to: usize,
+        /// The moved frame.
+        location: CodeLocation,
+    },
+}
+
+/// The frames that were added, removed, or moved between an old and a new
+/// trace, in the order of the new trace.
+///
+/// Frames are lined up using the longest sequence of frames the two traces
+/// have in common. A frame that is removed from one place and added in another
+/// is reported as [`Moved`][FrameChange::Moved]. Its
+/// [`Display`][fmt::Display] implementation renders one frame per line,
+/// prefixing added frames with `+`, removed frames with `-`, and moved frames
+/// with `~`. See [`ErrorTrace::diff()`].
+///
+/// [`ErrorTrace::diff()`]: crate::ErrorTrace::diff
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceChanges {
+    changes: Vec<FrameChange>,
+}
+
+impl TraceChanges {
+    /// Compares the frames of an `old` and a `new` trace.
+    pub fn new(old: &[CodeLocation], new: &[CodeLocation]) -> Self {
+        // `common[i][j]` is the length of the longest common subsequence of
+        // `old[i..]` and `new[j..]`.
+        let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                common[i][j] = if old[i] == new[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let mut changes = Vec::with_capacity(old.len().max(new.len()));
+        let (mut i, mut j) = (0, 0);
+        while i < old.len() || j < new.len() {
+            if i < old.len() && j < new.len() && old[i] == new[j] {
+                changes.push(FrameChange::Kept(new[j].clone()));
+                i += 1;
+                j += 1;
+            } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+                changes.push(FrameChange::Added {
+                    index: j,
+                    location: new[j].clone(),
+                });
+                j += 1;
+            } else {
+                changes.push(FrameChange::Removed {
+                    index: i,
+                    location: old[i].clone(),
+                });
+                i += 1;
+            }
+        }
+
+        Self {
+            changes: pair_moves(changes),
+        }
+    }
+
+    /// Returns `true` if no frames were added, removed, or moved.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes
+            .iter()
+            .all(|change| matches!(change, FrameChange::Kept(_)))
+    }
+
+    /// Returns every frame, in the order of the new trace.
+    #[inline]
+    pub fn changes(&self) -> &[FrameChange] {
+        &self.changes
+    }
+
+    /// Returns the frames that are only in the new trace.
+    pub fn added(&self) -> impl Iterator<Item = &CodeLocation> {
+        self.changes.iter().filter_map(|change| match change {
+            FrameChange::Added { location, .. } => Some(location),
+            _ => None,
+        })
+    }
+
+    /// Returns the frames that are only in the old trace.
+    pub fn removed(&self) -> impl Iterator<Item = &CodeLocation> {
+        self.changes.iter().filter_map(|change| match change {
+            FrameChange::Removed { location, .. } => Some(location),
+            _ => None,
+        })
+    }
+
+    /// Returns the frames that are in both traces, but in a different order.
+    pub fn moved(&self) -> impl Iterator<Item = &CodeLocation> {
+        self.changes.iter().filter_map(|change| match change {
+            FrameChange::Moved { location, .. } => Some(location),
+            _ => None,
+        })
+    }
+}
+
+/// Replaces each removed frame that is also added elsewhere with a single
+/// moved frame, at the position where it was added.
+fn pair_moves(mut changes: Vec<FrameChange>) -> Vec<FrameChange> {
+    let mut index = 0;
+    while index < changes.len() {
+        if let FrameChange::Added {
+            index: to,
+            location,
+        } = &changes[index]
+        {
+            let removed = changes.iter().position(|change| {
+                matches!(change, FrameChange::Removed { location: l, .. } if l == location)
+            });
+            if let Some(removed) = removed {
+                let from = match changes[removed] {
+                    FrameChange::Removed { index, .. } => index,
+                    _ => unreachable!(),
+                };
+                changes[index] = FrameChange::Moved {
+                    from,
+                    to: *to,
+                    location: location.clone(),
+                };
+                changes.remove(removed);
+                if removed < index {
+                    continue;
+                }
+            }
+        }
+        index += 1;
+    }
+
+    changes
+}
+
+impl fmt::Display for TraceChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            match change {
+                FrameChange::Kept(location) => writeln!(f, "    {}", location)?,
+                FrameChange::Added { index, location } => {
+                    writeln!(f, "  + {}: {}", index, location)?
+                }
+                FrameChange::Removed { index, location } => {
+                    writeln!(f, "  - {}: {}", index, location)?
+                }
+                FrameChange::Moved { from, to, location } => {
+                    writeln!(f, "  ~ {} -> {}: {}", from, to, location)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Asserts that two traces have the same frames.
+///
+/// Both arguments must dereference to a slice of [`CodeLocation`]s, such as
+/// the contents of an [`ErrorTrace`] or a `Vec<CodeLocation>`. On failure, the
+/// panic message contains a [`TraceDiff`] of the two traces instead of their
+/// [`Debug`] output. Like [`assert_eq!`], a custom message can be provided.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::{assert_trace_eq, CodeLocation};
+///
+/// let expected = vec![CodeLocation::here().down_by(1)];
+/// let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+///
+/// let (_, trace) = result.err_trace().unwrap();
+/// assert_trace_eq!(trace.0, expected);
+/// ```
+///
+/// [`CodeLocation`]: crate::CodeLocation
+/// [`ErrorTrace`]: crate::ErrorTrace
+/// [`Debug`]: std::fmt::Debug
+#[macro_export]
+macro_rules! assert_trace_eq {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::assert_trace_eq!($actual, $expected, "traces are not equal")
+    };
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {
+        match (&$actual, &$expected) {
+            (actual, expected) => {
+                let diff = $crate::diff::TraceDiff::new(&expected[..], &actual[..]);
+                if !diff.is_empty() {
+                    ::std::panic!(
+                        "{} (- expected, + actual):\n{}",
+                        ::std::format_args!($($arg)+),
+                        diff,
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn loc(line: u32) -> CodeLocation {
+        CodeLocation::new("src/diff.rs", line)
+    }
+
+    #[test]
+    fn lines_up_frames_by_position() {
+        let diff = TraceDiff::new(&[loc(1), loc(2)], &[loc(1), loc(3), loc(4)]);
+        assert_eq!(
+            diff.frames(),
+            &[
+                FrameDiff::Same(loc(1)),
+                FrameDiff::Changed {
+                    expected: loc(2),
+                    actual: loc(3)
+                },
+                FrameDiff::Unexpected(loc(4)),
+            ]
+        );
+    }
+
+    #[test]
+    fn pairs_removed_and_added_frames_as_moves() {
+        let changes =
+            TraceChanges::new(&[loc(1), loc(2), loc(3)], &[loc(2), loc(3), loc(1), loc(4)]);
+        assert_eq!(
+            changes.changes(),
+            &[
+                FrameChange::Kept(loc(2)),
+                FrameChange::Kept(loc(3)),
+                FrameChange::Moved {
+                    from: 0,
+                    to: 2,
+                    location: loc(1)
+                },
+                FrameChange::Added {
+                    index: 3,
+                    location: loc(4)
+                },
+            ]
+        );
+        assert_eq!(
+            changes.to_string(),
+            "    src/diff.rs:2\n    src/diff.rs:3\n  ~ 0 -> 2: src/diff.rs:1\n  + 3: src/diff.rs:4\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "  - 1: src/diff.rs:2\n")]
+    fn assert_trace_eq_panics_with_diff() {
+        assert_trace_eq!(vec![loc(1)], vec![loc(1), loc(2)]);
+    }
+}
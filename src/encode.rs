@@ -0,0 +1,724 @@
+//! Compact binary encoding of many traces.
+//!
+//! Services that export every error trace as telemetry send the same file
+//! names over and over, since most frames come from a handful of source files.
+//! A [`TraceEncoder`] writes each file name once, in a dictionary at the start
+//! of its output, and encodes each frame as an index into that dictionary and
+//! the difference from the line number of the previous frame, using
+//! variable-length integers. The output can be read back with [`decode()`].
+//!
+//! # Format
+//!
+//! All integers are unsigned LEB128 varints; line deltas are zigzag-encoded
+//! first. The output consists of:
+//!
+//! 1. The format version (currently 1).
+//! 2. The number of file names, followed by each name as a length and UTF-8
+//!    bytes.
+//! 3. The number of traces, followed by each trace as a number of frames, and
+//!    for each frame: the index of its file name, its line delta, and its
+//!    [`FrameKind`] (0 for a location, 1 for a message, 2 for a boundary,
+//!    followed by the message or label as a length and UTF-8 bytes).
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::encode::{self, TraceEncoder};
+//! use propagate::{CodeLocation, ErrorTrace};
+//!
+//! let trace = ErrorTrace(vec![
+//!     CodeLocation::new("src/server/handlers.rs", 120),
+//!     CodeLocation::new("src/server/handlers.rs", 134),
+//!     CodeLocation::new("src/server/mod.rs", 58),
+//! ]);
+//!
+//! let mut encoder = TraceEncoder::new();
+//! for _ in 0..100 {
+//!     encoder.encode(&trace);
+//! }
+//! let bytes = encoder.finish();
+//! assert!(bytes.len() < 100 * trace.to_string().len() / 4);
+//!
+//! let decoded = encode::decode(&bytes).unwrap();
+//! assert_eq!(decoded.len(), 100);
+//! assert_eq!(decoded.frames(99).unwrap()[2].to_string(), "src/server/mod.rs:58");
+//! ```
+//!
+//! [`FrameKind`]: crate::FrameKind
+
+use crate::redact::{self, Redactor};
+use crate::trace::{FrameKind, Traced};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+const VERSION: u64 = 1;
+
+/// The number of bytes taken by a trace with no frames, which is the smallest
+/// [maximum trace size](TraceEncoder::max_trace_size).
+pub const EMPTY_TRACE_SIZE: usize = 1;
+
+const KIND_LOCATION: u64 = 0;
+const KIND_MESSAGE: u64 = 1;
+const KIND_BOUNDARY: u64 = 2;
+
+/// Encodes traces into a compact binary form.
+///
+/// See the [module documentation](self) for the format.
+#[derive(Clone, Debug, Default)]
+pub struct TraceEncoder {
+    files: Vec<Cow<'static, str>>,
+    indices: HashMap<Cow<'static, str>, u64>,
+    count: u64,
+    traces: Vec<u8>,
+    max_trace_size: Option<usize>,
+    truncated: u64,
+}
+
+/// A frame about to be encoded, after redaction.
+struct Frame<'a> {
+    file: Cow<'static, str>,
+    line: u32,
+    kind: u64,
+    note: Option<Cow<'a, str>>,
+}
+
+impl TraceEncoder {
+    /// Constructs an encoder with no traces.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limits the number of bytes that each trace adds to the output to
+    /// `bytes`, consuming and returning `self`.
+    ///
+    /// The size of a trace includes the file names that it adds to the
+    /// dictionary. A trace that does not fit is truncated by the following
+    /// rules, applied in order until it fits:
+    ///
+    /// 1. Frames are dropped from the middle of the trace, one at a time,
+    ///    until only the oldest and the newest frames are left.
+    /// 2. The notes (context messages and boundary labels) of the remaining
+    ///    frames are dropped, oldest first.
+    /// 3. The newest frame is dropped, then the oldest.
+    ///
+    /// The same trace is always truncated in the same way, given the same
+    /// dictionary. A trace with no frames takes [`EMPTY_TRACE_SIZE`] bytes, so
+    /// smaller limits are raised to that.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::encode::{self, TraceEncoder};
+    /// use propagate::{CodeLocation, ErrorTrace};
+    ///
+    /// let trace: ErrorTrace = (1..=100)
+    ///     .map(|line| CodeLocation::new("src/lib.rs", line))
+    ///     .collect();
+    ///
+    /// let mut encoder = TraceEncoder::new().max_trace_size(64);
+    /// encoder.encode(&trace);
+    /// assert_eq!(encoder.truncated(), 1);
+    ///
+    /// let decoded = encode::decode(&encoder.finish()).unwrap();
+    /// let frames = decoded.frames(0).unwrap();
+    /// assert!(frames.len() < 100);
+    /// assert_eq!(frames.first().unwrap().to_string(), "src/lib.rs:1");
+    /// assert_eq!(frames.last().unwrap().to_string(), "src/lib.rs:100");
+    /// ```
+    pub fn max_trace_size(self, bytes: usize) -> Self {
+        Self {
+            max_trace_size: Some(bytes.max(EMPTY_TRACE_SIZE)),
+            ..self
+        }
+    }
+
+    /// Adds the frames of `stack` to the output.
+    ///
+    /// Stacks that do not keep their frames (see [`Traced::frames()`]) are
+    /// encoded as empty traces. File names and notes are passed through the
+    /// installed [redactor](crate::redact), if any, and the trace is
+    /// truncated to the [maximum size](Self::max_trace_size), if any.
+    pub fn encode<S: Traced + ?Sized>(&mut self, stack: &S) {
+        let locations: Vec<_> = stack.frames().into_iter().flatten().collect();
+        let redactor = redact::redactor();
+        let mut frames: Vec<Frame<'_>> = locations
+            .iter()
+            .map(|location| {
+                let file = match &redactor {
+                    Some(redactor) => redactor.redact_path(location.file()),
+                    None => Cow::Borrowed(location.file()),
+                };
+                let (kind, note) = match location.kind() {
+                    FrameKind::Location => (KIND_LOCATION, None),
+                    FrameKind::Message(message) => (KIND_MESSAGE, Some(message)),
+                    FrameKind::Boundary { label } => (KIND_BOUNDARY, Some(label)),
+                };
+                Frame {
+                    file,
+                    line: location.line(),
+                    kind,
+                    note: note.map(|note| redact_note(redactor.as_deref(), note)),
+                }
+            })
+            .collect();
+
+        let (mut bytes, mut new_files) = self.encode_frames(&frames);
+        if let Some(limit) = self.max_trace_size {
+            let fits = |bytes: &[u8], new_files: &[Cow<'static, str>]| {
+                bytes.len() + new_files.iter().map(|file| str_size(file)).sum::<usize>() <= limit
+            };
+            if !fits(&bytes, &new_files) {
+                self.truncated += 1;
+                let (prefix, suffix) = self.middle_to_keep(&frames, limit);
+                frames.drain(prefix..frames.len() - suffix);
+                let (truncated_bytes, truncated_files) = self.encode_frames(&frames);
+                bytes = truncated_bytes;
+                new_files = truncated_files;
+            }
+            // At most two frames are left, so the rest is cheap to re-encode.
+            while !fits(&bytes, &new_files) && !frames.is_empty() {
+                if let Some(frame) = frames.iter_mut().find(|frame| frame.note.is_some()) {
+                    frame.kind = KIND_LOCATION;
+                    frame.note = None;
+                } else {
+                    frames.pop();
+                }
+                let (truncated_bytes, truncated_files) = self.encode_frames(&frames);
+                bytes = truncated_bytes;
+                new_files = truncated_files;
+            }
+        }
+
+        for file in new_files {
+            self.indices.insert(file.clone(), self.files.len() as u64);
+            self.files.push(file);
+        }
+        self.traces.extend_from_slice(&bytes);
+        self.count += 1;
+    }
+
+    /// Returns the number of traces that were truncated to fit the
+    /// [maximum size](Self::max_trace_size).
+    pub fn truncated(&self) -> usize {
+        self.truncated as usize
+    }
+
+    /// Returns how many of the oldest and newest `frames` to keep, after
+    /// dropping frames from the middle of the trace until it fits in `limit`
+    /// bytes or only two frames are left.
+    ///
+    /// Dropping the middle frame one at a time always leaves a prefix and a
+    /// suffix of the trace, so its size is updated as each frame is dropped
+    /// instead of encoding it again. The indices of new file names are
+    /// assumed to be the largest they can be, so the size is exact unless the
+    /// dictionary has more than 127 names, and never too small.
+    fn middle_to_keep(&self, frames: &[Frame<'_>], limit: usize) -> (usize, usize) {
+        let len = frames.len();
+        let mut new_file_uses: HashMap<&str, usize> = HashMap::new();
+        for frame in frames {
+            if !self.indices.contains_key(&frame.file) {
+                *new_file_uses.entry(&frame.file).or_insert(0) += 1;
+            }
+        }
+        let new_index_size = varint_size((self.files.len() + new_file_uses.len()) as u64);
+        let frame_size = |index: usize, previous: Option<usize>| {
+            let frame = &frames[index];
+            let file_index_size = match self.indices.get(&frame.file) {
+                Some(&file_index) => varint_size(file_index),
+                None => new_index_size,
+            };
+            let previous_line = previous.map_or(0, |previous| i64::from(frames[previous].line));
+            file_index_size
+                + varint_size(zigzag(i64::from(frame.line) - previous_line))
+                + varint_size(frame.kind)
+                + frame.note.as_deref().map_or(0, str_size)
+        };
+
+        let mut size = varint_size(len as u64)
+            + new_file_uses
+                .keys()
+                .map(|file| str_size(file))
+                .sum::<usize>()
+            + (0..len)
+                .map(|index| frame_size(index, index.checked_sub(1)))
+                .sum::<usize>();
+
+        // The kept frames are `frames[..prefix]` and `frames[len - suffix..]`.
+        let mut prefix = len.div_ceil(2);
+        let mut suffix = len - prefix;
+        while size > limit && prefix + suffix > 2 {
+            let kept = prefix + suffix - 1;
+            let dropped = if kept.div_ceil(2) < prefix {
+                prefix -= 1;
+                prefix
+            } else {
+                suffix -= 1;
+                len - suffix - 1
+            };
+            let previous = prefix.checked_sub(1);
+            let next = (suffix > 0).then(|| len - suffix);
+
+            size -= frame_size(dropped, previous);
+            if let Some(next) = next {
+                size = size - frame_size(next, Some(dropped)) + frame_size(next, previous);
+            }
+            size = size - varint_size(kept as u64 + 1) + varint_size(kept as u64);
+            if let Some(uses) = new_file_uses.get_mut(&*frames[dropped].file) {
+                *uses -= 1;
+                if *uses == 0 {
+                    size -= str_size(&frames[dropped].file);
+                }
+            }
+        }
+        (prefix, suffix)
+    }
+
+    /// Encodes `frames`, returning the encoded trace and the file names that
+    /// it would add to the dictionary.
+    fn encode_frames(&self, frames: &[Frame<'_>]) -> (Vec<u8>, Vec<Cow<'static, str>>) {
+        let mut bytes = Vec::new();
+        let mut new_files: Vec<Cow<'static, str>> = Vec::new();
+        write_varint(&mut bytes, frames.len() as u64);
+
+        let mut previous_line = 0i64;
+        for frame in frames {
+            let index = match self.indices.get(&frame.file) {
+                Some(&index) => index,
+                None => match new_files.iter().position(|file| *file == frame.file) {
+                    Some(position) => (self.files.len() + position) as u64,
+                    None => {
+                        new_files.push(frame.file.clone());
+                        (self.files.len() + new_files.len() - 1) as u64
+                    }
+                },
+            };
+            write_varint(&mut bytes, index);
+
+            let line = i64::from(frame.line);
+            write_varint(&mut bytes, zigzag(line - previous_line));
+            previous_line = line;
+
+            write_varint(&mut bytes, frame.kind);
+            if let Some(note) = &frame.note {
+                write_str(&mut bytes, note);
+            }
+        }
+
+        (bytes, new_files)
+    }
+
+    /// Returns the number of traces encoded so far.
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Returns `true` if no traces have been encoded.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the encoded dictionary and traces, consuming `self`.
+    pub fn finish(self) -> Vec<u8> {
+        let mut output = Vec::with_capacity(self.traces.len() + 16 * self.files.len());
+        write_varint(&mut output, VERSION);
+        write_varint(&mut output, self.files.len() as u64);
+        for file in &self.files {
+            write_str(&mut output, file);
+        }
+        write_varint(&mut output, self.count);
+        output.extend_from_slice(&self.traces);
+        output
+    }
+}
+
+/// A frame read back by [`decode()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedFrame<'a> {
+    file: &'a str,
+    line: u32,
+    note: &'a DecodedNote,
+}
+
+/// The message or label of a decoded frame, owned by the [`DecodedTraces`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum DecodedNote {
+    Location,
+    Message(String),
+    Boundary(String),
+}
+
+impl<'a> DecodedFrame<'a> {
+    /// Returns the name of the source file.
+    pub fn file(&self) -> &'a str {
+        self.file
+    }
+
+    /// Returns the line number in the source file.
+    pub const fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the message attached to this frame, if it is a
+    /// [`Message`][FrameKind::Message] frame.
+    pub fn message(&self) -> Option<&'a str> {
+        match self.note {
+            DecodedNote::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Returns the label of this frame, if it is a
+    /// [`Boundary`][FrameKind::Boundary] frame.
+    pub fn label(&self) -> Option<&'a str> {
+        match self.note {
+            DecodedNote::Boundary(label) => Some(label),
+            _ => None,
+        }
+    }
+}
+
+/// Renders the frame like a [`CodeLocation`][crate::CodeLocation].
+impl fmt::Display for DecodedFrame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        match self.note {
+            DecodedNote::Location => Ok(()),
+            DecodedNote::Message(message) => write!(f, ": {}", message),
+            DecodedNote::Boundary(label) => write!(f, " ({})", label),
+        }
+    }
+}
+
+/// The traces read back by [`decode()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedTraces {
+    files: Vec<String>,
+    traces: Vec<Vec<(usize, u32, DecodedNote)>>,
+}
+
+impl DecodedTraces {
+    /// Returns the number of traces.
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    /// Returns `true` if there are no traces.
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    /// Returns the frames of the trace at `index`, oldest first, or `None` if
+    /// out of bounds.
+    pub fn frames(&self, index: usize) -> Option<Vec<DecodedFrame<'_>>> {
+        let trace = self.traces.get(index)?;
+        Some(
+            trace
+                .iter()
+                .map(|(file, line, note)| DecodedFrame {
+                    file: &self.files[*file],
+                    line: *line,
+                    note,
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The error returned by [`decode()`] when its input is not a valid encoding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DecodeError {
+    reason: &'static str,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid encoded traces: {}", self.reason)
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Reads back traces written by a [`TraceEncoder`].
+pub fn decode(bytes: &[u8]) -> std::result::Result<DecodedTraces, DecodeError> {
+    let mut reader = Reader { bytes };
+    if reader.varint()? != VERSION {
+        return Err(DecodeError {
+            reason: "unsupported version",
+        });
+    }
+
+    let file_count = reader.varint()?;
+    let mut files = Vec::new();
+    for _ in 0..file_count {
+        files.push(reader.string()?);
+    }
+
+    let trace_count = reader.varint()?;
+    let mut traces = Vec::new();
+    for _ in 0..trace_count {
+        let frame_count = reader.varint()?;
+        let mut frames = Vec::new();
+        let mut line = 0i64;
+        for _ in 0..frame_count {
+            let file = reader.varint()? as usize;
+            if file >= files.len() {
+                return Err(DecodeError {
+                    reason: "file index out of range",
+                });
+            }
+            let out_of_range = DecodeError {
+                reason: "line number out of range",
+            };
+            line = line
+                .checked_add(unzigzag(reader.varint()?))
+                .ok_or(out_of_range)?;
+            let line = u32::try_from(line).map_err(|_| out_of_range)?;
+            let kind = match reader.varint()? {
+                KIND_LOCATION => DecodedNote::Location,
+                KIND_MESSAGE => DecodedNote::Message(reader.string()?),
+                KIND_BOUNDARY => DecodedNote::Boundary(reader.string()?),
+                _ => {
+                    return Err(DecodeError {
+                        reason: "unknown frame kind",
+                    })
+                }
+            };
+            frames.push((file, line, kind));
+        }
+        traces.push(frames);
+    }
+
+    if !reader.bytes.is_empty() {
+        return Err(DecodeError {
+            reason: "trailing bytes",
+        });
+    }
+
+    Ok(DecodedTraces { files, traces })
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn write_varint(output: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        output.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+/// Passes `note` through `redactor`, if any.
+fn redact_note<'a>(redactor: Option<&dyn Redactor>, note: &'a str) -> Cow<'a, str> {
+    match redactor {
+        Some(redactor) => redactor.redact_note(note),
+        None => Cow::Borrowed(note),
+    }
+}
+
+/// Returns the number of bytes that `value` takes as a varint.
+fn varint_size(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    bits.max(1).div_ceil(7)
+}
+
+/// Returns the number of bytes that [`write_str()`] writes for `value`.
+fn str_size(value: &str) -> usize {
+    varint_size(value.len() as u64) + value.len()
+}
+
+fn write_str(output: &mut Vec<u8>, value: &str) {
+    write_varint(output, value.len() as u64);
+    output.extend_from_slice(value.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn varint(&mut self) -> std::result::Result<u64, DecodeError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.bytes.split_first().ok_or(DecodeError {
+                reason: "unexpected end of input",
+            })?;
+            self.bytes = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(DecodeError {
+            reason: "varint is too long",
+        })
+    }
+
+    fn string(&mut self) -> std::result::Result<String, DecodeError> {
+        let len = self.varint()? as usize;
+        if len > self.bytes.len() {
+            return Err(DecodeError {
+                reason: "unexpected end of input",
+            });
+        }
+        let (bytes, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError {
+            reason: "string is not UTF-8",
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CodeLocation, ErrorTrace};
+
+    #[test]
+    fn round_trips_frames_and_kinds() {
+        let first = ErrorTrace(vec![
+            CodeLocation::new("src/a.rs", 300),
+            CodeLocation::new("src/b.rs", 2).with_message("loading config"),
+            CodeLocation::new("src/a.rs", 1).labeled("thread 'worker'"),
+        ]);
+        let second = ErrorTrace(vec![CodeLocation::new("src/b.rs", 40)]);
+
+        let mut encoder = TraceEncoder::new();
+        encoder.encode(&first);
+        encoder.encode(&second);
+        encoder.encode(&crate::CountingStack::new());
+        let decoded = decode(&encoder.finish()).unwrap();
+
+        let rendered = |index| -> Vec<String> {
+            let frames = decoded.frames(index).unwrap();
+            frames.iter().map(ToString::to_string).collect()
+        };
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(rendered(0), first.to_strings());
+        assert_eq!(rendered(1), second.to_strings());
+        assert!(rendered(2).is_empty());
+    }
+
+    #[test]
+    fn truncates_middle_frames_then_notes() {
+        let trace = ErrorTrace(vec![
+            CodeLocation::new("src/a.rs", 1).with_message("opening the database"),
+            CodeLocation::new("src/b.rs", 2),
+            CodeLocation::new("src/c.rs", 3),
+            CodeLocation::new("src/a.rs", 4).labeled("thread 'worker'"),
+        ]);
+        let encode = |limit| {
+            let mut encoder = TraceEncoder::new().max_trace_size(limit);
+            encoder.encode(&trace);
+            let decoded = decode(&encoder.finish()).unwrap();
+            let frames = decoded.frames(0).unwrap();
+            frames.iter().map(ToString::to_string).collect::<Vec<_>>()
+        };
+
+        assert_eq!(encode(1000), trace.to_strings());
+        assert_eq!(
+            encode(60),
+            vec![
+                "src/a.rs:1: opening the database",
+                "src/a.rs:4 (thread 'worker')"
+            ]
+        );
+        assert_eq!(
+            encode(35),
+            vec!["src/a.rs:1", "src/a.rs:4 (thread 'worker')"]
+        );
+        assert_eq!(encode(16), vec!["src/a.rs:1", "src/a.rs:4"]);
+        assert_eq!(encode(14), vec!["src/a.rs:1"]);
+        assert!(encode(0).is_empty());
+    }
+
+    #[test]
+    fn truncated_traces_fit_in_the_smallest_limits() {
+        let trace = ErrorTrace(vec![CodeLocation::new("src/a.rs", 1)]);
+        for limit in [0, 1] {
+            let mut encoder = TraceEncoder::new().max_trace_size(limit);
+            encoder.encode(&trace);
+            encoder.encode(&trace);
+            assert_eq!(encoder.truncated(), 2);
+
+            let bytes = encoder.finish();
+            assert_eq!(bytes.len(), [VERSION, 0, 2].len() + 2 * EMPTY_TRACE_SIZE);
+            let decoded = decode(&bytes).unwrap();
+            assert_eq!(decoded.len(), 2);
+            assert!(decoded.frames(0).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn truncation_keeps_the_same_frames_as_dropping_one_at_a_time() {
+        let files = ["src/a.rs", "src/b.rs", "src/c/d.rs"];
+        let trace: ErrorTrace = [7, 300, 2, 9000, 1, 45, 46, 12000, 3, 8, 70]
+            .iter()
+            .enumerate()
+            .map(|(index, &line)| CodeLocation::new(files[index % 3], line))
+            .collect();
+        // Subtracts the version, the number of file names and the number of
+        // traces from the output of an encoder with one trace.
+        let trace_size = |bytes: &[u8]| bytes.len() - [VERSION, 0, 1].len();
+        let encode = |trace: &ErrorTrace, limit: Option<usize>| {
+            let mut encoder = TraceEncoder::new();
+            encoder.max_trace_size = limit;
+            encoder.encode(trace);
+            encoder.finish()
+        };
+
+        for limit in 1..=80 {
+            let bytes = encode(&trace, Some(limit));
+            assert!(trace_size(&bytes) <= limit);
+
+            let mut expected = ErrorTrace(trace.0.clone());
+            while expected.0.len() > 2 && trace_size(&encode(&expected, None)) > limit {
+                expected.0.remove(expected.0.len() / 2);
+            }
+            if trace_size(&encode(&expected, None)) <= limit {
+                let decoded = decode(&bytes).unwrap();
+                let frames = decoded.frames(0).unwrap();
+                let frames: Vec<String> = frames.iter().map(ToString::to_string).collect();
+                assert_eq!(frames, expected.to_strings(), "limit {}", limit);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_line_delta_overflow() {
+        let mut bytes = Vec::new();
+        for value in [VERSION, 1, 1, b'a'.into(), 1, 2] {
+            write_varint(&mut bytes, value);
+        }
+        for delta in [1, i64::MAX] {
+            write_varint(&mut bytes, 0);
+            write_varint(&mut bytes, zigzag(delta));
+            write_varint(&mut bytes, KIND_LOCATION);
+        }
+
+        assert_eq!(
+            decode(&bytes),
+            Err(DecodeError {
+                reason: "line number out of range"
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_input() {
+        let mut encoder = TraceEncoder::new();
+        encoder.encode(&ErrorTrace(vec![CodeLocation::new("src/a.rs", 1)]));
+        let bytes = encoder.finish();
+        assert!(decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
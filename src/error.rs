@@ -0,0 +1,1342 @@
+//! Defines error types used alongside traced results.
+
+use crate::trace::{ErrorTrace, Traced};
+
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::iter::FromIterator;
+use std::ops;
+use std::panic;
+use std::slice;
+use std::sync::RwLock;
+use std::vec;
+
+/// An error value together with its associated error trace.
+///
+/// This is the owned form of the contents of an [`Err`][crate::Err] result,
+/// obtained with [`Result::traced_err()`][crate::Result::traced_err]. It
+/// displays as the inner error, and forwards [`Error::source()`] to it.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::TracedError;
+///
+/// let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+/// let traced: TracedError<&str> = result.traced_err().unwrap();
+///
+/// assert_eq!(*traced.error(), "Nothing here");
+/// assert_eq!(traced.stack().0.len(), 1);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct TracedError<E, S = ErrorTrace> {
+    error: E,
+    stack: S,
+}
+
+impl<E, S> TracedError<E, S> {
+    /// Constructs a traced error from an error value and a trace.
+    ///
+    /// This and the accessors for the error and trace are `const`, so traced
+    /// errors can be inspected during const evaluation:
+    ///
+    /// ```
+    /// use propagate::TracedError;
+    ///
+    /// const ERROR: TracedError<u32, ()> = TracedError::new(404, ());
+    /// const _: () = assert!(*ERROR.error() == 404);
+    /// ```
+    #[inline]
+    pub const fn new(error: E, stack: S) -> Self {
+        Self { error, stack }
+    }
+
+    /// Returns a reference to the error value.
+    #[inline]
+    pub const fn error(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns a mutable reference to the error value.
+    #[inline]
+    pub fn error_mut(&mut self) -> &mut E {
+        &mut self.error
+    }
+
+    /// Returns a reference to the error trace.
+    #[inline]
+    pub const fn stack(&self) -> &S {
+        &self.stack
+    }
+
+    /// Returns a mutable reference to the error trace.
+    #[inline]
+    pub fn stack_mut(&mut self) -> &mut S {
+        &mut self.stack
+    }
+
+    /// Applies `f` to the error trace, returning `self`.
+    ///
+    /// This allows a trace to be annotated or trimmed in the middle of a chain
+    /// of calls.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::CodeLocation;
+    ///
+    /// let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+    /// let traced = result
+    ///     .traced_err()
+    ///     .unwrap()
+    ///     .modify_stack(|trace| trace.0.push(CodeLocation::new("synthetic.rs", 1)));
+    /// assert_eq!(traced.stack().0.len(), 2);
+    /// ```
+    #[inline]
+    pub fn modify_stack<F: FnOnce(&mut S)>(mut self, f: F) -> Self {
+        f(&mut self.stack);
+        self
+    }
+
+    /// Returns `true` if the error value matches a predicate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::{ErrorTrace, TracedError};
+    /// use std::io::{Error, ErrorKind};
+    ///
+    /// let traced = TracedError::new(Error::new(ErrorKind::NotFound, "!"), ErrorTrace::new());
+    /// assert!(traced.error_is(|e| e.kind() == ErrorKind::NotFound));
+    /// assert!(!traced.error_is(|e| e.kind() == ErrorKind::TimedOut));
+    /// ```
+    #[must_use]
+    #[inline]
+    pub fn error_is<P: FnOnce(&E) -> bool>(&self, predicate: P) -> bool {
+        predicate(&self.error)
+    }
+
+    /// Returns the error value, consuming `self` and discarding the trace.
+    #[inline]
+    pub fn into_error(self) -> E {
+        self.error
+    }
+
+    /// Splits `self` into the error value and the error trace.
+    #[inline]
+    pub fn into_parts(self) -> (E, S) {
+        (self.error, self.stack)
+    }
+
+    /// Converts `self` into an [`Err`][crate::Err] result.
+    #[inline]
+    pub fn into_result<T>(self) -> crate::Result<T, E, S> {
+        crate::Err(self.error, self.stack)
+    }
+}
+
+impl<E> TracedError<E> {
+    /// Returns a stable 64-bit fingerprint of the propagation path of this
+    /// error.
+    ///
+    /// See [`ErrorTrace::fingerprint()`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// fn gives_error() -> propagate::Result<(), &'static str> {
+    ///     propagate::Result::new_err("Nothing here")
+    /// }
+    ///
+    /// let first = gives_error().traced_err().unwrap();
+    /// let second = gives_error().traced_err().unwrap();
+    /// assert_eq!(first.fingerprint(), second.fingerprint());
+    /// ```
+    #[inline]
+    pub fn fingerprint(&self) -> u64 {
+        self.stack.fingerprint()
+    }
+
+    /// Returns the number of locations in the trace of this error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+    /// assert_eq!(result.traced_err().unwrap().depth(), 1);
+    /// ```
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.stack.depth()
+    }
+}
+
+impl<E, S: Traced> TracedError<E, S> {
+    /// Returns how long ago the error was created, if its stack records it
+    /// (see [`TimedStack`][crate::TimedStack]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::{ErrorTrace, TimedStack, TracedError};
+    ///
+    /// let timed = TracedError::new("Nothing here", TimedStack::<ErrorTrace>::new());
+    /// assert!(timed.age().is_some());
+    ///
+    /// let untimed = TracedError::new("Nothing here", ErrorTrace::new());
+    /// assert_eq!(untimed.age(), None);
+    /// ```
+    #[inline]
+    pub fn age(&self) -> Option<std::time::Duration> {
+        self.stack.created_at().map(|created| created.elapsed())
+    }
+}
+
+impl<S: Traced + Default> TracedError<BoxError, S> {
+    /// Constructs a traced error from a boxed error, starting a new trace at
+    /// the caller's location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::BoxError;
+    /// use propagate::TracedError;
+    ///
+    /// let boxed: BoxError = "connection reset".into();
+    /// let traced: TracedError<BoxError> = TracedError::from_boxed(boxed);
+    /// assert_eq!(traced.to_string(), "connection reset");
+    /// assert_eq!(traced.stack().depth(), 1);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn from_boxed(error: BoxError) -> Self {
+        let stack = crate::result::new_trace::<BoxError, S>(panic::Location::caller());
+        Self::new(error, stack)
+    }
+}
+
+impl<S: Traced + Default> TracedError<io::Error, S> {
+    /// Constructs a traced error from an OS error code (e.g., an `errno`
+    /// value), starting a new trace at the caller's location.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::TracedError;
+    /// use std::io;
+    ///
+    /// let traced: TracedError<io::Error> = TracedError::from_os_error(2);
+    /// assert_eq!(traced.raw_os_error(), Some(2));
+    /// assert_eq!(traced.stack().depth(), 1);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn from_os_error(code: i32) -> Self {
+        let stack = crate::result::new_trace::<io::Error, S>(panic::Location::caller());
+        Self::new(io::Error::from_raw_os_error(code), stack)
+    }
+
+    /// Constructs a traced error from the last OS error that occurred on the
+    /// current thread (see [`io::Error::last_os_error()`]), starting a new
+    /// trace at the caller's location.
+    ///
+    /// This is meant to be called right after a failed system call.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::TracedError;
+    /// use std::io;
+    ///
+    /// let traced: TracedError<io::Error> = TracedError::last_os_error();
+    /// assert!(traced.raw_os_error().is_some());
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn last_os_error() -> Self {
+        // Read the error before anything else can overwrite it.
+        let error = io::Error::last_os_error();
+        let stack = crate::result::new_trace::<io::Error, S>(panic::Location::caller());
+        Self::new(error, stack)
+    }
+}
+
+impl<S> TracedError<io::Error, S> {
+    /// Returns the OS error code that the error was created from, if any (see
+    /// [`io::Error::raw_os_error()`]).
+    #[inline]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        self.error.raw_os_error()
+    }
+}
+
+impl<E: fmt::Display, S> fmt::Display for TracedError<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl<E: Error, S> TracedError<E, S> {
+    /// Returns the last error in the chain of sources of the error value, or
+    /// the error value itself if it has no source.
+    ///
+    /// This lets handlers inspect the underlying failure, however many layers
+    /// of wrapping errors sit on top of it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::{ErrorTrace, TracedError, WrappedError};
+    /// use std::io;
+    ///
+    /// let io_error = io::Error::from(io::ErrorKind::NotFound);
+    /// let error = WrappedError::new("failed to load config", io_error);
+    /// let traced = TracedError::new(error, ErrorTrace::new());
+    ///
+    /// let root = traced.root_cause().downcast_ref::<io::Error>().unwrap();
+    /// assert_eq!(root.kind(), io::ErrorKind::NotFound);
+    /// ```
+    pub fn root_cause(&self) -> &(dyn Error + 'static)
+    where
+        E: 'static,
+    {
+        let mut cause: &(dyn Error + 'static) = &self.error;
+        while let Some(source) = cause.source() {
+            cause = source;
+        }
+        cause
+    }
+}
+
+impl<E: Error, S: fmt::Debug> Error for TracedError<E, S> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+impl<E, S> TracedError<E, S> {
+    /// Wraps `self` in a [`TransparentError`], which formats exactly like the
+    /// error value.
+    #[inline]
+    pub fn transparent(self) -> TransparentError<E, S> {
+        TransparentError(self)
+    }
+}
+
+/// A traced error that formats exactly like its error value.
+///
+/// Both the [`Display`][fmt::Display] and [`Debug`][fmt::Debug] output of a
+/// `TransparentError` are those of the inner error, so switching a codebase to
+/// propagate does not change existing assertions, golden logs, or messages
+/// shown to users. The trace is still accessible through the methods of
+/// [`TracedError`], which `TransparentError` dereferences to.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::TransparentError;
+///
+/// let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+/// let err: TransparentError<&str> = result.traced_err().unwrap().transparent();
+///
+/// assert_eq!(format!("{:?}", err), "\"Nothing here\"");
+/// assert_eq!(err.stack().0.len(), 1);
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct TransparentError<E, S = ErrorTrace>(pub TracedError<E, S>);
+
+impl<E, S> TransparentError<E, S> {
+    /// Returns the traced error, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> TracedError<E, S> {
+        self.0
+    }
+}
+
+impl<E, S> From<TracedError<E, S>> for TransparentError<E, S> {
+    #[inline]
+    fn from(error: TracedError<E, S>) -> Self {
+        Self(error)
+    }
+}
+
+impl<E, S> ops::Deref for TransparentError<E, S> {
+    type Target = TracedError<E, S>;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<E, S> ops::DerefMut for TransparentError<E, S> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<E: fmt::Debug, S> fmt::Debug for TransparentError<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0.error, f)
+    }
+}
+
+impl<E: fmt::Display, S> fmt::Display for TransparentError<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0.error, f)
+    }
+}
+
+impl<E: Error, S> Error for TransparentError<E, S> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.error.source()
+    }
+}
+
+/// A type-erased error, as returned by many third-party APIs.
+///
+/// Results with this error type can be propagated into a traced result with
+/// the same error type (or any error type that implements
+/// `From<BoxError>`) using the `?` operator, which starts a new trace:
+///
+/// ```
+/// use propagate::error::BoxError;
+///
+/// fn third_party() -> Result<u32, BoxError> {
+///     Err("connection reset".into())
+/// }
+///
+/// fn traced() -> propagate::Result<u32, BoxError> {
+///     propagate::Ok(third_party()?)
+/// }
+///
+/// let (err, trace) = traced().err_trace().unwrap();
+/// assert_eq!(err.to_string(), "connection reset");
+/// assert_eq!(trace.depth(), 1);
+/// ```
+///
+/// It is also the simplest error type for application glue code: since every
+/// error type that implements [`Error`] + [`Send`] + [`Sync`] (as well as
+/// strings) converts into a `BoxError`, a function returning
+/// `propagate::Result<T, BoxError>` can apply `?` to any traced or std result
+/// without a `From` impl per error type. Traced results keep their trace, and
+/// std results start a new one:
+///
+/// ```
+/// use propagate::error::BoxError;
+/// use std::io;
+///
+/// fn read_config() -> propagate::Result<String, io::Error> {
+///     propagate::Result::new_err(io::Error::from(io::ErrorKind::NotFound))
+/// }
+///
+/// fn run() -> propagate::Result<u16, BoxError> {
+///     let config = read_config()?;
+///     let port = config.trim().parse::<u16>()?;
+///     if port == 0 {
+///         return propagate::Result::new_err("port must not be zero");
+///     }
+///     propagate::Ok(port)
+/// }
+///
+/// let (err, trace) = run().err_trace().unwrap();
+/// assert!(err.downcast_ref::<io::Error>().is_some());
+/// assert_eq!(trace.depth(), 2);
+/// ```
+pub type BoxError = Box<dyn Error + Send + Sync + 'static>;
+
+/// A collection of errors, each with its own associated error trace.
+///
+/// This is produced by operations that can observe more than one failure at a
+/// time, such as [`try_join_all`][crate::future::try_join_all]. Each error
+/// keeps the trace it had accumulated before it was added to the set.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::{ErrorSet, ErrorTrace};
+///
+/// let mut errors: ErrorSet<&str> = ErrorSet::new();
+/// errors.push("first", ErrorTrace::new());
+/// errors.push("second", ErrorTrace::new());
+///
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors.errors().collect::<Vec<_>>(), vec![&"first", &"second"]);
+/// ```
+#[derive(PartialEq, Eq, Debug, Hash)]
+pub struct ErrorSet<E, S = ErrorTrace>(pub Vec<(E, S)>);
+
+impl<E, S> ErrorSet<E, S> {
+    /// Constructs a new, empty error set.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// Adds an error and its trace to the set.
+    #[inline]
+    pub fn push(&mut self, error: E, trace: S) {
+        self.0.push((error, trace));
+    }
+
+    /// Returns the number of errors in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the set contains no errors.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the errors and their traces.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, (E, S)> {
+        self.0.iter()
+    }
+
+    /// Returns an iterator over just the errors in the set.
+    #[inline]
+    pub fn errors(&self) -> impl Iterator<Item = &E> {
+        self.0.iter().map(|(err, _)| err)
+    }
+
+    /// Returns an iterator over just the traces in the set.
+    #[inline]
+    pub fn traces(&self) -> impl Iterator<Item = &S> {
+        self.0.iter().map(|(_, trace)| trace)
+    }
+}
+
+impl<E, S> Default for ErrorSet<E, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, S> IntoIterator for ErrorSet<E, S> {
+    type Item = (E, S);
+    type IntoIter = vec::IntoIter<(E, S)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, E, S> IntoIterator for &'a ErrorSet<E, S> {
+    type Item = &'a (E, S);
+    type IntoIter = slice::Iter<'a, (E, S)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<E, S> Extend<(E, S)> for ErrorSet<E, S> {
+    fn extend<I: IntoIterator<Item = (E, S)>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl<E, S> FromIterator<(E, S)> for ErrorSet<E, S> {
+    fn from_iter<I: IntoIterator<Item = (E, S)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<E: fmt::Display, S: fmt::Display> fmt::Display for ErrorSet<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error(s):", self.0.len())?;
+        for (index, (err, trace)) in self.0.iter().enumerate() {
+            write!(f, "\n\n[{}] {}\nReturn Trace: {}", index, err, trace)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An [`Error`] made up of several failures, each with its own trace.
+///
+/// This is a thin wrapper around an [`ErrorSet`], for use as the error type of
+/// batch APIs that report every failure rather than just the first. It
+/// displays as a numbered report of the contained errors and their traces.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::AggregateError;
+/// use propagate::ErrorTrace;
+/// use std::io;
+///
+/// let mut errors: AggregateError<io::Error> = AggregateError::new();
+/// errors.push(io::ErrorKind::NotFound.into(), ErrorTrace::new());
+/// errors.push(io::ErrorKind::TimedOut.into(), ErrorTrace::new());
+///
+/// let report = errors.to_string();
+/// assert!(report.starts_with("2 error(s):"));
+/// assert!(report.contains("[1] timed out"));
+/// ```
+#[derive(PartialEq, Eq, Debug, Hash)]
+pub struct AggregateError<E, S = ErrorTrace>(pub ErrorSet<E, S>);
+
+impl<E, S> AggregateError<E, S> {
+    /// Constructs a new aggregate error containing no errors.
+    #[inline]
+    pub const fn new() -> Self {
+        Self(ErrorSet::new())
+    }
+
+    /// Adds an error and its trace.
+    #[inline]
+    pub fn push(&mut self, error: E, trace: S) {
+        self.0.push(error, trace);
+    }
+
+    /// Returns the number of contained errors.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if there are no contained errors.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the errors and their traces.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, (E, S)> {
+        self.0.iter()
+    }
+
+    /// Returns an iterator over just the errors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::AggregateError;
+    /// use propagate::ErrorTrace;
+    ///
+    /// let errors: AggregateError<&str> = vec![
+    ///     ("first", ErrorTrace::new()),
+    ///     ("second", ErrorTrace::new()),
+    /// ]
+    /// .into_iter()
+    /// .collect();
+    ///
+    /// assert_eq!(errors.errors().collect::<Vec<_>>(), vec![&"first", &"second"]);
+    /// ```
+    #[inline]
+    pub fn errors(&self) -> impl Iterator<Item = &E> {
+        self.0.errors()
+    }
+
+    /// Returns an iterator over just the traces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::AggregateError;
+    /// use propagate::ErrorTrace;
+    ///
+    /// let errors: AggregateError<&str> = vec![("first", ErrorTrace::new())]
+    ///     .into_iter()
+    ///     .collect();
+    ///
+    /// assert_eq!(errors.traces().count(), 1);
+    /// ```
+    #[inline]
+    pub fn traces(&self) -> impl Iterator<Item = &S> {
+        self.0.traces()
+    }
+
+    /// Returns the underlying error set, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> ErrorSet<E, S> {
+        self.0
+    }
+}
+
+impl<E, S> Default for AggregateError<E, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E, S> From<ErrorSet<E, S>> for AggregateError<E, S> {
+    #[inline]
+    fn from(errors: ErrorSet<E, S>) -> Self {
+        Self(errors)
+    }
+}
+
+impl<E, S> IntoIterator for AggregateError<E, S> {
+    type Item = (E, S);
+    type IntoIter = vec::IntoIter<(E, S)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, E, S> IntoIterator for &'a AggregateError<E, S> {
+    type Item = &'a (E, S);
+    type IntoIter = slice::Iter<'a, (E, S)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<E, S> Extend<(E, S)> for AggregateError<E, S> {
+    fn extend<I: IntoIterator<Item = (E, S)>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+impl<E, S> FromIterator<(E, S)> for AggregateError<E, S> {
+    fn from_iter<I: IntoIterator<Item = (E, S)>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+impl<E: fmt::Display, S: fmt::Display> fmt::Display for AggregateError<E, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl<E, S> Error for AggregateError<E, S>
+where
+    E: fmt::Display + fmt::Debug,
+    S: fmt::Display + fmt::Debug,
+{
+}
+
+/// An error wrapped in an outer error or message.
+///
+/// This is produced by [`Result::wrap_err()`][crate::Result::wrap_err]. Its
+/// [`Display`][fmt::Display] implementation shows only the wrapper, while
+/// [`Error::source()`] returns the original error, so error reports show both.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::WrappedError;
+/// use std::error::Error;
+/// use std::io;
+///
+/// let err = io::Error::new(io::ErrorKind::NotFound, "no such file");
+/// let wrapped = WrappedError::new("failed to load config", err);
+///
+/// assert_eq!(wrapped.to_string(), "failed to load config");
+/// assert_eq!(wrapped.source().unwrap().to_string(), "no such file");
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct WrappedError<D, E> {
+    wrapper: D,
+    error: E,
+}
+
+impl<D, E> WrappedError<D, E> {
+    /// Wraps `error` in `wrapper`.
+    #[inline]
+    pub const fn new(wrapper: D, error: E) -> Self {
+        Self { wrapper, error }
+    }
+
+    /// Returns a reference to the wrapper.
+    #[inline]
+    pub const fn wrapper(&self) -> &D {
+        &self.wrapper
+    }
+
+    /// Returns a reference to the original error.
+    #[inline]
+    pub const fn inner(&self) -> &E {
+        &self.error
+    }
+
+    /// Returns the original error, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> E {
+        self.error
+    }
+}
+
+impl<D: fmt::Display, E> fmt::Display for WrappedError<D, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.wrapper, f)
+    }
+}
+
+impl<D, E> Error for WrappedError<D, E>
+where
+    D: fmt::Display + fmt::Debug,
+    E: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An adapter that lets any [`Debug`][fmt::Debug] error be reported from
+/// `main`.
+///
+/// Returning a [`propagate::Result`] from `main` requires the error type to
+/// implement [`Error`]. Errors such as [`String`] or plain enums can be
+/// wrapped in a `DebugError`, which implements [`Error`] by formatting the
+/// inner error using [`Debug`][fmt::Debug]. Since `DebugError<E>` implements
+/// `From<E>`, the `?` operator performs the wrapping automatically.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::DebugError;
+///
+/// #[derive(Debug)]
+/// enum MyError {
+///     TooSmall(u64),
+/// }
+///
+/// fn check(size: u64) -> propagate::Result<u64, MyError> {
+///     if size < 1024 {
+///         return propagate::Result::new_err(MyError::TooSmall(size));
+///     }
+///     propagate::Ok(size)
+/// }
+///
+/// fn main() -> propagate::Result<(), DebugError<MyError>> {
+///     let size = check(4096)?;
+///     println!("size: {}", size);
+///     propagate::Ok(())
+/// }
+/// ```
+///
+/// [`propagate::Result`]: crate::Result
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct DebugError<E>(pub E);
+
+impl<E> DebugError<E> {
+    /// Returns the inner error, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> E {
+        self.0
+    }
+}
+
+impl<E> From<E> for DebugError<E> {
+    #[inline]
+    fn from(error: E) -> Self {
+        Self(error)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for DebugError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl<E: fmt::Debug> Error for DebugError<E> {}
+
+/// A stable code identifying a kind of error (e.g., `E1234`).
+///
+/// Codes let support teams refer to failures without reading traces. Once the
+/// error type is registered with [`register_error_code()`], its code is shown
+/// in front of its message (e.g. `E1234: connection refused`) when it is
+/// returned from `main`, in [`ErrorReport`]s and crash reports, and as a field
+/// of the entries written by structured sinks. An error can also be wrapped in
+/// a [`CodedError`] to include its code whenever it is displayed.
+///
+/// [`ErrorReport`]: crate::report::ErrorReport
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::ErrorCode;
+/// use std::borrow::Cow;
+///
+/// enum NetError {
+///     Refused,
+///     Timeout,
+/// }
+///
+/// impl ErrorCode for NetError {
+///     fn code(&self) -> Cow<'_, str> {
+///         match self {
+///             NetError::Refused => "E1234".into(),
+///             NetError::Timeout => "E1235".into(),
+///         }
+///     }
+/// }
+/// ```
+pub trait ErrorCode {
+    /// Returns the code of this error.
+    fn code(&self) -> Cow<'_, str>;
+}
+
+impl<E: ErrorCode, S> TracedError<E, S> {
+    /// Returns the code of the error value.
+    #[inline]
+    pub fn code(&self) -> Cow<'_, str> {
+        self.error.code()
+    }
+}
+
+/// An adapter that prefixes an error's [`code`][ErrorCode::code] to its
+/// message.
+///
+/// `CodedError<E>` displays as `CODE: message`, and forwards
+/// [`Error::source()`] to the inner error. Since it implements `From<E>`, the
+/// `?` operator performs the wrapping automatically, e.g. when returning from
+/// `main`.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::{CodedError, ErrorCode};
+/// use std::borrow::Cow;
+/// use std::fmt;
+///
+/// #[derive(Debug)]
+/// struct Refused;
+///
+/// impl fmt::Display for Refused {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "connection refused")
+///     }
+/// }
+///
+/// impl std::error::Error for Refused {}
+///
+/// impl ErrorCode for Refused {
+///     fn code(&self) -> Cow<'_, str> {
+///         "E1234".into()
+///     }
+/// }
+///
+/// fn connect() -> propagate::Result<(), CodedError<Refused>> {
+///     propagate::Result::new_err(Refused)
+/// }
+///
+/// assert_eq!(connect().unwrap_err().to_string(), "E1234: connection refused");
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct CodedError<E>(pub E);
+
+impl<E> CodedError<E> {
+    /// Returns the inner error, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> E {
+        self.0
+    }
+}
+
+impl<E> From<E> for CodedError<E> {
+    #[inline]
+    fn from(error: E) -> Self {
+        Self(error)
+    }
+}
+
+impl<E: ErrorCode> ErrorCode for CodedError<E> {
+    fn code(&self) -> Cow<'_, str> {
+        self.0.code()
+    }
+}
+
+impl<E: ErrorCode + fmt::Display> fmt::Display for CodedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.0.code(), self.0)
+    }
+}
+
+impl<E: ErrorCode + Error> Error for CodedError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
+
+/// Looks up the code of a registered error type.
+#[derive(Copy, Clone)]
+struct CodeLookup {
+    type_id: TypeId,
+    of_error: fn(&(dyn Error + 'static)) -> Option<String>,
+    #[cfg(any(feature = "syslog", all(feature = "journald", unix)))]
+    of_any: fn(&dyn std::any::Any) -> Option<String>,
+}
+
+static ERROR_CODES: RwLock<Vec<CodeLookup>> = RwLock::new(Vec::new());
+
+/// Registers `E` as a type whose [`code`][ErrorCode::code] is shown in
+/// reports of its values.
+///
+/// Reports only see errors as trait objects, so the types with codes have to
+/// be registered, typically at the start of `main`. Registering a type more
+/// than once has no effect. [`CodedError`]s should not be registered, since
+/// they already show their code in their message.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::{self, ErrorCode};
+/// use propagate::report::ErrorReport;
+/// use std::borrow::Cow;
+/// use std::fmt;
+///
+/// #[derive(Debug)]
+/// struct Refused;
+///
+/// impl fmt::Display for Refused {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "connection refused")
+///     }
+/// }
+///
+/// impl std::error::Error for Refused {}
+///
+/// impl ErrorCode for Refused {
+///     fn code(&self) -> Cow<'_, str> {
+///         "E1234".into()
+///     }
+/// }
+///
+/// error::register_error_code::<Refused>();
+/// assert_eq!(error::error_code(&Refused).as_deref(), Some("E1234"));
+/// assert_eq!(ErrorReport::new(&Refused).to_string(), "E1234: connection refused");
+/// ```
+pub fn register_error_code<E: ErrorCode + Error + 'static>() {
+    fn of_error<E: ErrorCode + Error + 'static>(error: &(dyn Error + 'static)) -> Option<String> {
+        error
+            .downcast_ref::<E>()
+            .map(|error| error.code().into_owned())
+    }
+    #[cfg(any(feature = "syslog", all(feature = "journald", unix)))]
+    fn of_any<E: ErrorCode + 'static>(error: &dyn std::any::Any) -> Option<String> {
+        error
+            .downcast_ref::<E>()
+            .map(|error| error.code().into_owned())
+    }
+
+    let type_id = TypeId::of::<E>();
+    let mut codes = ERROR_CODES.write().unwrap_or_else(|e| e.into_inner());
+    if codes.iter().all(|registered| registered.type_id != type_id) {
+        codes.push(CodeLookup {
+            type_id,
+            of_error: of_error::<E>,
+            #[cfg(any(feature = "syslog", all(feature = "journald", unix)))]
+            of_any: of_any::<E>,
+        });
+    }
+}
+
+/// Returns the code of `error`, if its type was registered with
+/// [`register_error_code()`].
+pub fn error_code(error: &(dyn Error + 'static)) -> Option<String> {
+    let codes = ERROR_CODES.read().unwrap_or_else(|e| e.into_inner());
+    codes.iter().find_map(|lookup| (lookup.of_error)(error))
+}
+
+/// Returns the code of `error`, if its type was registered with
+/// [`register_error_code()`].
+#[cfg(any(feature = "syslog", all(feature = "journald", unix)))]
+pub(crate) fn code_of<E: 'static>(error: &E) -> Option<String> {
+    let codes = ERROR_CODES.read().unwrap_or_else(|e| e.into_inner());
+    codes.iter().find_map(|lookup| (lookup.of_any)(error))
+}
+
+/// The broad kind of a failure, which decides how it should be handled.
+///
+/// See [`Category`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
+pub enum ErrorCategory {
+    /// The request or input was invalid. Retrying will fail the same way, and
+    /// the error can be shown to the user without a trace.
+    User,
+    /// Something is wrong with the program or its environment. Retrying will
+    /// most likely fail the same way, and the trace is needed to fix it.
+    Internal,
+    /// The operation may succeed if it is retried later, e.g. after a timeout
+    /// or a dropped connection.
+    Transient,
+}
+
+impl ErrorCategory {
+    /// Returns `true` if an operation that failed with an error of this
+    /// category may succeed if it is retried.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::ErrorCategory;
+    ///
+    /// assert!(ErrorCategory::Transient.is_retryable());
+    /// assert!(!ErrorCategory::User.is_retryable());
+    /// ```
+    #[inline]
+    pub const fn is_retryable(self) -> bool {
+        matches!(self, ErrorCategory::Transient)
+    }
+
+    /// Returns `true` if an error of this category should be reported with
+    /// its trace, i.e. if it is not the user's fault.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::ErrorCategory;
+    ///
+    /// assert!(ErrorCategory::Internal.shows_trace());
+    /// assert!(!ErrorCategory::User.shows_trace());
+    /// ```
+    #[inline]
+    pub const fn shows_trace(self) -> bool {
+        !matches!(self, ErrorCategory::User)
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorCategory::User => "user error",
+            ErrorCategory::Internal => "internal error",
+            ErrorCategory::Transient => "transient error",
+        })
+    }
+}
+
+/// An error that knows its [`ErrorCategory`].
+///
+/// Retry layers can branch on [`is_retryable()`][ErrorCategory::is_retryable],
+/// and user-facing layers on [`shows_trace()`][ErrorCategory::shows_trace],
+/// without downcasting to concrete error types. An error that implements
+/// `Category` can be wrapped in a [`CategorizedError`] to include its category
+/// whenever it is displayed, including when it is returned from `main`.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::{Category, ErrorCategory};
+///
+/// enum FetchError {
+///     BadUrl,
+///     Timeout,
+/// }
+///
+/// impl Category for FetchError {
+///     fn category(&self) -> ErrorCategory {
+///         match self {
+///             FetchError::BadUrl => ErrorCategory::User,
+///             FetchError::Timeout => ErrorCategory::Transient,
+///         }
+///     }
+/// }
+///
+/// fn fetch() -> propagate::Result<String, FetchError> {
+///     propagate::Result::new_err(FetchError::Timeout)
+/// }
+///
+/// let mut attempts = 0;
+/// let result = loop {
+///     attempts += 1;
+///     match fetch() {
+///         propagate::Err(err, _) if err.category().is_retryable() && attempts < 3 => continue,
+///         result => break result,
+///     }
+/// };
+/// assert!(result.is_err());
+/// assert_eq!(attempts, 3);
+/// ```
+pub trait Category {
+    /// Returns the category of this error.
+    fn category(&self) -> ErrorCategory;
+}
+
+impl<E: Category, S> TracedError<E, S> {
+    /// Returns the category of the error value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::{Category, ErrorCategory};
+    ///
+    /// struct Timeout;
+    ///
+    /// impl Category for Timeout {
+    ///     fn category(&self) -> ErrorCategory {
+    ///         ErrorCategory::Transient
+    ///     }
+    /// }
+    ///
+    /// let result: propagate::Result<(), Timeout> = propagate::Result::new_err(Timeout);
+    /// assert_eq!(result.traced_err().unwrap().category(), ErrorCategory::Transient);
+    /// ```
+    #[inline]
+    pub fn category(&self) -> ErrorCategory {
+        self.error.category()
+    }
+}
+
+/// Looks up the category of a registered error type.
+#[derive(Copy, Clone)]
+struct CategoryLookup {
+    type_id: TypeId,
+    of_error: fn(&(dyn Error + 'static)) -> Option<ErrorCategory>,
+}
+
+static ERROR_CATEGORIES: RwLock<Vec<CategoryLookup>> = RwLock::new(Vec::new());
+
+/// Registers `E` as a type whose [`category`][Category::category] decides how
+/// its values are reported.
+///
+/// Like [`register_error_code()`], this is needed since reports only see
+/// errors as trait objects. Reports of errors of registered types are headed
+/// by their category, and do not include the trace if the category does not
+/// [show one][ErrorCategory::shows_trace]. Registering a type more than once
+/// has no effect. [`CategorizedError`]s should not be registered, since they
+/// already show their category in their message.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::{self, Category, ErrorCategory};
+/// use propagate::report::ErrorReport;
+/// use std::fmt;
+///
+/// #[derive(Debug)]
+/// struct BadUrl;
+///
+/// impl fmt::Display for BadUrl {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "invalid URL")
+///     }
+/// }
+///
+/// impl std::error::Error for BadUrl {}
+///
+/// impl Category for BadUrl {
+///     fn category(&self) -> ErrorCategory {
+///         ErrorCategory::User
+///     }
+/// }
+///
+/// error::register_error_category::<BadUrl>();
+/// assert_eq!(error::error_category(&BadUrl), Some(ErrorCategory::User));
+///
+/// let report = ErrorReport::new(&BadUrl);
+/// assert_eq!(report.to_string(), "user error: invalid URL");
+/// assert!(!report.shows_trace());
+/// ```
+pub fn register_error_category<E: Category + Error + 'static>() {
+    fn of_error<E: Category + Error + 'static>(
+        error: &(dyn Error + 'static),
+    ) -> Option<ErrorCategory> {
+        error.downcast_ref::<E>().map(Category::category)
+    }
+
+    let type_id = TypeId::of::<E>();
+    let mut categories = ERROR_CATEGORIES.write().unwrap_or_else(|e| e.into_inner());
+    if categories
+        .iter()
+        .all(|registered| registered.type_id != type_id)
+    {
+        categories.push(CategoryLookup {
+            type_id,
+            of_error: of_error::<E>,
+        });
+    }
+}
+
+/// Returns the category of `error`, if its type was registered with
+/// [`register_error_category()`].
+pub fn error_category(error: &(dyn Error + 'static)) -> Option<ErrorCategory> {
+    let categories = ERROR_CATEGORIES.read().unwrap_or_else(|e| e.into_inner());
+    categories
+        .iter()
+        .find_map(|lookup| (lookup.of_error)(error))
+}
+
+/// An adapter that prefixes an error's [`category`][Category::category] to
+/// its message.
+///
+/// `CategorizedError<E>` displays as `category: message`, and forwards
+/// [`Error::source()`] to the inner error. Since it implements `From<E>`, the
+/// `?` operator performs the wrapping automatically, e.g. when returning from
+/// `main`, so that the category appears in the heading of the report. For
+/// errors that are not wrapped, see [`register_error_category()`].
+///
+/// # Examples
+///
+/// ```
+/// use propagate::error::{CategorizedError, Category, ErrorCategory};
+/// use std::fmt;
+///
+/// #[derive(Debug)]
+/// struct Timeout;
+///
+/// impl fmt::Display for Timeout {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         write!(f, "connection timed out")
+///     }
+/// }
+///
+/// impl std::error::Error for Timeout {}
+///
+/// impl Category for Timeout {
+///     fn category(&self) -> ErrorCategory {
+///         ErrorCategory::Transient
+///     }
+/// }
+///
+/// fn connect() -> propagate::Result<(), CategorizedError<Timeout>> {
+///     propagate::Result::new_err(Timeout)
+/// }
+///
+/// assert_eq!(
+///     connect().unwrap_err().to_string(),
+///     "transient error: connection timed out"
+/// );
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+pub struct CategorizedError<E>(pub E);
+
+impl<E> CategorizedError<E> {
+    /// Returns the inner error, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> E {
+        self.0
+    }
+}
+
+impl<E> From<E> for CategorizedError<E> {
+    #[inline]
+    fn from(error: E) -> Self {
+        Self(error)
+    }
+}
+
+impl<E: Category> Category for CategorizedError<E> {
+    fn category(&self) -> ErrorCategory {
+        self.0.category()
+    }
+}
+
+impl<E: Category + fmt::Display> fmt::Display for CategorizedError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.0.category(), self.0)
+    }
+}
+
+impl<E: Category + Error> Error for CategorizedError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.0.source()
+    }
+}
@@ -0,0 +1,254 @@
+//! Exporting the flow of errors for visualization.
+//!
+//! An [`ErrorFlow`] aggregates the traces of errors as they are logged, and
+//! writes them out in formats understood by profiling tools, so the paths
+//! errors take through a codebase can be explored as a flamegraph:
+//!
+//! * The folded-stack format of [`write_folded()`][ErrorFlow::write_folded],
+//!   which is read by `inferno-flamegraph` and `flamegraph.pl`.
+//! * The Chrome trace-event format of
+//!   [`write_chrome_trace()`][ErrorFlow::write_chrome_trace], which is read by
+//!   `chrome://tracing`, Perfetto and speedscope.
+//!
+//! In both, the root of each stack is the last location an error reached, and
+//! the leaves are the locations where errors were created, so the widest
+//! leaves are the most common origins of errors.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::export::ErrorFlow;
+//!
+//! fn load() -> propagate::Result<(), &'static str> {
+//!     propagate::Result::new_err("Nothing here")
+//! }
+//!
+//! let flow = ErrorFlow::new();
+//! for _ in 0..3 {
+//!     if let Some(err) = load().traced_err() {
+//!         flow.record(err.stack());
+//!     }
+//! }
+//!
+//! let mut folded = Vec::new();
+//! flow.write_folded(&mut folded).unwrap();
+//! assert!(String::from_utf8(folded).unwrap().ends_with(" 3\n"));
+//! ```
+
+use crate::redact;
+use crate::trace::Traced;
+
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Mutex;
+
+/// Aggregates error traces for export to profiling tools.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Debug, Default)]
+pub struct ErrorFlow {
+    stacks: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl ErrorFlow {
+    /// Constructs an empty flow.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the frames of `stack` to the flow.
+    ///
+    /// Stacks that do not keep their frames (see [`Traced::frames()`]) are
+    /// ignored. Frames are passed through the installed
+    /// [redactor](crate::redact), if any.
+    pub fn record<S: Traced + ?Sized>(&self, stack: &S) {
+        let frames = match stack.frames() {
+            Some(frames) => frames,
+            None => return,
+        };
+        let redactor = redact::redactor();
+        let mut path: Vec<String> = frames
+            .map(|frame| redact::display_frame(&frame, redactor.as_deref()))
+            .collect();
+        if path.is_empty() {
+            return;
+        }
+        path.reverse();
+
+        *self.lock().entry(path).or_insert(0) += 1;
+    }
+
+    /// Returns the number of traces recorded.
+    pub fn len(&self) -> u64 {
+        self.lock().values().sum()
+    }
+
+    /// Returns `true` if no traces have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Writes the recorded traces in folded-stack format: one line per
+    /// distinct trace, with its frames separated by `;` and followed by the
+    /// number of times it was recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::export::ErrorFlow;
+    /// use propagate::{CodeLocation, ErrorTrace};
+    ///
+    /// let flow = ErrorFlow::new();
+    /// flow.record(&ErrorTrace(vec![
+    ///     CodeLocation::new("src/parser.rs", 88),
+    ///     CodeLocation::new("src/main.rs", 12),
+    /// ]));
+    ///
+    /// let mut folded = Vec::new();
+    /// flow.write_folded(&mut folded).unwrap();
+    /// assert_eq!(folded, b"src/main.rs:12;src/parser.rs:88 1\n");
+    /// ```
+    pub fn write_folded<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        for (path, count) in self.lock().iter() {
+            let names: Vec<String> = path.iter().map(|frame| folded_name(frame)).collect();
+            writeln!(writer, "{} {}", names.join(";"), count)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the recorded traces as a JSON array of Chrome trace events.
+    ///
+    /// Each frame becomes a complete (`"X"`) event nested inside the event of
+    /// the frame it propagated to, lasting one microsecond per recorded trace
+    /// that passed through it, so the result displays as a flame chart.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::export::ErrorFlow;
+    /// use propagate::{CodeLocation, ErrorTrace};
+    ///
+    /// let flow = ErrorFlow::new();
+    /// flow.record(&ErrorTrace(vec![CodeLocation::new("src/main.rs", 12)]));
+    ///
+    /// let mut json = Vec::new();
+    /// flow.write_chrome_trace(&mut json).unwrap();
+    /// assert_eq!(
+    ///     String::from_utf8(json).unwrap(),
+    ///     r#"[{"name":"src/main.rs:12","ph":"X","ts":0,"dur":1,"pid":1,"tid":1}]"#,
+    /// );
+    /// ```
+    pub fn write_chrome_trace<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        let stacks = self.lock();
+
+        // Since the stacks are sorted, the stacks sharing a prefix are
+        // adjacent, so each frame's event covers a contiguous range of them.
+        let mut open: Vec<(&str, u64)> = Vec::new();
+        let mut events = Vec::new();
+        let mut time = 0;
+        for (path, count) in stacks.iter() {
+            let shared = open
+                .iter()
+                .zip(path)
+                .take_while(|((name, _), frame)| name == frame)
+                .count();
+            for (name, start) in open.drain(shared..).rev() {
+                events.push((name, start, time - start));
+            }
+            open.extend(path[shared..].iter().map(|frame| (frame.as_str(), time)));
+            time += count;
+        }
+        for (name, start) in open.drain(..).rev() {
+            events.push((name, start, time - start));
+        }
+        events.sort_by_key(|&(_, start, duration)| (start, std::cmp::Reverse(duration)));
+
+        write!(writer, "[")?;
+        for (index, (name, start, duration)) in events.iter().enumerate() {
+            if index > 0 {
+                write!(writer, ",")?;
+            }
+            write!(
+                writer,
+                r#"{{"name":"{}","ph":"X","ts":{},"dur":{},"pid":1,"tid":1}}"#,
+                json_escape(name),
+                start,
+                duration
+            )?;
+        }
+        write!(writer, "]")
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<Vec<String>, u64>> {
+        self.stacks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns `frame` with the characters that separate frames and counts in the
+/// folded-stack format replaced.
+fn folded_name(frame: &str) -> String {
+    frame
+        .chars()
+        .map(|c| match c {
+            ';' => ',',
+            '\n' | '\r' => ' ',
+            c => c,
+        })
+        .collect()
+}
+
+/// Escapes `text` for use in a JSON string.
+fn json_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CodeLocation, ErrorTrace};
+
+    fn trace(lines: &[u32]) -> ErrorTrace {
+        lines
+            .iter()
+            .map(|&line| CodeLocation::new("a.rs", line))
+            .collect()
+    }
+
+    #[test]
+    fn chrome_trace_nests_shared_prefixes() {
+        let flow = ErrorFlow::new();
+        flow.record(&trace(&[1, 9]));
+        flow.record(&trace(&[1, 9]));
+        flow.record(&trace(&[2, 9]));
+        flow.record(&ErrorTrace(vec![
+            CodeLocation::new("a.rs", 3).with_message("say \"hi\"")
+        ]));
+        assert_eq!(flow.len(), 4);
+
+        let mut json = Vec::new();
+        flow.write_chrome_trace(&mut json).unwrap();
+        let events: Vec<String> = String::from_utf8(json).unwrap()[1..]
+            .split("},")
+            .map(|event| event.split(",\"pid\"").next().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                r#"{"name":"a.rs:3: say \"hi\"","ph":"X","ts":0,"dur":1"#,
+                r#"{"name":"a.rs:9","ph":"X","ts":1,"dur":3"#,
+                r#"{"name":"a.rs:1","ph":"X","ts":1,"dur":2"#,
+                r#"{"name":"a.rs:2","ph":"X","ts":3,"dur":1"#,
+            ]
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Customizable rendering of error traces.
+//!
+//! The [`Display`] implementation of [`ErrorTrace`] (which is also used when
+//! reporting an error returned from `main`) delegates to a global
+//! [`TraceFormatter`]. By default, this is [`DefaultFormatter`], which renders
+//! one numbered frame per line:
+//!
+//! ```txt
+//!    0: src/main.rs:10
+//!    1: src/main.rs:24
+//! ```
+//!
+//! Applications can change how every trace is rendered by installing their own
+//! formatter with [`set_formatter()`].
+//!
+//! # Path Remapping
+//!
+//! The file paths in a trace are recorded at compile time, so they already
+//! honor rustc's `--remap-path-prefix` flag. Paths can additionally be
+//! rewritten when they are displayed, using [`remap_path_prefix()`]. This is
+//! useful for making traces from release builds and containers show stable,
+//! meaningful paths instead of absolute paths from the build machine.
+//!
+//! # Formatting Flags
+//!
+//! Traces honor the flags of the format string they are rendered with:
+//!
+//! * The precision limits the number of frames shown, e.g. `{:.5}` shows only
+//!   the first 5 frames, followed by a line counting the frames left out.
+//! * The width sets the minimum width of the frame numbers, e.g. `{:2}`.
+//! * The `+` flag includes column numbers, for stacks that record them (such
+//!   as [`LocationStack`]). [`ErrorTrace`] does not record columns.
+//!
+//! ```
+//! use propagate::trace::LocationStack;
+//!
+//! let stack = LocationStack(vec![std::panic::Location::caller(); 3]);
+//! let rendered = format!("{:.2}", stack);
+//! assert_eq!(rendered.lines().count(), 4);
+//! assert!(rendered.ends_with("\n   ... 1 more frame(s)"));
+//! ```
+//!
+//! Custom formatters get the same [`fmt::Formatter`], and can decide for
+//! themselves which flags to honor.
+//!
+//! # Module Tags
+//!
+//! In large workspaces, many crates have files with the same relative paths
+//! (`src/lib.rs`, `src/error.rs`, ...), so a trace can be ambiguous. Files can
+//! be tagged with the module they belong to, either one at a time with
+//! [`register_module!`], or a whole directory at a time with
+//! [`tag_path_prefix()`]. The [`DefaultFormatter`] shows the tag of each frame
+//! after its location:
+//!
+//! ```txt
+//!    0: src/lib.rs:10 [storage::db]
+//!    1: src/lib.rs:52 [server]
+//! ```
+//!
+//! [`Display`]: fmt::Display
+//! [`LocationStack`]: crate::trace::LocationStack
+//! [`ErrorTrace`]: crate::ErrorTrace
+
+use crate::trace::{CodeLocation, Traced};
+
+use std::borrow::Cow;
+use std::fmt;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// Renders the frames of an error trace.
+///
+/// Both methods have default implementations matching [`DefaultFormatter`], so
+/// implementors only need to override the parts they want to change.
+///
+/// # Examples
+///
+/// Rendering a trace on a single line, most recent frame first:
+///
+/// ```
+/// use propagate::fmt::TraceFormatter;
+/// use propagate::CodeLocation;
+/// use std::fmt;
+///
+/// struct OneLine;
+///
+/// impl TraceFormatter for OneLine {
+///     fn fmt_trace(&self, frames: &[CodeLocation], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         for (index, location) in frames.iter().rev().enumerate() {
+///             if index > 0 {
+///                 write!(f, " <- ")?;
+///             }
+///             write!(f, "{}", location)?;
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// propagate::fmt::set_formatter(OneLine);
+/// ```
+pub trait TraceFormatter: Send + Sync {
+    /// Renders all of the frames of a trace, oldest first.
+    ///
+    /// The default implementation calls [`fmt_frame()`] for each frame in
+    /// order, stopping after as many frames as the precision of `f`, if any
+    /// (see [Formatting Flags](self#formatting-flags)).
+    ///
+    /// [`fmt_frame()`]: TraceFormatter::fmt_frame
+    fn fmt_trace(&self, frames: &[CodeLocation], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = shown_frames(frames.len(), f);
+        for (index, location) in frames[..shown].iter().enumerate() {
+            self.fmt_frame(index, location, f)?;
+        }
+
+        fmt_omitted(frames.len() - shown, f)
+    }
+
+    /// Renders a single frame of a trace, where `index` is the position of the
+    /// frame in the trace (starting at 0 for the oldest frame).
+    ///
+    /// The default implementation writes the frame on its own indented line,
+    /// padding `index` to the width of `f`, if any, and followed by the
+    /// [tag](self#module-tags) of its file, if any.
+    fn fmt_frame(
+        &self,
+        index: usize,
+        location: &CodeLocation,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        fmt_index(index, f)?;
+        write!(f, "{}", location)?;
+        match module_of(location.file()) {
+            Some(module) => write!(f, " [{}]", module),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The formatter used when no other formatter has been installed.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DefaultFormatter;
+
+impl TraceFormatter for DefaultFormatter {}
+
+static FORMATTER: RwLock<Option<Arc<dyn TraceFormatter>>> = RwLock::new(None);
+
+/// Installs `formatter` as the global trace formatter, replacing any
+/// previously installed formatter.
+pub fn set_formatter(formatter: impl TraceFormatter + 'static) {
+    *FORMATTER.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(formatter));
+}
+
+/// Restores the [`DefaultFormatter`] as the global trace formatter.
+pub fn reset_formatter() {
+    *FORMATTER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Returns the currently installed global trace formatter.
+pub fn formatter() -> Arc<dyn TraceFormatter> {
+    FORMATTER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(DefaultFormatter))
+}
+
+/// Renders `frames` using `formatter`, regardless of the installed global
+/// formatter.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::fmt::{display_with, DefaultFormatter};
+/// use propagate::CodeLocation;
+///
+/// let frames = [CodeLocation::new("foo.rs", 1), CodeLocation::new("bar.rs", 2)];
+/// assert_eq!(
+///     display_with(&DefaultFormatter, &frames).to_string(),
+///     "\n   0: foo.rs:1\n   1: bar.rs:2",
+/// );
+/// ```
+pub fn display_with<'a, F: TraceFormatter + ?Sized>(
+    formatter: &'a F,
+    frames: &'a [CodeLocation],
+) -> impl fmt::Display + 'a {
+    struct DisplayWith<'a, F: ?Sized>(&'a F, &'a [CodeLocation]);
+
+    impl<F: TraceFormatter + ?Sized> fmt::Display for DisplayWith<'_, F> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt_trace(self.1, f)
+        }
+    }
+
+    DisplayWith(formatter, frames)
+}
+
+/// Returns how many of `len` frames are shown, given the precision of `f`.
+fn shown_frames(len: usize, f: &fmt::Formatter<'_>) -> usize {
+    f.precision().unwrap_or(len).min(len)
+}
+
+/// Starts a new line for the frame at `index`, padding `index` to the width of
+/// `f`, if any.
+fn fmt_index(index: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match f.width() {
+        Some(width) => write!(f, "\n{:>width$}: ", index, width = width),
+        None => write!(f, "\n   {}: ", index),
+    }
+}
+
+/// Writes a line counting the `omitted` frames that were not shown, if any.
+fn fmt_omitted(omitted: usize, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if omitted > 0 {
+        write!(f, "\n   ... {} more frame(s)", omitted)?;
+    }
+    Ok(())
+}
+
+/// Renders `locations` one per line, honoring the flags of `f`.
+pub(crate) fn fmt_locations(
+    locations: &[&'static panic::Location<'static>],
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    let shown = shown_frames(locations.len(), f);
+    for (index, location) in locations[..shown].iter().enumerate() {
+        fmt_index(index, f)?;
+        write!(f, "{}:{}", remap_path(location.file()), location.line())?;
+        if f.sign_plus() {
+            write!(f, ":{}", location.column())?;
+        }
+    }
+
+    fmt_omitted(locations.len() - shown, f)
+}
+
+/// The environment variable that selects the [`Style`] used when an error is
+/// returned from `main`.
+pub const STYLE_VAR: &str = "PROPAGATE_STYLE";
+
+/// How much of the trace to print when an error is returned from `main`.
+///
+/// The style is read from the [`PROPAGATE_STYLE`][STYLE_VAR] environment
+/// variable each time an error is reported, so operators can change the
+/// verbosity of a deployed binary without rebuilding it. The accepted values
+/// are `full`, `compact` and `off` (case-insensitive); when the variable is
+/// unset or has any other value, the [`Full`][Style::Full] style is used.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Style {
+    /// Render the trace using the global formatter, one frame per line.
+    #[default]
+    Full,
+    /// Render the trace on a single line.
+    Compact,
+    /// Omit the trace entirely.
+    Off,
+}
+
+impl Style {
+    /// Parses the name of a style, as accepted in [`PROPAGATE_STYLE`][STYLE_VAR].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::fmt::Style;
+    ///
+    /// assert_eq!(Style::from_name("Compact"), Some(Style::Compact));
+    /// assert_eq!(Style::from_name("verbose"), None);
+    /// ```
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "full" => Some(Style::Full),
+            "compact" => Some(Style::Compact),
+            "off" => Some(Style::Off),
+            _ => None,
+        }
+    }
+
+    /// Returns the style selected by the [`PROPAGATE_STYLE`][STYLE_VAR]
+    /// environment variable.
+    pub fn from_env() -> Self {
+        std::env::var(STYLE_VAR)
+            .ok()
+            .and_then(|name| Self::from_name(&name))
+            .unwrap_or(Style::Full)
+    }
+}
+
+/// Renders `stack` on a single line, for embedding in log messages.
+///
+/// The frames are separated by `<-`, oldest first, so the line reads from
+/// where the error was created to where it ended up. Like the multi-line
+/// format, the precision limits the number of frames shown. Stacks that do not
+/// keep their frames (see [`Traced::frames()`]) have the lines of their usual
+/// rendering joined instead.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::{CodeLocation, ErrorTrace};
+///
+/// let trace = ErrorTrace(vec![
+///     CodeLocation::new("src/parser.rs", 88),
+///     CodeLocation::new("src/loader.rs", 41),
+///     CodeLocation::new("src/main.rs", 12),
+/// ]);
+///
+/// let compact = propagate::fmt::compact(&trace);
+/// assert_eq!(
+///     compact.to_string(),
+///     "src/parser.rs:88 <- src/loader.rs:41 <- src/main.rs:12",
+/// );
+/// assert_eq!(format!("{:.1}", compact), "src/parser.rs:88 <- ... 2 more");
+/// ```
+pub fn compact<S: Traced + fmt::Display + ?Sized>(stack: &S) -> Compact<'_, S> {
+    Compact(stack)
+}
+
+/// Renders a stack on a single line.
+///
+/// This is returned by [`compact()`].
+#[derive(Debug)]
+pub struct Compact<'a, S: ?Sized>(&'a S);
+
+impl<S: Traced + fmt::Display + ?Sized> fmt::Display for Compact<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frames: Vec<_> = match self.0.frames() {
+            Some(frames) => frames.collect(),
+            None => return f.write_str(&compact_lines(&self.0.to_string())),
+        };
+
+        let shown = shown_frames(frames.len(), f);
+        for (index, location) in frames[..shown].iter().enumerate() {
+            if index > 0 {
+                f.write_str(" <- ")?;
+            }
+            write!(f, "{}", location)?;
+        }
+        if shown < frames.len() {
+            if shown > 0 {
+                f.write_str(" <- ")?;
+            }
+            write!(f, "... {} more", frames.len() - shown)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Joins the non-empty lines of `text` into a single line.
+pub(crate) fn compact_lines(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+static PATH_REMAPS: RwLock<Vec<(String, String)>> = RwLock::new(Vec::new());
+
+/// Registers a rule that rewrites displayed file paths starting with `from` to
+/// start with `to` instead.
+///
+/// Like `--remap-path-prefix`, when multiple rules match a path, the rule that
+/// was registered last wins. Backslashes in paths and prefixes are normalized
+/// to forward slashes before matching, so a single rule works for traces
+/// produced on any platform.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::CodeLocation;
+///
+/// propagate::fmt::remap_path_prefix("/home/builder/project/", "");
+///
+/// let loc = CodeLocation::new("/home/builder/project/src/main.rs", 10);
+/// assert_eq!(loc.to_string(), "src/main.rs:10");
+/// ```
+pub fn remap_path_prefix(from: impl Into<String>, to: impl Into<String>) {
+    let from = normalize_separators(&from.into()).into_owned();
+    let to = to.into();
+    PATH_REMAPS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((from, to));
+}
+
+/// Removes all rules registered with [`remap_path_prefix()`].
+pub fn clear_path_remaps() {
+    PATH_REMAPS
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+/// Applies the registered path remapping rules to `path`.
+///
+/// # Examples
+///
+/// ```
+/// propagate::fmt::remap_path_prefix("C:/build/", "");
+/// assert_eq!(propagate::fmt::remap_path("C:\\build\\src\\lib.rs"), "src/lib.rs");
+/// ```
+pub fn remap_path(path: &str) -> Cow<'_, str> {
+    let path = normalize_separators(path);
+    let remaps = PATH_REMAPS.read().unwrap_or_else(|e| e.into_inner());
+
+    for (from, to) in remaps.iter().rev() {
+        if let Some(rest) = path.strip_prefix(from.as_str()) {
+            return Cow::Owned(format!("{}{}", to, rest));
+        }
+    }
+
+    path
+}
+
+static MODULE_TAGS: RwLock<Vec<(String, &'static str)>> = RwLock::new(Vec::new());
+
+/// Whether any module tags are registered, so that rendering frames does not
+/// take the lock of [`MODULE_TAGS`] in programs that use none.
+static MODULE_TAGGED: AtomicBool = AtomicBool::new(false);
+
+/// Tags the files whose paths start with `prefix` (or equal it) as belonging
+/// to `module`.
+///
+/// When multiple rules match a path, the rule with the longest prefix wins.
+/// Backslashes are normalized to forward slashes before matching, like for
+/// [`remap_path_prefix()`].
+///
+/// # Examples
+///
+/// ```
+/// propagate::fmt::tag_path_prefix("crates/storage/", "storage");
+///
+/// assert_eq!(propagate::fmt::module_of("crates/storage/src/lib.rs"), Some("storage"));
+/// assert_eq!(propagate::fmt::module_of("src/lib.rs"), None);
+/// ```
+pub fn tag_path_prefix(prefix: impl Into<String>, module: &'static str) {
+    let prefix = normalize_separators(&prefix.into()).into_owned();
+    let mut tags = MODULE_TAGS.write().unwrap_or_else(|e| e.into_inner());
+    tags.retain(|(existing, _)| *existing != prefix);
+    tags.push((prefix, module));
+    MODULE_TAGGED.store(true, Ordering::Release);
+}
+
+/// Removes all module tags.
+pub fn clear_module_tags() {
+    let mut tags = MODULE_TAGS.write().unwrap_or_else(|e| e.into_inner());
+    tags.clear();
+    MODULE_TAGGED.store(false, Ordering::Release);
+}
+
+/// Returns the module that `file` is tagged with, if any.
+pub fn module_of(file: &str) -> Option<&'static str> {
+    if !MODULE_TAGGED.load(Ordering::Acquire) {
+        return None;
+    }
+    let file = normalize_separators(file);
+    MODULE_TAGS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(prefix, _)| file.starts_with(prefix.as_str()))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|&(_, module)| module)
+}
+
+/// Tags the file of the invocation as belonging to the module of the
+/// invocation (as given by [`module_path!`]).
+///
+/// See [Module Tags](crate::fmt#module-tags).
+///
+/// # Examples
+///
+/// ```
+/// propagate::fmt::register_module!();
+///
+/// assert_eq!(propagate::fmt::module_of(file!()), Some(module_path!()));
+/// ```
+#[macro_export]
+macro_rules! register_module {
+    () => {
+        $crate::fmt::tag_path_prefix(::std::file!(), ::std::module_path!())
+    };
+}
+
+#[doc(inline)]
+pub use crate::register_module;
+
+fn normalize_separators(path: &str) -> Cow<'_, str> {
+    if path.contains('\\') {
+        Cow::Owned(path.replace('\\', "/"))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Bracketed;
+
+    impl TraceFormatter for Bracketed {
+        fn fmt_frame(
+            &self,
+            _index: usize,
+            location: &CodeLocation,
+            f: &mut fmt::Formatter<'_>,
+        ) -> fmt::Result {
+            write!(f, "[{}]", location)
+        }
+    }
+
+    #[test]
+    fn overriding_fmt_frame_keeps_default_ordering() {
+        let frames = [CodeLocation::new("a.rs", 1), CodeLocation::new("b.rs", 2)];
+        assert_eq!(
+            display_with(&Bracketed, &frames).to_string(),
+            "[a.rs:1][b.rs:2]"
+        );
+    }
+
+    #[test]
+    fn default_formatter_honors_width_and_precision() {
+        let frames = [
+            CodeLocation::new("a.rs", 1),
+            CodeLocation::new("b.rs", 2),
+            CodeLocation::new("c.rs", 3),
+        ];
+        let display = display_with(&DefaultFormatter, &frames);
+        assert_eq!(
+            format!("{:2.1}", display),
+            "\n 0: a.rs:1\n   ... 2 more frame(s)"
+        );
+        assert_eq!(
+            format!("{}", display),
+            "\n   0: a.rs:1\n   1: b.rs:2\n   2: c.rs:3"
+        );
+    }
+
+    #[test]
+    fn compact_joins_lines_of_stacks_without_frames() {
+        let (mut stack, line) = (crate::CountingStack::new(), line!());
+        stack.trace(panic::Location::caller());
+        assert_eq!(
+            compact(&stack).to_string(),
+            format!("origin: {}:{} (propagated 1 time(s))", file!(), line)
+        );
+    }
+
+    /// Clears the module tags when dropped, even if the test fails.
+    struct ClearModuleTags;
+
+    impl Drop for ClearModuleTags {
+        fn drop(&mut self) {
+            clear_module_tags();
+        }
+    }
+
+    // This is the only test that registers module tags, so it does not need
+    // to be serialized with other tests, as long as it removes them.
+    #[test]
+    fn default_formatter_shows_module_tags() {
+        let clear = ClearModuleTags;
+        tag_path_prefix("tagged/", "tagged");
+        tag_path_prefix("tagged/inner/", "tagged::inner");
+
+        let frames = [
+            CodeLocation::new("tagged/inner/a.rs", 1),
+            CodeLocation::new("tagged/b.rs", 2),
+        ];
+        assert_eq!(
+            display_with(&DefaultFormatter, &frames).to_string(),
+            "\n   0: tagged/inner/a.rs:1 [tagged::inner]\n   1: tagged/b.rs:2 [tagged]"
+        );
+
+        drop(clear);
+        assert_eq!(module_of("tagged/b.rs"), None);
+    }
+
+    #[test]
+    fn normalize_separators_only_allocates_when_needed() {
+        assert!(matches!(
+            normalize_separators("a/b.rs"),
+            Cow::Borrowed("a/b.rs")
+        ));
+        assert_eq!(normalize_separators("a\\b.rs"), "a/b.rs");
+    }
+}
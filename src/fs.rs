@@ -0,0 +1,275 @@
+//! Filesystem operations with traced errors.
+//!
+//! The functions in this module wrap their counterparts in [`std::fs`]. When
+//! they fail, they return an [`FsError`] that names the operation and the
+//! path involved, and whose trace starts at the location of the caller, so
+//! the error can be propagated with `?` without attaching context by hand:
+//!
+//! ```
+//! fn load_config(dir: &std::path::Path) -> propagate::Result<String, propagate::fs::FsError> {
+//!     let config = propagate::fs::read_to_string(dir.join("config.toml"))?;
+//!     propagate::Ok(config)
+//! }
+//!
+//! let err = load_config("/no/such/dir".as_ref()).unwrap_err();
+//! assert_eq!(err.to_string(), "failed to read `/no/such/dir/config.toml`");
+//! assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+//! ```
+//!
+//! The underlying I/O error is the [`source()`][Error::source] of the
+//! [`FsError`], so reports show it as the cause rather than repeating it in
+//! the message. An [`FsError`] converts into an [`io::Error`] with the same
+//! [`kind()`][io::Error::kind], for use in code that expects one.
+
+use crate::result::Result::{self, Ok};
+
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, Metadata, ReadDir};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The error returned when a filesystem operation fails.
+#[derive(Debug)]
+pub struct FsError {
+    operation: &'static str,
+    path: PathBuf,
+    destination: Option<PathBuf>,
+    source: io::Error,
+}
+
+impl FsError {
+    /// Returns the path that the operation was applied to.
+    ///
+    /// For operations with two paths, such as [`copy()`] and [`rename()`],
+    /// this is the source path.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the destination path of an operation with two paths, such as
+    /// [`copy()`] and [`rename()`].
+    pub fn destination(&self) -> Option<&Path> {
+        self.destination.as_deref()
+    }
+
+    /// Returns the kind of the underlying I/O error.
+    pub fn kind(&self) -> io::ErrorKind {
+        self.source.kind()
+    }
+
+    /// Returns the underlying I/O error.
+    pub fn io_error(&self) -> &io::Error {
+        &self.source
+    }
+
+    /// Consumes the error, returning the underlying I/O error, without the
+    /// path.
+    pub fn into_io_error(self) -> io::Error {
+        self.source
+    }
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (operation, path) = (self.operation, self.path.display());
+        match &self.destination {
+            Some(destination) => write!(
+                f,
+                "failed to {} `{}` to `{}`",
+                operation,
+                path,
+                destination.display()
+            ),
+            None => write!(f, "failed to {} `{}`", operation, path),
+        }
+    }
+}
+
+impl Error for FsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl From<FsError> for io::Error {
+    /// Wraps the error in an [`io::Error`] of the same kind.
+    fn from(error: FsError) -> Self {
+        io::Error::new(error.kind(), error)
+    }
+}
+
+/// Converts the result of an operation on `path` into a traced result.
+#[track_caller]
+fn traced<T>(
+    result: io::Result<T>,
+    operation: &'static str,
+    path: &Path,
+    destination: Option<&Path>,
+) -> Result<T, FsError> {
+    match result {
+        std::result::Result::Ok(value) => Ok(value),
+        std::result::Result::Err(source) => Result::new_err(FsError {
+            operation,
+            path: path.to_path_buf(),
+            destination: destination.map(Path::to_path_buf),
+            source,
+        }),
+    }
+}
+
+/// Reads the entire contents of a file into a vector of bytes, like
+/// [`fs::read()`].
+#[track_caller]
+pub fn read<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, FsError> {
+    let path = path.as_ref();
+    traced(fs::read(path), "read", path, None)
+}
+
+/// Reads the entire contents of a file into a string, like
+/// [`fs::read_to_string()`].
+///
+/// # Examples
+///
+/// ```
+/// let path = std::env::temp_dir().join("propagate-fs-read_to_string.txt");
+/// propagate::fs::write(&path, "hello").unwrap();
+/// assert_eq!(propagate::fs::read_to_string(&path).unwrap(), "hello");
+/// # propagate::fs::remove_file(&path).unwrap();
+/// ```
+#[track_caller]
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> Result<String, FsError> {
+    let path = path.as_ref();
+    traced(fs::read_to_string(path), "read", path, None)
+}
+
+/// Writes a slice as the entire contents of a file, like [`fs::write()`].
+#[track_caller]
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> Result<(), FsError> {
+    let path = path.as_ref();
+    traced(fs::write(path, contents), "write", path, None)
+}
+
+/// Opens a file in read-only mode, like [`File::open()`].
+///
+/// # Examples
+///
+/// ```
+/// let err = propagate::fs::open("/no/such/file").unwrap_err();
+/// assert_eq!(err.kind(), std::io::ErrorKind::NotFound);
+/// assert_eq!(err.path(), std::path::Path::new("/no/such/file"));
+/// ```
+#[track_caller]
+pub fn open<P: AsRef<Path>>(path: P) -> Result<File, FsError> {
+    let path = path.as_ref();
+    traced(File::open(path), "open", path, None)
+}
+
+/// Opens a file in write-only mode, creating or truncating it, like
+/// [`File::create()`].
+#[track_caller]
+pub fn create<P: AsRef<Path>>(path: P) -> Result<File, FsError> {
+    let path = path.as_ref();
+    traced(File::create(path), "create", path, None)
+}
+
+/// Queries the metadata of a file or directory, like [`fs::metadata()`].
+#[track_caller]
+pub fn metadata<P: AsRef<Path>>(path: P) -> Result<Metadata, FsError> {
+    let path = path.as_ref();
+    traced(fs::metadata(path), "query metadata of", path, None)
+}
+
+/// Returns an iterator over the entries of a directory, like
+/// [`fs::read_dir()`].
+#[track_caller]
+pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<ReadDir, FsError> {
+    let path = path.as_ref();
+    traced(fs::read_dir(path), "read directory", path, None)
+}
+
+/// Creates a new, empty directory, like [`fs::create_dir()`].
+#[track_caller]
+pub fn create_dir<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
+    let path = path.as_ref();
+    traced(fs::create_dir(path), "create directory", path, None)
+}
+
+/// Creates a directory and all of its missing parents, like
+/// [`fs::create_dir_all()`].
+#[track_caller]
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
+    let path = path.as_ref();
+    traced(fs::create_dir_all(path), "create directory", path, None)
+}
+
+/// Removes a file, like [`fs::remove_file()`].
+#[track_caller]
+pub fn remove_file<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
+    let path = path.as_ref();
+    traced(fs::remove_file(path), "remove file", path, None)
+}
+
+/// Removes an empty directory, like [`fs::remove_dir()`].
+#[track_caller]
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
+    let path = path.as_ref();
+    traced(fs::remove_dir(path), "remove directory", path, None)
+}
+
+/// Removes a directory and all of its contents, like
+/// [`fs::remove_dir_all()`].
+#[track_caller]
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> Result<(), FsError> {
+    let path = path.as_ref();
+    traced(fs::remove_dir_all(path), "remove directory", path, None)
+}
+
+/// Returns the canonical, absolute form of a path, like
+/// [`fs::canonicalize()`].
+#[track_caller]
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> Result<PathBuf, FsError> {
+    let path = path.as_ref();
+    traced(fs::canonicalize(path), "canonicalize", path, None)
+}
+
+/// Copies the contents of one file to another, like [`fs::copy()`].
+///
+/// # Examples
+///
+/// ```
+/// let err = propagate::fs::copy("/no/such/file", "/tmp/copy").unwrap_err();
+/// assert_eq!(err.to_string(), "failed to copy `/no/such/file` to `/tmp/copy`");
+/// ```
+#[track_caller]
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<u64, FsError> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    traced(fs::copy(from, to), "copy", from, Some(to))
+}
+
+/// Renames a file or directory, like [`fs::rename()`].
+#[track_caller]
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> Result<(), FsError> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    traced(fs::rename(from, to), "rename", from, Some(to))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CodeLocation;
+
+    #[test]
+    fn failure_starts_trace_at_caller() {
+        let here = CodeLocation::here().down_by(1);
+        let (err, trace) = read("/no/such/file").err_trace().unwrap();
+        assert_eq!(trace.0, vec![here]);
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let io_error = io::Error::from(err);
+        assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(io_error.to_string(), "failed to read `/no/such/file`");
+        let source = io_error.source().unwrap().downcast_ref::<io::Error>();
+        assert_eq!(source.unwrap().kind(), io::ErrorKind::NotFound);
+    }
+}
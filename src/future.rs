@@ -0,0 +1,539 @@
+//! Helpers for futures that produce traced results.
+//!
+//! The joining helpers in this module differ from their usual counterparts in
+//! one important way: they do not short-circuit on the first failure. Every
+//! branch is driven to completion, and if any of them fail, the result is an
+//! [`ErrorSet`] carrying each failed branch's error and trace. The location of
+//! the join itself starts a new trace for the aggregated error.
+//!
+//! The racing helpers, [`select`] and [`select_ok`], likewise report a failed
+//! race as an [`ErrorSet`] that starts at the location of the race, and can
+//! keep the traces of the branches that lost it.
+
+use crate::error::ErrorSet;
+use crate::result::Result::{self, Err, Ok};
+use crate::trace::Traced;
+
+use std::future::Future;
+use std::mem;
+use std::panic;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+#[doc(inline)]
+pub use crate::try_join;
+
+/// Polls multiple futures simultaneously, returning a tuple of all results
+/// once complete.
+///
+/// Each future must return a [`propagate::Result`] with the same error type
+/// and stack type. If any of the futures fail, the output is an `Err`
+/// containing an [`ErrorSet`] of every failure (each with its own trace). The
+/// trace of the aggregated error begins at the `try_join!` invocation.
+///
+/// This macro is only usable inside of async functions, closures, and blocks.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::ErrorSet;
+///
+/// async fn first() -> propagate::Result<u32, String> {
+///     propagate::Ok(1)
+/// }
+///
+/// async fn second() -> propagate::Result<&'static str, String> {
+///     propagate::Result::new_err("oops")
+/// }
+///
+/// async fn both() -> propagate::Result<(u32, &'static str), ErrorSet<String>> {
+///     propagate::future::try_join!(first(), second()).await
+/// }
+/// ```
+///
+/// [`propagate::Result`]: crate::Result
+/// [`ErrorSet`]: crate::ErrorSet
+#[macro_export]
+macro_rules! try_join {
+    (@munch [$($acc:tt)*] $fut:expr, $($rest:tt)*) => {
+        $crate::try_join!(@munch [$($acc)* (fut $fut)] $($rest)*)
+    };
+    (@munch [$(($name:ident $fut:expr))*]) => {
+        async {
+            $( let mut $name = $crate::future::MaybeDone::new($fut); )*
+            ::std::future::poll_fn(|cx| {
+                let mut all_done = true;
+                $( all_done &= $name.poll_done(cx); )*
+                if all_done {
+                    ::std::task::Poll::Ready(())
+                } else {
+                    ::std::task::Poll::Pending
+                }
+            })
+            .await;
+            let mut errors = $crate::ErrorSet::new();
+            $( let $name = $name.take_ok(&mut errors); )*
+            $crate::future::join_result(errors, || ($($name.unwrap(),)*))
+        }
+    };
+    ($($fut:expr),+ $(,)?) => {
+        $crate::try_join!(@munch [] $($fut,)+)
+    };
+}
+
+/// Creates a future which represents a collection of the results of the
+/// futures given.
+///
+/// The returned future will drive execution for all of its underlying futures,
+/// collecting the results into a `Vec<T>` in the same order as they were
+/// provided. If any of the futures fail, the output is an `Err` containing an
+/// [`ErrorSet`] of every failure (each with its own trace). The trace of the
+/// aggregated error begins at the location where `try_join_all` was called.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::ErrorSet;
+///
+/// async fn fetch(i: u32) -> propagate::Result<u32, String> {
+///     if i % 2 == 0 {
+///         propagate::Ok(i)
+///     } else {
+///         propagate::Result::new_err(format!("{} is odd", i))
+///     }
+/// }
+///
+/// async fn fetch_all() -> propagate::Result<Vec<u32>, ErrorSet<String>> {
+///     propagate::future::try_join_all((0..4).map(fetch)).await
+/// }
+/// ```
+#[track_caller]
+pub fn try_join_all<I>(iter: I) -> TryJoinAll<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    TryJoinAll {
+        elems: iter.into_iter().map(MaybeDone::new).collect(),
+        location: panic::Location::caller(),
+    }
+}
+
+/// Future for the [`try_join_all`] function.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct TryJoinAll<F: Future> {
+    elems: Vec<MaybeDone<F>>,
+    location: &'static panic::Location<'static>,
+}
+
+// None of the fields are ever pinned: each inner future is boxed separately.
+impl<F: Future> Unpin for TryJoinAll<F> {}
+
+impl<F, T, E, S> Future for TryJoinAll<F>
+where
+    F: Future<Output = Result<T, E, S>>,
+    S: Traced + Default,
+{
+    type Output = Result<Vec<T>, ErrorSet<E, S>, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        let mut all_done = true;
+        for elem in this.elems.iter_mut() {
+            all_done &= elem.poll_done(cx);
+        }
+        if !all_done {
+            return Poll::Pending;
+        }
+
+        let mut errors = ErrorSet::new();
+        let oks: Vec<Option<T>> = this
+            .elems
+            .iter_mut()
+            .map(|elem| elem.take_ok(&mut errors))
+            .collect();
+
+        Poll::Ready(if errors.is_empty() {
+            Ok(oks.into_iter().map(Option::unwrap).collect())
+        } else {
+            let trace = crate::result::new_trace::<ErrorSet<E, S>, S>(this.location);
+            Err(errors, trace)
+        })
+    }
+}
+
+/// A future that may have completed.
+///
+/// This is an implementation detail of [`try_join!`] and [`try_join_all`].
+#[doc(hidden)]
+pub enum MaybeDone<F: Future> {
+    Future(Pin<Box<F>>),
+    Done(F::Output),
+    Gone,
+}
+
+impl<F: Future> MaybeDone<F> {
+    pub fn new(future: F) -> Self {
+        Self::Future(Box::pin(future))
+    }
+
+    /// Polls the inner future if it has not completed yet. Returns `true` once
+    /// the output is available.
+    pub fn poll_done(&mut self, cx: &mut Context<'_>) -> bool {
+        if let Self::Future(future) = self {
+            match future.as_mut().poll(cx) {
+                Poll::Ready(output) => *self = Self::Done(output),
+                Poll::Pending => return false,
+            }
+        }
+        true
+    }
+}
+
+impl<F, T, E, S> MaybeDone<F>
+where
+    F: Future<Output = Result<T, E, S>>,
+{
+    /// Takes the output of the completed future, moving any error into
+    /// `errors`.
+    pub fn take_ok(&mut self, errors: &mut ErrorSet<E, S>) -> Option<T> {
+        match mem::replace(self, Self::Gone) {
+            Self::Done(Ok(t)) => Some(t),
+            Self::Done(Err(err, trace)) => {
+                errors.push(err, trace);
+                None
+            }
+            _ => panic!("`MaybeDone` polled after its output was taken"),
+        }
+    }
+}
+
+/// Produces the output of [`try_join!`], starting a new trace at the caller if
+/// there were any errors.
+#[doc(hidden)]
+#[track_caller]
+pub fn join_result<T, E, S, O>(errors: ErrorSet<E, S>, ok: O) -> Result<T, ErrorSet<E, S>, S>
+where
+    S: Traced + Default,
+    O: FnOnce() -> T,
+{
+    if errors.is_empty() {
+        Ok(ok())
+    } else {
+        let trace = crate::result::new_trace::<ErrorSet<E, S>, S>(panic::Location::caller());
+        Err(errors, trace)
+    }
+}
+
+/// Creates a future which completes with the output of whichever of the
+/// futures given completes first.
+///
+/// If the first future to complete succeeds, the output is its value and its
+/// index among the futures. If it fails, the output is an `Err` containing an
+/// [`ErrorSet`] with its error and trace, and the trace of the aggregated
+/// error begins at the location where `select` was called. By default, the
+/// other futures are dropped; with [`with_losers()`][Select::with_losers],
+/// they are driven to completion and their failures are added to the set, so
+/// the error tells the whole story of the race.
+///
+/// # Panics
+///
+/// Panics if no futures are given.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::ErrorSet;
+///
+/// async fn primary() -> propagate::Result<&'static str, String> {
+///     propagate::Result::new_err("primary is down".to_string())
+/// }
+///
+/// async fn fallback() -> propagate::Result<&'static str, String> {
+///     propagate::Result::new_err("fallback is down".to_string())
+/// }
+///
+/// async fn fetch() -> propagate::Result<(&'static str, usize), ErrorSet<String>> {
+///     let branches: Vec<std::pin::Pin<Box<dyn std::future::Future<Output = _>>>> =
+///         vec![Box::pin(primary()), Box::pin(fallback())];
+///     propagate::future::select(branches).with_losers().await
+/// }
+/// ```
+#[track_caller]
+pub fn select<I>(iter: I) -> Select<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    Select::new(iter, false, panic::Location::caller())
+}
+
+/// Creates a future which completes with the output of the first of the
+/// futures given to succeed.
+///
+/// If a future succeeds, the output is its value and its index among the
+/// futures, and the rest of the futures are dropped. If every future fails,
+/// the output is an `Err` containing an [`ErrorSet`] of every failure, in the
+/// order they completed, and the trace of the aggregated error begins at the
+/// location where `select_ok` was called.
+///
+/// # Panics
+///
+/// Panics if no futures are given.
+///
+/// # Examples
+///
+/// ```
+/// async fn fetch(mirror: u32) -> propagate::Result<u32, String> {
+///     if mirror == 0 {
+///         propagate::Result::new_err("mirror 0 is down".to_string())
+///     } else {
+///         propagate::Ok(mirror)
+///     }
+/// }
+///
+/// async fn fetch_any() -> propagate::Result<(u32, usize), propagate::ErrorSet<String>> {
+///     propagate::future::select_ok((0..3).map(fetch)).await
+/// }
+/// ```
+#[track_caller]
+pub fn select_ok<I>(iter: I) -> Select<I::Item>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    Select::new(iter, true, panic::Location::caller())
+}
+
+/// Future for the [`select`] and [`select_ok`] functions.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct Select<F: Future> {
+    elems: Vec<Option<Pin<Box<F>>>>,
+    until_ok: bool,
+    with_losers: bool,
+    // The outputs of the futures that failed, in the order they completed.
+    failures: Vec<F::Output>,
+    location: &'static panic::Location<'static>,
+}
+
+// None of the fields are ever pinned: each inner future is boxed separately.
+impl<F: Future> Unpin for Select<F> {}
+
+impl<F: Future> Select<F> {
+    fn new<I>(iter: I, until_ok: bool, location: &'static panic::Location<'static>) -> Self
+    where
+        I: IntoIterator<Item = F>,
+    {
+        let elems: Vec<_> = iter
+            .into_iter()
+            .map(|future| Some(Box::pin(future)))
+            .collect();
+        assert!(!elems.is_empty(), "no futures were given to select from");
+
+        Self {
+            elems,
+            until_ok,
+            with_losers: false,
+            failures: Vec::new(),
+            location,
+        }
+    }
+
+    /// If the first future to complete fails, drives the other futures to
+    /// completion and includes their failures in the error, consuming and
+    /// returning `self`.
+    ///
+    /// This has no effect on [`select_ok`], which always waits for every
+    /// future to fail before failing.
+    pub fn with_losers(self) -> Self {
+        Self {
+            with_losers: true,
+            ..self
+        }
+    }
+}
+
+impl<F, T, E, S> Future for Select<F>
+where
+    F: Future<Output = Result<T, E, S>>,
+    S: Traced + Default,
+{
+    type Output = Result<(T, usize), ErrorSet<E, S>, S>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+
+        for (index, elem) in this.elems.iter_mut().enumerate() {
+            let future = match elem {
+                Some(future) => future,
+                None => continue,
+            };
+            match future.as_mut().poll(cx) {
+                Poll::Pending => continue,
+                Poll::Ready(Ok(t)) if this.failures.is_empty() || this.until_ok => {
+                    return Poll::Ready(Ok((t, index)));
+                }
+                // A loser that succeeded after the winner failed.
+                Poll::Ready(Ok(_)) => {}
+                Poll::Ready(failure) => this.failures.push(failure),
+            }
+            *elem = None;
+
+            if !this.until_ok && !this.with_losers && !this.failures.is_empty() {
+                break;
+            }
+        }
+
+        let done = this.elems.iter().all(Option::is_none);
+        let lost = !this.until_ok && !this.with_losers && !this.failures.is_empty();
+        if !done && !lost {
+            return Poll::Pending;
+        }
+
+        let mut errors = ErrorSet::new();
+        for failure in this.failures.drain(..) {
+            if let Err(err, trace) = failure {
+                errors.push(err, trace);
+            }
+        }
+        let trace = crate::result::new_trace::<ErrorSet<E, S>, S>(this.location);
+        Poll::Ready(Err(errors, trace))
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::{block_on, Fixture};
+    use crate::{CodeLocation, ErrorTrace};
+
+    type BoxFuture<'a> = Pin<Box<dyn Future<Output = Result<u32, String>> + 'a>>;
+
+    async fn succeed(x: u32) -> Result<u32, String> {
+        Ok(x)
+    }
+
+    async fn fail(fix: &mut Fixture, tag: &'static str) -> Result<u32, String> {
+        fix.tag_location(tag, CodeLocation::here().down_by(1));
+        Result::new_err(tag)
+    }
+
+    #[test]
+    fn try_join_all_succeeds() {
+        let result = block_on(try_join_all(vec![succeed(1), succeed(2), succeed(3)]));
+        assert_eq!(result, Ok(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn try_join_all_collects_every_error() {
+        let mut fix = Fixture::default();
+        let (mut fix_a, mut fix_b) = (Fixture::default(), Fixture::default());
+
+        let futures: Vec<BoxFuture<'_>> = vec![
+            Box::pin(fail(&mut fix_a, "a")),
+            Box::pin(succeed(2)),
+            Box::pin(fail(&mut fix_b, "b")),
+        ];
+        fix.tag_location("join", CodeLocation::here().down_by(1));
+        let result = block_on(try_join_all(futures));
+
+        let (errors, trace) = result.err_trace().unwrap();
+        fix.assert_stack_matches_tags(&trace, &["join"]);
+
+        let errors: Vec<(String, ErrorTrace)> = errors.into_iter().collect();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].0, "a");
+        fix_a.assert_stack_matches_tags(&errors[0].1, &["a"]);
+        assert_eq!(errors[1].0, "b");
+        fix_b.assert_stack_matches_tags(&errors[1].1, &["b"]);
+    }
+
+    #[test]
+    fn try_join_macro_succeeds() {
+        let result = block_on(async { try_join!(succeed(1), succeed(2)).await });
+        assert_eq!(result, Ok((1, 2)));
+    }
+
+    #[test]
+    fn try_join_macro_collects_every_error() {
+        let mut fix = Fixture::default();
+        let (mut fix_a, mut fix_b) = (Fixture::default(), Fixture::default());
+
+        fix.tag_location("join", CodeLocation::here().down_by(2));
+        let result = block_on(async {
+            try_join!(fail(&mut fix_a, "a"), succeed(1), fail(&mut fix_b, "b")).await
+        });
+
+        let (errors, trace) = result.err_trace().unwrap();
+        fix.assert_stack_matches_tags(&trace, &["join"]);
+        assert_eq!(errors.errors().collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    /// Returns `Pending` once before completing.
+    struct YieldOnce(bool);
+
+    impl Future for YieldOnce {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    async fn fail_later(fix: &mut Fixture, tag: &'static str) -> Result<u32, String> {
+        YieldOnce(false).await;
+        fail(fix, tag).await
+    }
+
+    #[test]
+    fn select_returns_first_to_complete() {
+        let mut fix_a = Fixture::default();
+        let futures: Vec<BoxFuture<'_>> =
+            vec![Box::pin(fail_later(&mut fix_a, "a")), Box::pin(succeed(2))];
+        assert_eq!(block_on(select(futures)), Ok((2, 1)));
+    }
+
+    #[test]
+    fn select_with_losers_collects_loser_errors() {
+        let mut fix = Fixture::default();
+        let (mut fix_a, mut fix_b) = (Fixture::default(), Fixture::default());
+
+        let futures: Vec<BoxFuture<'_>> = vec![
+            Box::pin(fail_later(&mut fix_a, "a")),
+            Box::pin(fail(&mut fix_b, "b")),
+        ];
+        fix.tag_location("select", CodeLocation::here().down_by(1));
+        let (errors, trace) = block_on(select(futures).with_losers()).err_trace().unwrap();
+        fix.assert_stack_matches_tags(&trace, &["select"]);
+        assert_eq!(errors.errors().collect::<Vec<_>>(), vec!["b", "a"]);
+    }
+
+    #[test]
+    fn select_without_losers_keeps_only_winner() {
+        let (mut fix_a, mut fix_b) = (Fixture::default(), Fixture::default());
+        let futures: Vec<BoxFuture<'_>> = vec![
+            Box::pin(fail_later(&mut fix_a, "a")),
+            Box::pin(fail(&mut fix_b, "b")),
+        ];
+        let errors = block_on(select(futures)).unwrap_err();
+        assert_eq!(errors.errors().collect::<Vec<_>>(), vec!["b"]);
+    }
+
+    #[test]
+    fn select_ok_skips_failures() {
+        let mut fix_a = Fixture::default();
+        let futures: Vec<BoxFuture<'_>> = vec![
+            Box::pin(fail(&mut fix_a, "a")),
+            Box::pin(async {
+                YieldOnce(false).await;
+                succeed(3).await
+            }),
+        ];
+        assert_eq!(block_on(select_ok(futures)), Ok((3, 1)));
+    }
+}
@@ -0,0 +1,219 @@
+//! Rendering the relationships between errors as a tree.
+//!
+//! A failure with several sources is hard to follow when each error is
+//! reported separately. An [`ErrorGraph`] collects an error, the errors that
+//! caused it (see [`Error::source()`]), and the errors it aggregates (see
+//! [`ErrorSet`]), each with its own trace, and renders them as a single
+//! indented tree.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::error::WrappedError;
+//! use propagate::graph::ErrorGraph;
+//! use propagate::{CodeLocation, ErrorSet, ErrorTrace};
+//! use std::io;
+//!
+//! let mut errors: ErrorSet<WrappedError<&str, io::Error>> = ErrorSet::new();
+//! errors.push(
+//!     WrappedError::new("failed to load a.toml", io::ErrorKind::NotFound.into()),
+//!     ErrorTrace(vec![CodeLocation::new("src/config.rs", 12)]),
+//! );
+//! errors.push(
+//!     WrappedError::new("failed to load b.toml", io::ErrorKind::TimedOut.into()),
+//!     ErrorTrace(vec![CodeLocation::new("src/config.rs", 12)]),
+//! );
+//!
+//! let graph = ErrorGraph::from_set("failed to load 2 config files", &errors);
+//! assert_eq!(
+//!     graph.to_string(),
+//!     "\
+//! failed to load 2 config files
+//! |-- failed to load a.toml
+//! |   |   0: src/config.rs:12
+//! |   `-- caused by: entity not found
+//! `-- failed to load b.toml
+//!     |   0: src/config.rs:12
+//!     `-- caused by: timed out
+//! "
+//! );
+//! ```
+//!
+//! [`ErrorSet`]: crate::ErrorSet
+
+use crate::error::{ErrorSet, TracedError};
+
+use std::error::Error;
+use std::fmt;
+
+/// A node in a tree of related errors.
+///
+/// Each node has a message, the lines of its trace (if it has one), and its
+/// children. See the [module documentation](self) for more information.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct ErrorGraph {
+    message: String,
+    trace: Vec<String>,
+    children: Vec<ErrorGraph>,
+}
+
+impl ErrorGraph {
+    /// Constructs a node with the given message, no trace, and no children.
+    pub fn new(message: impl fmt::Display) -> Self {
+        Self {
+            message: message.to_string(),
+            trace: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Constructs a node for `error`, with a child for each error in its
+    /// chain of sources.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::error::WrappedError;
+    /// use propagate::graph::ErrorGraph;
+    ///
+    /// let err = WrappedError::new("failed to start", std::fmt::Error);
+    /// let graph = ErrorGraph::from_error(&err);
+    /// assert_eq!(
+    ///     graph.to_string(),
+    ///     "failed to start\n`-- caused by: an error occurred when formatting an argument\n",
+    /// );
+    /// ```
+    pub fn from_error(error: &dyn Error) -> Self {
+        let mut node = Self::new(error);
+        let mut causes = Vec::new();
+        let mut source = error.source();
+        while let Some(cause) = source {
+            causes.push(Self::new(format_args!("caused by: {}", cause)));
+            source = cause.source();
+        }
+
+        // Each cause is a child of the one before it.
+        if let Some(last) = causes.pop() {
+            let chain = causes.into_iter().rev().fold(last, |child, mut parent| {
+                parent.children.push(child);
+                parent
+            });
+            node.children.push(chain);
+        }
+
+        node
+    }
+
+    /// Constructs a node for a traced error, showing its trace and its chain
+    /// of sources.
+    pub fn from_traced<E: Error, S: fmt::Display>(error: &TracedError<E, S>) -> Self {
+        Self::from_error(error.error()).with_trace(error.stack())
+    }
+
+    /// Constructs a node with the given message, with a child for each error
+    /// of `errors`.
+    pub fn from_set<E: Error, S: fmt::Display>(
+        message: impl fmt::Display,
+        errors: &ErrorSet<E, S>,
+    ) -> Self {
+        let mut node = Self::new(message);
+        for (error, trace) in errors {
+            node.push(Self::from_error(error).with_trace(trace));
+        }
+        node
+    }
+
+    /// Sets the trace shown for this node, consuming and returning `self`.
+    ///
+    /// The trace is rendered with its [`Display`][fmt::Display]
+    /// implementation, one frame per line.
+    pub fn with_trace(mut self, trace: impl fmt::Display) -> Self {
+        self.trace = trace
+            .to_string()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect();
+        self
+    }
+
+    /// Adds `child` as the last child of this node.
+    pub fn push(&mut self, child: ErrorGraph) {
+        self.children.push(child);
+    }
+
+    /// Returns the message of this node.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the children of this node.
+    pub fn children(&self) -> &[ErrorGraph] {
+        &self.children
+    }
+
+    fn render(&self, prefix: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let trace_prefix = if self.children.is_empty() {
+            "    "
+        } else {
+            "|   "
+        };
+        for line in &self.trace {
+            writeln!(f, "{}{}{}", prefix, trace_prefix, line)?;
+        }
+
+        for (index, child) in self.children.iter().enumerate() {
+            let last = index + 1 == self.children.len();
+            let (branch, indent) = if last {
+                ("`-- ", "    ")
+            } else {
+                ("|-- ", "|   ")
+            };
+            writeln!(f, "{}{}{}", prefix, branch, child.message)?;
+            child.render(&format!("{}{}", prefix, indent), f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Renders the tree with one node or trace frame per line, with ASCII art
+/// connecting each node to its children.
+impl fmt::Display for ErrorGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.message)?;
+        self.render("", f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::WrappedError;
+    use crate::{CodeLocation, ErrorTrace};
+
+    #[test]
+    fn renders_nested_causes_and_traces() {
+        let inner = WrappedError::new("failed to parse", fmt::Error);
+        let outer = WrappedError::new("failed to load", inner);
+        let traced = TracedError::new(
+            outer,
+            ErrorTrace(vec![
+                CodeLocation::new("src/a.rs", 1),
+                CodeLocation::new("src/b.rs", 2),
+            ]),
+        );
+
+        assert_eq!(
+            ErrorGraph::from_traced(&traced).to_string(),
+            "\
+failed to load
+|   0: src/a.rs:1
+|   1: src/b.rs:2
+`-- caused by: failed to parse
+    `-- caused by: an error occurred when formatting an argument
+"
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Helpers for iterators over traced results.
+
+use crate::error::TracedError;
+use crate::result::Result::{self, Err, Ok};
+use crate::trace::Traced;
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::panic;
+
+/// Processes an iterator of results as an iterator of their success values.
+///
+/// The closure `processor` is given an iterator that yields the `T` value of
+/// each [`Ok`] result. The first [`Err`] result ends that iterator, and is
+/// returned in place of the closure's output, with the location of the call to
+/// `process_results` pushed to its trace.
+///
+/// This allows streaming computations (sums, folds, searches, etc.) over
+/// fallible items without first collecting them into a `Vec`.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::iter::process_results;
+/// use std::num::ParseIntError;
+///
+/// let inputs = ["1", "2", "3"];
+/// let results = inputs.iter().map(|s| -> propagate::Result<u32, ParseIntError> {
+///     propagate::Ok(s.parse::<u32>()?)
+/// });
+/// let sum = process_results(results, |iter| iter.sum::<u32>());
+/// assert_eq!(sum.unwrap(), 6);
+///
+/// let inputs = ["1", "two", "3"];
+/// let results = inputs.iter().map(|s| -> propagate::Result<u32, ParseIntError> {
+///     propagate::Ok(s.parse::<u32>()?)
+/// });
+/// let sum = process_results(results, |iter| iter.sum::<u32>());
+/// assert!(sum.is_err());
+/// ```
+///
+/// [`Ok`]: crate::Ok
+/// [`Err`]: crate::Err
+#[track_caller]
+pub fn process_results<I, F, T, E, S, R>(iter: I, processor: F) -> Result<R, E, S>
+where
+    I: IntoIterator<Item = Result<T, E, S>>,
+    F: FnOnce(ProcessResults<'_, I::IntoIter, E, S>) -> R,
+    S: Traced,
+{
+    let mut error = None;
+    let output = processor(ProcessResults {
+        iter: iter.into_iter(),
+        error: &mut error,
+    });
+
+    match error {
+        None => Ok(output),
+        Some((err, mut trace)) => {
+            crate::result::push_propagated::<E, S>(&mut trace, panic::Location::caller());
+            Err(err, trace)
+        }
+    }
+}
+
+/// An iterator over the success values of an iterator of results.
+///
+/// See [`process_results`] for details.
+pub struct ProcessResults<'a, I, E, S> {
+    iter: I,
+    error: &'a mut Option<(E, S)>,
+}
+
+impl<'a, I, T, E, S> Iterator for ProcessResults<'a, I, E, S>
+where
+    I: Iterator<Item = Result<T, E, S>>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        match self.iter.next() {
+            Some(Ok(t)) => Some(t),
+            Some(Err(err, trace)) => {
+                *self.error = Some((err, trace));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.error.is_some() {
+            (0, Some(0))
+        } else {
+            (0, self.iter.size_hint().1)
+        }
+    }
+}
+
+/// Extends iterators with [`traced()`][TracedIterator::traced] and
+/// [`partition_traced()`][TracedIterator::partition_traced].
+pub trait TracedIterator: Iterator + Sized {
+    /// Wraps the iterator so that the results of its [`map()`][TracedIter::map]
+    /// adapter say which element failed.
+    ///
+    /// When the closure given to `map()` fails for an element, a context
+    /// frame with the index of the element is pushed to the trace of the
+    /// error, at the location of the call to `map()`. Call
+    /// [`with_items()`][TracedIter::with_items] to include a `Debug`
+    /// rendering of the element as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::iter::TracedIterator;
+    /// use std::num::ParseIntError;
+    ///
+    /// fn parse(s: &str) -> propagate::Result<u32, ParseIntError> {
+    ///     propagate::Ok(s.parse::<u32>()?)
+    /// }
+    ///
+    /// let sizes: propagate::Result<Vec<u32>, _> = ["1", "2", "three"]
+    ///     .iter()
+    ///     .traced()
+    ///     .with_items()
+    ///     .map(|s| parse(s))
+    ///     .try_collect();
+    ///
+    /// let (_, trace) = sizes.err_trace().unwrap();
+    /// let messages: Vec<_> = trace.0.iter().filter_map(|frame| frame.message()).collect();
+    /// assert_eq!(messages, ["item 2: \"three\""]);
+    /// ```
+    fn traced(self) -> TracedIter<Self> {
+        TracedIter {
+            iter: self,
+            index: 0,
+            describe: None,
+        }
+    }
+
+    /// Consumes an iterator of results, returning the success values and the
+    /// errors separately, in order.
+    ///
+    /// The location of the caller is pushed to the trace of each error. This
+    /// suits best-effort batch operations, which keep going after a failure
+    /// but need the full diagnostics of every failure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::iter::TracedIterator;
+    /// use std::num::ParseIntError;
+    ///
+    /// fn parse(s: &str) -> propagate::Result<u32, ParseIntError> {
+    ///     propagate::Ok(s.parse::<u32>()?)
+    /// }
+    ///
+    /// let (sizes, errors) = ["1", "two", "3", "four"]
+    ///     .iter()
+    ///     .map(|s| parse(s))
+    ///     .partition_traced();
+    ///
+    /// assert_eq!(sizes, [1, 3]);
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[0].stack().0.len(), 2);
+    /// ```
+    #[track_caller]
+    fn partition_traced<T, E, S>(self) -> (Vec<T>, Vec<TracedError<E, S>>)
+    where
+        Self: Iterator<Item = Result<T, E, S>>,
+        S: Traced,
+    {
+        let location = panic::Location::caller();
+        let mut successes = Vec::new();
+        let mut errors = Vec::new();
+        for result in self {
+            match result {
+                Ok(t) => successes.push(t),
+                Err(err, mut trace) => {
+                    crate::result::push_propagated::<E, S>(&mut trace, location);
+                    errors.push(TracedError::new(err, trace));
+                }
+            }
+        }
+        (successes, errors)
+    }
+}
+
+impl<I: Iterator> TracedIterator for I {}
+
+/// An iterator that counts its elements, so that the results of
+/// [`map()`][TracedIter::map] say which element failed.
+///
+/// See [`TracedIterator::traced()`] for details.
+pub struct TracedIter<I: Iterator> {
+    iter: I,
+    index: usize,
+    describe: Option<fn(&I::Item) -> String>,
+}
+
+impl<I: Iterator> TracedIter<I> {
+    /// Includes a `Debug` rendering of the failed element in the frames
+    /// pushed by [`map()`][TracedIter::map], consuming and returning `self`.
+    ///
+    /// Since the closure consumes the element, every element is rendered
+    /// before it is passed to the closure.
+    pub fn with_items(self) -> Self
+    where
+        I::Item: fmt::Debug,
+    {
+        Self {
+            describe: Some(|item| format!("{:?}", item)),
+            ..self
+        }
+    }
+
+    /// Applies the fallible closure `f` to each element.
+    ///
+    /// If `f` fails, a context frame naming the element is pushed to the
+    /// trace of the error, at the location of the caller.
+    #[track_caller]
+    pub fn map<F, T, E, S>(self, f: F) -> TracedMap<I, F>
+    where
+        F: FnMut(I::Item) -> Result<T, E, S>,
+        S: Traced,
+    {
+        TracedMap {
+            inner: self,
+            f,
+            location: panic::Location::caller(),
+        }
+    }
+}
+
+impl<I: Iterator> Iterator for TracedIter<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let item = self.iter.next()?;
+        self.index += 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator that applies a fallible closure to each element, recording
+/// which element failed.
+///
+/// See [`TracedIterator::traced()`] for details.
+pub struct TracedMap<I: Iterator, F> {
+    inner: TracedIter<I>,
+    f: F,
+    location: &'static panic::Location<'static>,
+}
+
+impl<I, F, T, E, S> TracedMap<I, F>
+where
+    I: Iterator,
+    F: FnMut(I::Item) -> Result<T, E, S>,
+    S: Traced,
+{
+    /// Collects the success values, or returns the first error, with the
+    /// location of the caller pushed to its trace.
+    #[track_caller]
+    pub fn try_collect<B: FromIterator<T>>(self) -> Result<B, E, S> {
+        process_results(self, |iter| iter.collect())
+    }
+}
+
+impl<I, F, T, E, S> Iterator for TracedMap<I, F>
+where
+    I: Iterator,
+    F: FnMut(I::Item) -> Result<T, E, S>,
+    S: Traced,
+{
+    type Item = Result<T, E, S>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.inner.next()?;
+        let index = self.inner.index - 1;
+        let description = self.inner.describe.map(|describe| describe(&item));
+
+        Some(match (self.f)(item) {
+            Ok(t) => Ok(t),
+            Err(err, mut trace) => {
+                let location = self.location;
+                match description {
+                    Some(description) => crate::result::push_context::<E, S>(
+                        &mut trace,
+                        location,
+                        &format_args!("item {}: {}", index, description),
+                    ),
+                    None => crate::result::push_context::<E, S>(
+                        &mut trace,
+                        location,
+                        &format_args!("item {}", index),
+                    ),
+                }
+                Err(err, trace)
+            }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::Fixture;
+    use crate::CodeLocation;
+
+    #[test]
+    fn process_results_stops_at_first_error() {
+        let mut fix = Fixture::default();
+
+        let mut seen = Vec::new();
+        fix.tag_location("first", CodeLocation::here().down_by(3));
+        let results: Vec<Result<u32, &str>> = vec![
+            Ok(1),
+            Result::new_err("first"),
+            Ok(2),
+            Result::new_err("second"),
+        ];
+        fix.tag_location("process", CodeLocation::here().down_by(1));
+        let result = process_results(results, |iter| seen.extend(iter));
+
+        assert_eq!(seen, vec![1]);
+        let (err, trace) = result.err_trace().unwrap();
+        assert_eq!(err, "first");
+        fix.assert_stack_matches_tags(&trace, &["first", "process"]);
+    }
+
+    #[test]
+    fn traced_map_records_failing_index() {
+        let results: Vec<Result<u32, &str>> = vec![Ok(1), Ok(2), Result::new_err("bad")];
+
+        let map_at = CodeLocation::here().down_by(1);
+        let mapped = results.into_iter().traced().map(|result| result);
+        let collect_at = CodeLocation::here().down_by(1);
+        let (err, trace) = mapped.try_collect::<Vec<u32>>().err_trace().unwrap();
+
+        assert_eq!(err, "bad");
+        assert_eq!(trace.0[1..], [map_at.with_message("item 2"), collect_at]);
+    }
+}
@@ -145,6 +145,7 @@
 //! [`propagate::Result`]: crate::Result
 //! [`try` blocks]: https://doc.rust-lang.org/beta/unstable-book/language-features/try-blocks.html
 
+#![feature(allocator_api)]
 #![feature(try_trait_v2)]
 #![feature(control_flow_enum)]
 #![feature(termination_trait_lib)]
@@ -157,16 +158,158 @@
 //   - i.e., should more methods preserve the error trace?
 // * Put `MyError` into shared example module?
 
+pub mod channel;
+pub mod debug;
+pub mod diff;
+pub mod encode;
+pub mod error;
+pub mod export;
+pub mod fmt;
+pub mod fs;
+pub mod future;
+pub mod graph;
+pub mod iter;
+mod macros;
+pub mod observe;
+pub mod process;
+pub mod redact;
+pub mod report;
 pub mod result;
+pub mod scope;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod sink;
+#[cfg(feature = "trace-sites")]
+pub mod sites;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "tracing")]
+pub mod subscriber;
+pub mod sync;
+pub mod thread;
+#[cfg(feature = "tower")]
+pub mod tower;
 pub mod trace;
+#[cfg(feature = "usdt")]
+pub mod usdt;
+pub mod validated;
+#[cfg(feature = "warp")]
+pub mod warp;
 
 #[doc(inline)]
 pub use self::{
+    error::{
+        AggregateError, CategorizedError, CodedError, DebugError, ErrorSet, TracedError,
+        WrappedError,
+    },
     result::Result,
-    trace::{CodeLocation, ErrorTrace, Traced},
+    trace::{
+        Append, ArenaStack, CodeLocation, CountingStack, CowStack, ErrorTrace, FrameKind,
+        HashingStack, LocationStack, PooledStack, TimedStack, Traced,
+    },
+    validated::Validated,
 };
 
 pub use self::result::Result::{Err, Ok};
+#[doc(inline)]
+pub use self::scope::scope;
+
+#[cfg(feature = "trace-sites")]
+#[doc(inline)]
+pub use self::sites::trace_sites;
+#[cfg(feature = "stats")]
+#[doc(inline)]
+pub use self::stats::stats;
+
+/// Derives traced constructors for each variant of an error enum.
+///
+/// For every variant `FooBar`, two associated functions are generated, which
+/// take the fields of the variant (in order) as arguments:
+///
+/// * `foo_bar_err()`, which returns a new error [`Result`], and
+/// * `foo_bar_traced()`, which returns a new [`TracedError`].
+///
+/// Both are `#[track_caller]`, so the trace starts where they are called.
+///
+/// Available with the `derive` feature.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::Constructors;
+///
+/// #[derive(Debug, Constructors)]
+/// enum MyError {
+///     TooSmall(u64),
+///     NotFound { path: String },
+///     Unlucky,
+/// }
+///
+/// fn check(size: u64) -> propagate::Result<u64, MyError> {
+///     if size < 1024 {
+///         return MyError::too_small_err(size);
+///     }
+///     propagate::Ok(size)
+/// }
+///
+/// assert!(matches!(check(10).unwrap_err(), MyError::TooSmall(10)));
+///
+/// let traced = MyError::not_found_traced("a.toml".to_string());
+/// assert_eq!(traced.stack().0.len(), 1);
+///
+/// let result: propagate::Result<(), MyError> = MyError::unlucky_err();
+/// assert!(result.is_err());
+/// ```
+#[cfg(feature = "derive")]
+pub use propagate_macros::Constructors;
+
+/// Records a function in the trace of the errors it returns.
+///
+/// When the function returns an error, a context frame (see
+/// [`Result::context()`]) naming the function is pushed to its trace, at the
+/// location of the function's name. With `args(...)`, the listed arguments
+/// are recorded in the frame as well, formatted with [`Debug`]; arguments
+/// wrapped in `redact(...)` are recorded as `[redacted]`, for values that
+/// must not appear in traces at all.
+///
+/// Since the function may move its arguments, they are formatted before its
+/// body runs, whether or not it fails. This is skipped while trace capture is
+/// disabled (see [`trace::capture_enabled()`]), but `args(...)` is best kept
+/// to functions that are not on a hot path. The frame is also subject to the
+/// installed [`Redactor`][redact::Redactor] when the trace is exported.
+///
+/// The body of the function is expanded in place, so `#[track_caller]`,
+/// `async` and `impl Trait` results work as usual; in a `#[track_caller]`
+/// function, the frame is at the location of the caller, like the others. The
+/// frame is pushed like those of [`Result::context()`], so it is only recorded
+/// while capture is enabled. Errors returned by `?` or `return` inside macro
+/// invocations (e.g. `vec![parse(a)?]`) are returned without the frame, since
+/// the attribute cannot see into them.
+///
+/// The function must return a [`Result`], and must not be `const`.
+///
+/// Available with the `derive` feature.
+///
+/// # Examples
+///
+/// ```
+/// #[propagate::traced(args(user_id, redact(token)))]
+/// fn load_profile(user_id: u32, token: &str) -> propagate::Result<String, &'static str> {
+///     let _ = token;
+///     propagate::Result::new_err("no such user")
+/// }
+///
+/// #[propagate::traced]
+/// fn show_profile() -> propagate::Result<String, &'static str> {
+///     propagate::Ok(load_profile(42, "hunter2")?)
+/// }
+///
+/// let (_, trace) = show_profile().err_trace().unwrap();
+/// let messages: Vec<_> = trace.0.iter().filter_map(|frame| frame.message()).collect();
+/// assert_eq!(messages, ["load_profile(user_id = 42, token = [redacted])", "show_profile"]);
+/// ```
+#[cfg(feature = "derive")]
+pub use propagate_macros::traced;
 
 #[cfg(test)]
 mod test;
@@ -0,0 +1,245 @@
+//! Defines macros for constructing traced errors.
+
+/// Returns early with a traced error if two expressions are not equal to each
+/// other (using [`PartialEq`]).
+///
+/// The error is constructed using [`Result::new_err()`] from a [`String`]
+/// message that includes both operands (formatted using [`Debug`]), so the
+/// function's error type must implement `From<String>`. The location of the
+/// macro invocation is the first frame of the error trace.
+///
+/// Like [`assert_eq!`], this macro has a second form, where a custom message
+/// can be provided.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::ensure_eq;
+///
+/// fn check_len(v: &[u8]) -> propagate::Result<(), String> {
+///     ensure_eq!(v.len(), 4);
+///     ensure_eq!(v[0], 0x7f, "bad magic number");
+///     propagate::Ok(())
+/// }
+///
+/// assert!(check_len(&[0x7f, 1, 2, 3]).is_ok());
+/// assert_eq!(
+///     check_len(&[0x7f]).unwrap_err(),
+///     "condition `v.len() == 4` failed (left: `1`, right: `4`)",
+/// );
+/// assert_eq!(
+///     check_len(&[0, 1, 2, 3]).unwrap_err(),
+///     "bad magic number (left: `0`, right: `127`)",
+/// );
+/// ```
+///
+/// [`Result::new_err()`]: crate::Result::new_err
+/// [`Debug`]: std::fmt::Debug
+#[macro_export]
+macro_rules! ensure_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left_val, right_val) => {
+                if !(*left_val == *right_val) {
+                    return $crate::Result::new_err(::std::format!(
+                        "condition `{} == {}` failed (left: `{:?}`, right: `{:?}`)",
+                        ::std::stringify!($left),
+                        ::std::stringify!($right),
+                        &*left_val,
+                        &*right_val,
+                    ));
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left_val, right_val) => {
+                if !(*left_val == *right_val) {
+                    return $crate::Result::new_err(::std::format!(
+                        "{} (left: `{:?}`, right: `{:?}`)",
+                        ::std::format_args!($($arg)+),
+                        &*left_val,
+                        &*right_val,
+                    ));
+                }
+            }
+        }
+    };
+}
+
+/// Returns early with a traced error if two expressions are equal to each
+/// other (using [`PartialEq`]).
+///
+/// This is the inverse of [`ensure_eq!`]; see its documentation for details.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::ensure_ne;
+///
+/// fn divide(a: u32, b: u32) -> propagate::Result<u32, String> {
+///     ensure_ne!(b, 0, "cannot divide {} by zero", a);
+///     propagate::Ok(a / b)
+/// }
+///
+/// assert_eq!(divide(6, 3).unwrap(), 2);
+/// assert_eq!(
+///     divide(6, 0).unwrap_err(),
+///     "cannot divide 6 by zero (left: `0`, right: `0`)",
+/// );
+/// ```
+#[macro_export]
+macro_rules! ensure_ne {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left_val, right_val) => {
+                if *left_val == *right_val {
+                    return $crate::Result::new_err(::std::format!(
+                        "condition `{} != {}` failed (left: `{:?}`, right: `{:?}`)",
+                        ::std::stringify!($left),
+                        ::std::stringify!($right),
+                        &*left_val,
+                        &*right_val,
+                    ));
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left_val, right_val) => {
+                if *left_val == *right_val {
+                    return $crate::Result::new_err(::std::format!(
+                        "{} (left: `{:?}`, right: `{:?}`)",
+                        ::std::format_args!($($arg)+),
+                        &*left_val,
+                        &*right_val,
+                    ));
+                }
+            }
+        }
+    };
+}
+
+/// Asserts that a result is an error equal to an expected value (using
+/// [`PartialEq`]), ignoring its trace.
+///
+/// Comparing traced results with [`assert_eq!`] compares their traces as well,
+/// which rarely match the expected value. This only compares the error value.
+/// On failure, the panic message contains both values (formatted using
+/// [`Debug`]) and the actual trace. Like [`assert_eq!`], a custom message can
+/// be provided.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::assert_err_eq;
+///
+/// fn parse(s: &str) -> propagate::Result<u32, String> {
+///     if s.is_empty() {
+///         return propagate::Result::new_err("empty input".to_string());
+///     }
+///     propagate::Ok(s.len() as u32)
+/// }
+///
+/// assert_err_eq!(parse(""), "empty input");
+/// assert_err_eq!(parse(""), "empty input", "parsing {:?}", "");
+/// ```
+///
+/// [`Debug`]: std::fmt::Debug
+#[macro_export]
+macro_rules! assert_err_eq {
+    ($result:expr, $expected:expr $(,)?) => {
+        $crate::assert_err_eq!($result, $expected, "errors are not equal")
+    };
+    ($result:expr, $expected:expr, $($arg:tt)+) => {
+        match (&$result, &$expected) {
+            ($crate::Err(err, trace), expected) => {
+                if !(*err == *expected) {
+                    ::std::panic!(
+                        "{}\n  actual: `{:?}`\nexpected: `{:?}`\n\nReturn Trace: {}",
+                        ::std::format_args!($($arg)+),
+                        err,
+                        &*expected,
+                        trace,
+                    );
+                }
+            }
+            ($crate::Ok(value), _) => {
+                ::std::panic!(
+                    "{}: expected an error, got `Ok({:?})`",
+                    ::std::format_args!($($arg)+),
+                    value,
+                );
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use crate::test::Fixture;
+    use crate::{CodeLocation, Ok, Result};
+
+    #[derive(Debug, PartialEq)]
+    struct MyError(String);
+
+    impl From<String> for MyError {
+        fn from(s: String) -> Self {
+            Self(s)
+        }
+    }
+
+    fn check_eq(fix: &mut Fixture, a: u32, b: u32) -> Result<(), MyError> {
+        fix.tag_location("ensure", CodeLocation::here().down_by(1));
+        ensure_eq!(a, b);
+        Ok(())
+    }
+
+    fn check_ne(fix: &mut Fixture, a: u32, b: u32) -> Result<(), MyError> {
+        fix.tag_location("ensure", CodeLocation::here().down_by(1));
+        ensure_ne!(a, b, "values must differ");
+        Ok(())
+    }
+
+    #[test]
+    fn ensure_eq_passes() {
+        let mut fix = Fixture::default();
+        assert!(check_eq(&mut fix, 1, 1).is_ok());
+    }
+
+    #[test]
+    fn ensure_eq_starts_trace_at_macro_site() {
+        let mut fix = Fixture::default();
+        let (err, trace) = check_eq(&mut fix, 1, 2).err_trace().unwrap();
+        assert_eq!(err.0, "condition `a == b` failed (left: `1`, right: `2`)");
+        fix.assert_stack_matches_tags(&trace, &["ensure"]);
+    }
+
+    #[test]
+    fn ensure_ne_starts_trace_at_macro_site() {
+        let mut fix = Fixture::default();
+        assert!(check_ne(&mut fix, 1, 2).is_ok());
+
+        let (err, trace) = check_ne(&mut fix, 3, 3).err_trace().unwrap();
+        assert_eq!(err.0, "values must differ (left: `3`, right: `3`)");
+        fix.assert_stack_matches_tags(&trace, &["ensure"]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "  actual: `1`\nexpected: `2`\n\nReturn Trace: \n   0: src/macros.rs:"
+    )]
+    fn assert_err_eq_panics_with_trace() {
+        let result: Result<(), u32> = Result::new_err(1u32);
+        assert_err_eq!(result, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "errors are not equal: expected an error, got `Ok(())`")]
+    fn assert_err_eq_panics_on_ok() {
+        let result: Result<(), u32> = Ok(());
+        assert_err_eq!(result, 2);
+    }
+}
@@ -0,0 +1,243 @@
+//! Observing the lifecycle of traced errors.
+//!
+//! When an observer is installed with [`set_observer()`], it is notified of an
+//! [`Event`] at each stage of an error's life:
+//!
+//! * [`Created`][EventKind::Created] when a new trace is started, e.g. by
+//!   [`Result::new_err()`] or by applying `?` to a [`std::result::Result`].
+//! * [`Propagated`][EventKind::Propagated] each time the `?` operator pushes
+//!   a location to the trace.
+//! * [`Converted`][EventKind::Converted] when the error is converted to a
+//!   [`std::result::Result`] with [`Result::to_std()`], dropping its trace.
+//! * [`Handled`][EventKind::Handled] when the error is consumed without being
+//!   propagated further, by [`Result::ok()`], [`Result::unwrap_or()`],
+//!   [`Result::unwrap_or_else()`], [`Result::unwrap_or_default()`],
+//!   [`Result::consume_err()`], or explicitly by [`Result::handled_here()`].
+//!
+//! Events of the same error can be correlated using
+//! [`Event::correlation_id()`], if the stack type carries an identifier (see
+//! [`Traced::correlation_id()`]). This makes it possible to build dashboards
+//! of error flow through a codebase, or to audit whether errors are being
+//! swallowed in the right places. Nothing is recorded until an observer is
+//! installed.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::observe::{self, Event, EventKind};
+//!
+//! fn report(event: &Event) {
+//!     if event.kind() == EventKind::Handled {
+//!         eprintln!("{} handled at {}", event.error_type(), event.location());
+//!     }
+//! }
+//!
+//! observe::set_observer(report);
+//!
+//! let x: propagate::Result<u32, &str> = propagate::Result::new_err("Nothing here");
+//! assert_eq!(x.unwrap_or(0), 0);
+//!
+//! observe::clear_observer();
+//! ```
+//!
+//! [`Result::new_err()`]: crate::Result::new_err
+//! [`Result::to_std()`]: crate::Result::to_std
+//! [`Result::ok()`]: crate::Result::ok
+//! [`Result::unwrap_or()`]: crate::Result::unwrap_or
+//! [`Result::unwrap_or_else()`]: crate::Result::unwrap_or_else
+//! [`Result::unwrap_or_default()`]: crate::Result::unwrap_or_default
+//! [`Result::consume_err()`]: crate::Result::consume_err
+//! [`Result::handled_here()`]: crate::Result::handled_here
+//! [`Traced::correlation_id()`]: crate::Traced::correlation_id
+
+use crate::trace::Traced;
+
+use std::any;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+/// The stage of an error's life that an [`Event`] describes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// A new trace was started at the location of the event.
+    Created,
+    /// The error was propagated through the location of the event.
+    Propagated,
+    /// The error was converted to a [`std::result::Result`], dropping its
+    /// trace.
+    Converted,
+    /// The error was handled at the location of the event.
+    Handled,
+}
+
+/// Describes something that happened to a traced error.
+#[derive(Copy, Clone, Debug)]
+pub struct Event {
+    kind: EventKind,
+    location: &'static panic::Location<'static>,
+    error_type: &'static str,
+    correlation_id: Option<u64>,
+}
+
+impl Event {
+    /// Returns what happened to the error.
+    #[inline]
+    pub const fn kind(&self) -> EventKind {
+        self.kind
+    }
+
+    /// Returns the location where it happened.
+    #[inline]
+    pub const fn location(&self) -> &'static panic::Location<'static> {
+        self.location
+    }
+
+    /// Returns the name of the type of the error.
+    #[inline]
+    pub const fn error_type(&self) -> &'static str {
+        self.error_type
+    }
+
+    /// Returns the identifier shared by all events of the same error, if
+    /// known.
+    ///
+    /// This is `None` if the stack type does not carry an identifier, and for
+    /// [`Converted`][EventKind::Converted] and [`Handled`][EventKind::Handled]
+    /// events, which are reported by operations that do not require the stack
+    /// type to implement [`Traced`].
+    #[inline]
+    pub const fn correlation_id(&self) -> Option<u64> {
+        self.correlation_id
+    }
+}
+
+/// A function that is notified of each [`Event`].
+pub type Observer = fn(&Event);
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static OBSERVER: RwLock<Option<Observer>> = RwLock::new(None);
+
+/// Installs `observer` to be notified of every [`Event`], replacing any
+/// previously installed observer.
+pub fn set_observer(observer: Observer) {
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = Some(observer);
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Removes the installed observer, if any.
+pub fn clear_observer() {
+    ENABLED.store(false, Ordering::Release);
+    *OBSERVER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Notifies the installed observer, if any, of an event for an error of type
+/// `E` whose stack is not available.
+#[inline]
+pub(crate) fn record<E>(kind: EventKind, location: &'static panic::Location<'static>) {
+    #[cfg(feature = "stats")]
+    crate::stats::count(kind, location);
+    #[cfg(feature = "usdt")]
+    crate::usdt::fire(kind, location);
+    if ENABLED.load(Ordering::Acquire) {
+        notify(Event {
+            kind,
+            location,
+            error_type: any::type_name::<E>(),
+            correlation_id: None,
+        });
+    }
+}
+
+/// Notifies the installed observer, if any, of an event for an error of type
+/// `E` with the given stack.
+#[inline]
+pub(crate) fn record_traced<E, S: Traced>(
+    kind: EventKind,
+    location: &'static panic::Location<'static>,
+    stack: &S,
+) {
+    #[cfg(feature = "stats")]
+    crate::stats::count_traced(kind, location, stack);
+    #[cfg(feature = "usdt")]
+    crate::usdt::fire(kind, location);
+    if ENABLED.load(Ordering::Acquire) {
+        notify(Event {
+            kind,
+            location,
+            error_type: any::type_name::<E>(),
+            correlation_id: stack.correlation_id(),
+        });
+    }
+}
+
+#[cold]
+fn notify(event: Event) {
+    let observer = *OBSERVER.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(observer) = observer {
+        observer(&event);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CodeLocation, Result};
+    use std::cell::RefCell;
+
+    thread_local! {
+        static SEEN: RefCell<Vec<(EventKind, CodeLocation)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_event(event: &Event) {
+        let seen = (event.kind(), event.location().into());
+        SEEN.with(|events| events.borrow_mut().push(seen));
+    }
+
+    #[test]
+    fn reports_lifecycle_events() {
+        set_observer(record_event);
+
+        let ok: Result<u32, &str> = crate::Ok(2);
+        assert_eq!(ok.unwrap_or(0), 2);
+
+        let created_at = CodeLocation::here().down_by(2);
+        let propagated_at = CodeLocation::here().down_by(2);
+        let inner = || -> Result<u32, &str> { Result::new_err("Nothing here") };
+        let outer = || -> Result<u32, &str> { crate::Ok(inner()?) };
+
+        let handled_at = CodeLocation::here().down_by(1);
+        assert_eq!(outer().unwrap_or(0), 0);
+
+        let err: Result<u32, &str> = Result::new_err("Nothing here");
+        let converted_at = CodeLocation::here().down_by(1);
+        let _ = err.to_std();
+
+        let err: Result<u32, &str> = Result::new_err("Nothing here");
+        let explicit_at = CodeLocation::here().down_by(1);
+        err.handled_here();
+
+        clear_observer();
+        SEEN.with(|events| {
+            let kinds: Vec<_> = events.borrow().iter().map(|(kind, _)| *kind).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    EventKind::Created,
+                    EventKind::Propagated,
+                    EventKind::Handled,
+                    EventKind::Created,
+                    EventKind::Converted,
+                    EventKind::Created,
+                    EventKind::Handled,
+                ]
+            );
+            let events = events.borrow();
+            assert_eq!(events[0].1, created_at);
+            assert_eq!(events[1].1, propagated_at);
+            assert_eq!(events[2].1, handled_at);
+            assert_eq!(events[4].1, converted_at);
+            assert_eq!(events[6].1, explicit_at);
+        });
+    }
+}
@@ -0,0 +1,291 @@
+//! Helpers for running subprocesses with traced errors.
+//!
+//! [`Command::status()`] and [`Command::output()`] only fail if the process
+//! could not be run at all; a process that exits unsuccessfully has to be
+//! checked for by hand, and the error built for it usually loses track of
+//! which command failed and where. The extension trait in this module runs a
+//! command and turns both kinds of failure into a traced [`CommandError`]
+//! that records the command line and starts at the location of the caller.
+//!
+//! For commands that were run some other way, [`check_status()`] and
+//! [`check_output()`] do the same with an [`ExitStatus`] or an [`Output`]
+//! that has already been collected.
+
+use crate::result::Result::{self, Ok};
+
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt;
+use std::io;
+use std::process::{Command, ExitStatus, Output};
+
+/// The error returned when a command could not be run, or exited
+/// unsuccessfully.
+#[derive(Debug)]
+pub struct CommandError {
+    command: String,
+    failure: Failure,
+}
+
+#[derive(Debug)]
+enum Failure {
+    Spawn(io::Error),
+    Exit {
+        status: ExitStatus,
+        stderr: Option<String>,
+    },
+}
+
+impl CommandError {
+    fn spawn(command: &Command, error: io::Error) -> Self {
+        Self {
+            command: command_line(command),
+            failure: Failure::Spawn(error),
+        }
+    }
+
+    fn exit(command: &Command, status: ExitStatus, stderr: Option<&[u8]>) -> Self {
+        Self {
+            command: command_line(command),
+            failure: Failure::Exit {
+                status,
+                stderr: stderr.map(|bytes| String::from_utf8_lossy(bytes).trim_end().to_string()),
+            },
+        }
+    }
+
+    /// Returns the command line of the command, with its program and
+    /// arguments separated by spaces.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Returns the exit status of the command, or `None` if it could not be
+    /// run.
+    pub fn status(&self) -> Option<ExitStatus> {
+        match &self.failure {
+            Failure::Exit { status, .. } => Some(*status),
+            Failure::Spawn(_) => None,
+        }
+    }
+
+    /// Returns the exit code of the command, or `None` if it could not be run
+    /// or was terminated by a signal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use propagate::process::TracedCommand;
+    /// use std::process::Command;
+    ///
+    /// let err = Command::new("sh").args(["-c", "exit 3"]).status_traced().unwrap_err();
+    /// assert_eq!(err.exit_code(), Some(3));
+    /// assert_eq!(err.command(), "sh -c exit 3");
+    /// # }
+    /// ```
+    pub fn exit_code(&self) -> Option<i32> {
+        self.status().and_then(|status| status.code())
+    }
+
+    /// Returns what the command wrote to its standard error, if it was
+    /// captured.
+    pub fn stderr(&self) -> Option<&str> {
+        match &self.failure {
+            Failure::Exit { stderr, .. } => stderr.as_deref(),
+            Failure::Spawn(_) => None,
+        }
+    }
+
+    /// Returns the error that prevented the command from running, or `None`
+    /// if it ran and exited unsuccessfully.
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match &self.failure {
+            Failure::Spawn(error) => Some(error),
+            Failure::Exit { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            Failure::Spawn(_) => write!(f, "could not run `{}`", self.command),
+            Failure::Exit {
+                status,
+                stderr: Some(stderr),
+            } if !stderr.is_empty() => {
+                write!(f, "`{}` failed with {}: {}", self.command, status, stderr)
+            }
+            Failure::Exit { status, .. } => write!(f, "`{}` failed with {}", self.command, status),
+        }
+    }
+}
+
+impl Error for CommandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.failure {
+            Failure::Spawn(error) => Some(error),
+            Failure::Exit { .. } => None,
+        }
+    }
+}
+
+/// Traced execution for [`Command`].
+pub trait TracedCommand {
+    /// Executes the command and waits for it to finish, like
+    /// [`Command::status()`].
+    ///
+    /// If the command could not be run or exited unsuccessfully, returns a
+    /// [`CommandError`] whose trace starts at the location of the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use propagate::process::{CommandError, TracedCommand};
+    /// use std::process::Command;
+    ///
+    /// fn build() -> propagate::Result<(), CommandError> {
+    ///     Command::new("false").status_traced()?;
+    ///     propagate::Ok(())
+    /// }
+    ///
+    /// let err = build().unwrap_err();
+    /// assert_eq!(err.to_string(), "`false` failed with exit status: 1");
+    /// # }
+    /// ```
+    #[track_caller]
+    fn status_traced(&mut self) -> Result<ExitStatus, CommandError>;
+
+    /// Executes the command and collects its output, like
+    /// [`Command::output()`].
+    ///
+    /// If the command could not be run or exited unsuccessfully, returns a
+    /// [`CommandError`] whose trace starts at the location of the caller. The
+    /// error includes what the command wrote to its standard error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[cfg(unix)] {
+    /// use propagate::process::TracedCommand;
+    /// use std::process::Command;
+    ///
+    /// let output = Command::new("echo").arg("hello").output_traced().unwrap();
+    /// assert_eq!(output.stdout, b"hello\n");
+    ///
+    /// let err = Command::new("sh")
+    ///     .args(["-c", "echo oops >&2; exit 2"])
+    ///     .output_traced()
+    ///     .unwrap_err();
+    /// assert_eq!(err.stderr(), Some("oops"));
+    /// # }
+    /// ```
+    #[track_caller]
+    fn output_traced(&mut self) -> Result<Output, CommandError>;
+}
+
+impl TracedCommand for Command {
+    #[track_caller]
+    fn status_traced(&mut self) -> Result<ExitStatus, CommandError> {
+        match self.status() {
+            std::result::Result::Ok(status) => check_status(self, status),
+            std::result::Result::Err(err) => Result::new_err(CommandError::spawn(self, err)),
+        }
+    }
+
+    #[track_caller]
+    fn output_traced(&mut self) -> Result<Output, CommandError> {
+        match self.output() {
+            std::result::Result::Ok(output) => check_output(self, output),
+            std::result::Result::Err(err) => Result::new_err(CommandError::spawn(self, err)),
+        }
+    }
+}
+
+/// Returns `status` if it is successful, or otherwise a [`CommandError`] for
+/// `command` whose trace starts at the location of the caller.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use std::process::Command;
+///
+/// let mut command = Command::new("false");
+/// let status = command.status().unwrap();
+/// let err = propagate::process::check_status(&command, status).unwrap_err();
+/// assert_eq!(err.exit_code(), Some(1));
+/// # }
+/// ```
+#[track_caller]
+pub fn check_status(command: &Command, status: ExitStatus) -> Result<ExitStatus, CommandError> {
+    if status.success() {
+        Ok(status)
+    } else {
+        Result::new_err(CommandError::exit(command, status, None))
+    }
+}
+
+/// Returns `output` if its status is successful, or otherwise a
+/// [`CommandError`] for `command`, including its standard error, whose trace
+/// starts at the location of the caller.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use std::process::Command;
+///
+/// let mut command = Command::new("sh");
+/// command.args(["-c", "echo missing file >&2; exit 1"]);
+/// let output = command.output().unwrap();
+/// let err = propagate::process::check_output(&command, output).unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "`sh -c echo missing file >&2; exit 1` failed with exit status: 1: missing file"
+/// );
+/// # }
+/// ```
+#[track_caller]
+pub fn check_output(command: &Command, output: Output) -> Result<Output, CommandError> {
+    if output.status.success() {
+        Ok(output)
+    } else {
+        let error = CommandError::exit(command, output.status, Some(&output.stderr));
+        Result::new_err(error)
+    }
+}
+
+/// Returns the program and arguments of `command`, separated by spaces.
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(OsStr::to_string_lossy)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CodeLocation;
+
+    #[test]
+    fn spawn_failure_starts_trace_at_caller() {
+        let mut command = Command::new("propagate-test-no-such-program");
+        command.arg("--help");
+
+        let here = CodeLocation::here().down_by(1);
+        let (err, trace) = command.status_traced().err_trace().unwrap();
+        assert_eq!(trace.0, vec![here]);
+        assert_eq!(err.command(), "propagate-test-no-such-program --help");
+        assert!(err.status().is_none());
+        assert_eq!(
+            err.to_string(),
+            "could not run `propagate-test-no-such-program --help`"
+        );
+        assert!(err.source().is_some());
+    }
+}
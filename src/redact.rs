@@ -0,0 +1,245 @@
+//! Redacting traces before they leave the process.
+//!
+//! Traces name the source files of a program and carry free-form context
+//! messages, which can reveal internal layout or user data when traces are
+//! shipped to a third party. When a [`Redactor`] is installed with
+//! [`set_redactor()`], the exporters in this crate pass every file path and
+//! every note (context message or boundary label) through it:
+//!
+//! * [`TraceEncoder::encode()`][crate::encode::TraceEncoder::encode]
+//! * [`ErrorFlow::record()`][crate::export::ErrorFlow::record]
+//!
+//! Exporters outside of this crate can do the same with [`redacted_path()`]
+//! and [`redacted_note()`]. Traces that are displayed locally, e.g. in logs
+//! and panic messages, are not redacted.
+//!
+//! [`Redaction`] covers the common cases; other policies can be written by
+//! implementing [`Redactor`].
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::export::ErrorFlow;
+//! use propagate::redact::{self, PathRedaction, Redaction};
+//! use propagate::{CodeLocation, ErrorTrace};
+//!
+//! redact::set_redactor(
+//!     Redaction::new()
+//!         .paths(PathRedaction::FileName)
+//!         .scrub_notes(true),
+//! );
+//!
+//! let flow = ErrorFlow::new();
+//! flow.record(&ErrorTrace(vec![
+//!     CodeLocation::new("/srv/billing/src/card.rs", 42).with_message("card 4111-1111"),
+//! ]));
+//!
+//! let mut folded = Vec::new();
+//! flow.write_folded(&mut folded).unwrap();
+//! assert_eq!(folded, b"card.rs:42: [redacted] 1\n");
+//!
+//! redact::clear_redactor();
+//! ```
+
+use crate::trace::{fnv1a, CodeLocation, FrameKind, FNV_OFFSET_BASIS};
+
+use std::borrow::Cow;
+use std::sync::{Arc, RwLock};
+
+/// Rewrites the parts of traces that may be sensitive before they are
+/// exported.
+///
+/// Both methods return their input unchanged by default.
+pub trait Redactor: Send + Sync {
+    /// Returns the file path to export in place of `path`.
+    fn redact_path<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(path)
+    }
+
+    /// Returns the text to export in place of `note`, which is a context
+    /// message or a boundary label.
+    fn redact_note<'a>(&self, note: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(note)
+    }
+}
+
+/// What a [`Redaction`] does to file paths.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PathRedaction {
+    /// Paths are exported unchanged.
+    #[default]
+    Keep,
+    /// Only the last component of each path is exported.
+    FileName,
+    /// Each path is replaced by a hash of it, as 16 hexadecimal digits.
+    ///
+    /// The hash is stable across builds and platforms, so the same file has
+    /// the same hash in every trace, and can be looked up by whoever knows
+    /// the original paths.
+    Hash,
+}
+
+/// A [`Redactor`] that applies fixed rules.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::redact::{PathRedaction, Redaction, Redactor};
+///
+/// let redaction = Redaction::new().paths(PathRedaction::Hash);
+/// assert_eq!(redaction.redact_path("src/main.rs"), "path-df9f8d2d8eb12e9c");
+/// assert_eq!(redaction.redact_note("loading config"), "loading config");
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Redaction {
+    paths: PathRedaction,
+    scrub_notes: bool,
+}
+
+impl Redaction {
+    /// Constructs a redaction that leaves traces unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets what is done to file paths, consuming and returning `self`.
+    pub fn paths(self, paths: PathRedaction) -> Self {
+        Self { paths, ..self }
+    }
+
+    /// Sets whether notes are replaced with `[redacted]`, consuming and
+    /// returning `self`.
+    pub fn scrub_notes(self, scrub_notes: bool) -> Self {
+        Self {
+            scrub_notes,
+            ..self
+        }
+    }
+}
+
+impl Redactor for Redaction {
+    fn redact_path<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        match self.paths {
+            PathRedaction::Keep => Cow::Borrowed(path),
+            PathRedaction::FileName => {
+                Cow::Borrowed(path.rsplit(['/', '\\']).next().unwrap_or(path))
+            }
+            PathRedaction::Hash => Cow::Owned(format!(
+                "path-{:016x}",
+                fnv1a(FNV_OFFSET_BASIS, path.as_bytes())
+            )),
+        }
+    }
+
+    fn redact_note<'a>(&self, note: &'a str) -> Cow<'a, str> {
+        if self.scrub_notes {
+            Cow::Borrowed("[redacted]")
+        } else {
+            Cow::Borrowed(note)
+        }
+    }
+}
+
+static REDACTOR: RwLock<Option<Arc<dyn Redactor>>> = RwLock::new(None);
+
+/// Installs `redactor` to be applied to exported traces, replacing any
+/// previously installed redactor.
+pub fn set_redactor(redactor: impl Redactor + 'static) {
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(redactor));
+}
+
+/// Removes the installed redactor, if any, so that traces are exported
+/// unchanged.
+pub fn clear_redactor() {
+    *REDACTOR.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Returns the currently installed redactor, if any.
+pub fn redactor() -> Option<Arc<dyn Redactor>> {
+    REDACTOR.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Applies the installed redactor, if any, to a file path.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::redact::{self, PathRedaction, Redaction};
+///
+/// assert_eq!(redact::redacted_path("src/db/pool.rs"), "src/db/pool.rs");
+///
+/// redact::set_redactor(Redaction::new().paths(PathRedaction::FileName));
+/// assert_eq!(redact::redacted_path("src/db/pool.rs"), "pool.rs");
+/// redact::clear_redactor();
+/// ```
+pub fn redacted_path(path: &str) -> Cow<'_, str> {
+    match redactor() {
+        Some(redactor) => Cow::Owned(redactor.redact_path(path).into_owned()),
+        None => Cow::Borrowed(path),
+    }
+}
+
+/// Applies the installed redactor, if any, to a context message or boundary
+/// label.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::redact::{self, Redaction};
+///
+/// redact::set_redactor(Redaction::new().scrub_notes(true));
+/// assert_eq!(redact::redacted_note("user alice@example.com"), "[redacted]");
+/// redact::clear_redactor();
+/// ```
+pub fn redacted_note(note: &str) -> Cow<'_, str> {
+    match redactor() {
+        Some(redactor) => Cow::Owned(redactor.redact_note(note).into_owned()),
+        None => Cow::Borrowed(note),
+    }
+}
+
+/// Renders `frame` like its [`Display`](std::fmt::Display) implementation,
+/// with its path and note passed through `redactor`.
+pub(crate) fn display_frame(frame: &CodeLocation, redactor: Option<&dyn Redactor>) -> String {
+    let redactor = match redactor {
+        Some(redactor) => redactor,
+        None => return frame.to_string(),
+    };
+
+    let path = crate::fmt::remap_path(frame.file());
+    let mut rendered = format!("{}:{}", redactor.redact_path(&path), frame.line());
+    match frame.kind() {
+        FrameKind::Location => {}
+        FrameKind::Message(message) => {
+            rendered.push_str(": ");
+            rendered.push_str(&redactor.redact_note(message));
+        }
+        FrameKind::Boundary { label } => {
+            rendered.push_str(" (");
+            rendered.push_str(&redactor.redact_note(label));
+            rendered.push(')');
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn redaction_rewrites_paths_and_notes() {
+        let redaction = Redaction::new()
+            .paths(PathRedaction::FileName)
+            .scrub_notes(true);
+        let frame = CodeLocation::new("C:\\src\\secret\\vault.rs", 7).labeled("thread worker-3");
+        assert_eq!(
+            display_frame(&frame, Some(&redaction)),
+            "vault.rs:7 ([redacted])"
+        );
+
+        let hash = Redaction::new().paths(PathRedaction::Hash);
+        assert_eq!(hash.redact_path("a.rs"), hash.redact_path("a.rs"));
+        assert_ne!(hash.redact_path("a.rs"), hash.redact_path("b.rs"));
+    }
+}
@@ -0,0 +1,966 @@
+//! Reporting fatal errors to end users.
+//!
+//! By default, an error returned from `main` is printed to the terminal along
+//! with its trace (see the [`Termination`] implementation of [`Result`]).
+//! That is the right thing for developers, but end users of a command-line
+//! tool are better served by a short message. When a [`CrashReporter`] is
+//! installed with [`set_crash_reporter()`], the full report (the error, its
+//! chain of sources, its trace, and the version of the program and operating
+//! system) is written to a file in the temporary directory instead, and only a
+//! friendly message pointing to that file is printed.
+//!
+//! For tools whose users do want to see the trace, [`ReportOptions`] controls
+//! how much of it is printed, e.g. depending on `-q` and `-v` flags.
+//!
+//! The fixed English text of reports, such as the `Error:` and `Return Trace:`
+//! headings, comes from the installed [`ReportMessages`], which applications
+//! can replace with [`set_messages()`] to translate it.
+//!
+//! When an error also carries a [`Backtrace`] of where it was created,
+//! [`UnifiedTrace`] renders both in a single view, showing the function that
+//! each frame of the trace belongs to.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use propagate::report;
+//!
+//! fn main() -> propagate::Result<(), std::io::Error> {
+//!     report::set_crash_reporter(
+//!         report::crash_reporter!().homepage("https://example.com/issues"),
+//!     );
+//!
+//!     propagate::Ok(std::fs::remove_file("does-not-exist")?)
+//! }
+//! ```
+//!
+//! [`Backtrace`]: std::backtrace::Backtrace
+//! [`Termination`]: std::process::Termination
+//! [`Result`]: crate::Result
+
+use crate::trace::{CodeLocation, Traced};
+
+use std::backtrace::Backtrace;
+use std::borrow::Cow;
+use std::collections::hash_map::RandomState;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, RwLock};
+
+#[doc(inline)]
+pub use crate::crash_reporter;
+
+/// Writes crash reports for fatal errors to files.
+///
+/// See the [module documentation](self) for more information.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrashReporter {
+    name: &'static str,
+    version: &'static str,
+    homepage: Option<&'static str>,
+    directory: Option<PathBuf>,
+}
+
+impl CrashReporter {
+    /// Constructs a reporter for the program with the given name and version.
+    ///
+    /// The [`crash_reporter!`] macro fills these in from the Cargo manifest of
+    /// the calling crate.
+    pub const fn new(name: &'static str, version: &'static str) -> Self {
+        Self {
+            name,
+            version,
+            homepage: None,
+            directory: None,
+        }
+    }
+
+    /// Sets the address where users should send crash reports, consuming and
+    /// returning `self`.
+    pub fn homepage(self, homepage: &'static str) -> Self {
+        Self {
+            homepage: Some(homepage),
+            ..self
+        }
+    }
+
+    /// Sets the directory that reports are written to, consuming and
+    /// returning `self`.
+    ///
+    /// By default, reports are written to [`std::env::temp_dir()`].
+    pub fn directory(self, directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: Some(directory.into()),
+            ..self
+        }
+    }
+
+    /// Writes a report of `error` and its `trace` to a new file, returning
+    /// the path of the file.
+    ///
+    /// The name of the file ends with a random suffix, and the file is only
+    /// written if it did not exist yet, so that other users of a shared
+    /// directory cannot predict the name or plant a link in its place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::report::CrashReporter;
+    /// use std::io;
+    ///
+    /// let reporter = CrashReporter::new("mytool", "1.2.3");
+    /// let error = io::Error::new(io::ErrorKind::NotFound, "no such file");
+    /// let path = reporter.write_report(&error, &"\n   0: src/main.rs:10").unwrap();
+    ///
+    /// let report = std::fs::read_to_string(&path).unwrap();
+    /// assert!(report.contains("version = 1.2.3"));
+    /// assert!(report.contains("error = no such file"));
+    /// # std::fs::remove_file(path).unwrap();
+    /// ```
+    pub fn write_report(
+        &self,
+        error: &(dyn Error + 'static),
+        trace: &dyn fmt::Display,
+    ) -> io::Result<PathBuf> {
+        let directory = self.directory.clone().unwrap_or_else(std::env::temp_dir);
+        let (mut file, path) = create_report_file(&directory, self.name)?;
+        writeln!(file, "name = {}", self.name)?;
+        writeln!(file, "version = {}", self.version)?;
+        writeln!(
+            file,
+            "operating_system = {} ({})",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )?;
+        if let Some(category) = crate::error::error_category(error) {
+            writeln!(file, "category = {}", category)?;
+        }
+        if let Some(code) = crate::error::error_code(error) {
+            writeln!(file, "code = {}", code)?;
+        }
+        writeln!(file, "error = {}", error)?;
+        let mut source = error.source();
+        while let Some(cause) = source {
+            writeln!(file, "caused_by = {}", cause)?;
+            source = cause.source();
+        }
+        writeln!(file, "\nReturn Trace: {}", trace)?;
+
+        Ok(path)
+    }
+
+    /// Writes a report of `error` and its `trace`, and prints a message to
+    /// standard error telling the user where to find it.
+    ///
+    /// If the report cannot be written, the error and its trace are printed
+    /// instead.
+    pub fn report(&self, error: &(dyn Error + 'static), trace: &dyn fmt::Display) {
+        let messages = messages();
+        match self.write_report(error, trace) {
+            Ok(path) => {
+                eprintln!("{}", messages.crash_notice(self.name, &path));
+                if let Some(homepage) = self.homepage {
+                    eprintln!("\n{}", messages.crash_homepage(homepage));
+                }
+            }
+            Err(_) => {
+                eprintln!("{} {}", messages.error_heading(), ErrorReport::new(error));
+                eprintln!("\n{} {}", messages.trace_heading(), trace);
+            }
+        }
+    }
+}
+
+/// How many names are tried for a report before giving up.
+const REPORT_FILE_ATTEMPTS: usize = 16;
+
+/// Creates a new report file for the program `name` in `directory`, with a
+/// random name that did not exist yet.
+fn create_report_file(directory: &Path, name: &str) -> io::Result<(fs::File, PathBuf)> {
+    let mut error = None;
+    for _ in 0..REPORT_FILE_ATTEMPTS {
+        let suffix = RandomState::new().build_hasher().finish();
+        let path = directory.join(format!(
+            "{}-report-{}-{:016x}.txt",
+            name,
+            process::id(),
+            suffix
+        ));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(error.unwrap_or_else(|| io::ErrorKind::AlreadyExists.into()))
+}
+
+/// Constructs a [`CrashReporter`] for the calling crate, using the name and
+/// version from its Cargo manifest.
+///
+/// [`CrashReporter`]: crate::report::CrashReporter
+#[macro_export]
+macro_rules! crash_reporter {
+    () => {
+        $crate::report::CrashReporter::new(
+            ::std::env!("CARGO_PKG_NAME"),
+            ::std::env!("CARGO_PKG_VERSION"),
+        )
+    };
+}
+
+static CRASH_REPORTER: RwLock<Option<CrashReporter>> = RwLock::new(None);
+
+/// Installs `reporter` to report errors returned from `main`, replacing any
+/// previously installed reporter.
+pub fn set_crash_reporter(reporter: CrashReporter) {
+    *CRASH_REPORTER.write().unwrap_or_else(|e| e.into_inner()) = Some(reporter);
+}
+
+/// Removes the installed crash reporter, if any, so that errors returned
+/// from `main` are printed to the terminal again.
+pub fn clear_crash_reporter() {
+    *CRASH_REPORTER.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Returns the installed crash reporter, if any.
+pub(crate) fn installed_crash_reporter() -> Option<CrashReporter> {
+    CRASH_REPORTER
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Options controlling how an error returned from `main` is reported.
+///
+/// The options can be built from the verbosity level of a command-line tool
+/// with [`from_verbosity()`][ReportOptions::from_verbosity], and installed
+/// with [`set_report_options()`]. Once installed, they take precedence over
+/// the [`PROPAGATE_STYLE`][crate::fmt::STYLE_VAR] environment variable.
+///
+/// # Examples
+///
+/// ```no_run
+/// use propagate::report::{self, ReportOptions};
+///
+/// fn main() -> propagate::Result<(), std::io::Error> {
+///     // e.g., the number of `-v` flags minus the number of `-q` flags.
+///     let verbosity = std::env::args().filter(|arg| arg == "-v").count() as i8;
+///     report::set_report_options(ReportOptions::from_verbosity(verbosity));
+///
+///     propagate::Ok(std::fs::remove_file("does-not-exist")?)
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ReportOptions {
+    trace: bool,
+    max_frames: Option<usize>,
+    color: bool,
+    source_snippets: bool,
+}
+
+impl Default for ReportOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReportOptions {
+    /// The number of frames shown at the default verbosity level.
+    pub const DEFAULT_MAX_FRAMES: usize = 10;
+
+    /// Constructs options that show the whole trace, without color or source
+    /// snippets.
+    pub const fn new() -> Self {
+        Self {
+            trace: true,
+            max_frames: None,
+            color: false,
+            source_snippets: false,
+        }
+    }
+
+    /// Constructs options for a verbosity level, where 0 is the default, each
+    /// `-v` flag adds one, and each `-q` flag subtracts one.
+    ///
+    /// | Level | Options |
+    /// |-------|---------|
+    /// | < 0 (`-q`) | No trace |
+    /// | 0 | At most [`DEFAULT_MAX_FRAMES`][ReportOptions::DEFAULT_MAX_FRAMES] frames |
+    /// | 1 (`-v`) | The whole trace |
+    /// | > 1 (`-vv`) | The whole trace, with source snippets |
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::report::ReportOptions;
+    ///
+    /// assert!(!ReportOptions::from_verbosity(-1).shows_trace());
+    /// assert_eq!(ReportOptions::from_verbosity(0).max_frames(), Some(10));
+    /// assert_eq!(ReportOptions::from_verbosity(1).max_frames(), None);
+    /// assert!(ReportOptions::from_verbosity(2).shows_source_snippets());
+    /// ```
+    pub const fn from_verbosity(level: i8) -> Self {
+        let options = Self::new();
+        match level {
+            i8::MIN..=-1 => options.with_trace(false),
+            0 => options.with_max_frames(Some(Self::DEFAULT_MAX_FRAMES)),
+            1 => options,
+            _ => options.with_source_snippets(true),
+        }
+    }
+
+    /// Sets whether the trace is shown at all.
+    pub const fn with_trace(self, trace: bool) -> Self {
+        Self { trace, ..self }
+    }
+
+    /// Sets the maximum number of frames shown, or `None` to show every frame.
+    pub const fn with_max_frames(self, max_frames: Option<usize>) -> Self {
+        Self { max_frames, ..self }
+    }
+
+    /// Sets whether the report is colored using ANSI escape codes.
+    pub const fn with_color(self, color: bool) -> Self {
+        Self { color, ..self }
+    }
+
+    /// Sets whether each frame is followed by the line of source code it
+    /// points to, when the source file can be read.
+    pub const fn with_source_snippets(self, source_snippets: bool) -> Self {
+        Self {
+            source_snippets,
+            ..self
+        }
+    }
+
+    /// Returns `true` if the trace is shown.
+    pub const fn shows_trace(&self) -> bool {
+        self.trace
+    }
+
+    /// Returns the maximum number of frames shown, if limited.
+    pub const fn max_frames(&self) -> Option<usize> {
+        self.max_frames
+    }
+
+    /// Returns `true` if the report is colored.
+    pub const fn uses_color(&self) -> bool {
+        self.color
+    }
+
+    /// Returns `true` if frames are followed by source snippets.
+    pub const fn shows_source_snippets(&self) -> bool {
+        self.source_snippets
+    }
+
+    /// Renders `trace` according to these options, one frame per line.
+    ///
+    /// Each frame is rendered by the installed
+    /// [formatter][crate::fmt::TraceFormatter::fmt_frame]. Stacks that do not
+    /// keep their frames (see [`Traced::frames()`]) are rendered as usual
+    /// instead, without a limit on the number of frames or source snippets.
+    /// The age of errors whose stack records their creation time (see
+    /// [`TimedStack`][crate::TimedStack]) is shown after the frames.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::report::ReportOptions;
+    /// use propagate::{CodeLocation, ErrorTrace};
+    ///
+    /// let trace = ErrorTrace(vec![
+    ///     CodeLocation::new("src/a.rs", 1),
+    ///     CodeLocation::new("src/b.rs", 2),
+    ///     CodeLocation::new("src/c.rs", 3),
+    /// ]);
+    ///
+    /// let options = ReportOptions::new().with_max_frames(Some(2));
+    /// assert_eq!(
+    ///     options.render_trace(&trace),
+    ///     "\n   0: src/a.rs:1\n   1: src/b.rs:2\n   ... 1 more frame(s)",
+    /// );
+    /// ```
+    pub fn render_trace<S: Traced + fmt::Display + ?Sized>(&self, trace: &S) -> String {
+        if !self.trace {
+            return String::new();
+        }
+
+        let frames: Vec<_> = match trace.frames() {
+            Some(frames) => frames.collect(),
+            None => return trace.to_string(),
+        };
+        let shown = self.max_frames.unwrap_or(frames.len()).min(frames.len());
+
+        let formatter = crate::fmt::formatter();
+        let mut output = String::new();
+        for (index, frame) in frames[..shown].iter().enumerate() {
+            let rendered = RenderedFrame(&*formatter, index, frame).to_string();
+            let rendered = rendered.strip_prefix('\n').unwrap_or(&rendered);
+            if self.color {
+                output.push_str(&format!("\n\x1b[36m{}\x1b[0m", rendered));
+            } else {
+                output.push_str(&format!("\n{}", rendered));
+            }
+            if self.source_snippets {
+                if let Some(snippet) = source_snippet(frame) {
+                    output.push_str(&format!("\n         | {}", snippet.trim_end()));
+                }
+            }
+        }
+        if shown < frames.len() {
+            let more = messages().more_frames(frames.len() - shown);
+            output.push_str(&format!("\n   ... {}", more));
+        }
+        if let Some(created) = trace.created_at() {
+            output.push_str(&format!(
+                "\n   error created {:.1?} before being reported",
+                created.elapsed()
+            ));
+        }
+
+        output
+    }
+
+    /// Renders the `Error:` heading of a report.
+    pub(crate) fn heading(&self) -> String {
+        let heading = messages().error_heading().to_owned();
+        if self.color {
+            format!("\x1b[1;31m{}\x1b[0m", heading)
+        } else {
+            heading
+        }
+    }
+}
+
+/// Renders a single frame with [`TraceFormatter::fmt_frame()`].
+///
+/// [`TraceFormatter::fmt_frame()`]: crate::fmt::TraceFormatter::fmt_frame
+struct RenderedFrame<'a>(&'a dyn crate::fmt::TraceFormatter, usize, &'a CodeLocation);
+
+impl fmt::Display for RenderedFrame<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_frame(self.1, self.2, f)
+    }
+}
+
+/// Returns the line of source code that `frame` points to, if the file can be
+/// read.
+fn source_snippet(frame: &CodeLocation) -> Option<String> {
+    let index = frame.line().checked_sub(1)? as usize;
+    let source = fs::read_to_string(frame.file()).ok()?;
+    source.lines().nth(index).map(String::from)
+}
+
+static REPORT_OPTIONS: RwLock<Option<ReportOptions>> = RwLock::new(None);
+
+/// Installs `options` to control how errors returned from `main` are reported,
+/// replacing any previously installed options.
+pub fn set_report_options(options: ReportOptions) {
+    *REPORT_OPTIONS.write().unwrap_or_else(|e| e.into_inner()) = Some(options);
+}
+
+/// Returns the installed report options, if any.
+pub(crate) fn installed_report_options() -> Option<ReportOptions> {
+    *REPORT_OPTIONS.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Renders an error followed by its chain of sources, one per line.
+///
+/// This is how errors returned from `main` are printed, so that the causes of
+/// wrapped errors are not hidden.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::report::ErrorReport;
+/// use propagate::WrappedError;
+///
+/// let error = WrappedError::new("failed to load config", std::fmt::Error);
+/// assert_eq!(
+///     ErrorReport::new(&error).to_string(),
+///     "failed to load config\n\n\
+///      Caused by:\n   \
+///         0: an error occurred when formatting an argument",
+/// );
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ErrorReport<'a>(&'a (dyn Error + 'static));
+
+impl<'a> ErrorReport<'a> {
+    /// Constructs a report of `error`.
+    ///
+    /// The error and each of its sources are preceded by their code, if their
+    /// type was registered with [`register_error_code()`]. The error is also
+    /// preceded by its category, if its type was registered with
+    /// [`register_error_category()`].
+    ///
+    /// [`register_error_code()`]: crate::error::register_error_code
+    /// [`register_error_category()`]: crate::error::register_error_category
+    pub fn new(error: &'a (dyn Error + 'static)) -> Self {
+        Self(error)
+    }
+
+    /// Returns `true` if the trace should be reported along with the error,
+    /// which is the case unless its registered category does not
+    /// [show one][crate::error::ErrorCategory::shows_trace].
+    pub fn shows_trace(&self) -> bool {
+        crate::error::error_category(self.0).is_none_or(|category| category.shows_trace())
+    }
+}
+
+impl fmt::Display for ErrorReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(category) = crate::error::error_category(self.0) {
+            write!(f, "{}: ", category)?;
+        }
+        write_coded(f, self.0)?;
+
+        let mut source = self.0.source();
+        if source.is_some() {
+            write!(f, "\n\n{}", messages().caused_by_heading())?;
+        }
+        let mut index = 0;
+        while let Some(cause) = source {
+            write!(f, "\n   {}: ", index)?;
+            write_coded(f, cause)?;
+            source = cause.source();
+            index += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes `error`, preceded by its code if it has one.
+fn write_coded(f: &mut dyn fmt::Write, error: &(dyn Error + 'static)) -> fmt::Result {
+    match crate::error::error_code(error) {
+        Some(code) => write!(f, "{}: {}", code, error),
+        None => write!(f, "{}", error),
+    }
+}
+
+/// Provides the fixed text of reports, so that it can be translated.
+///
+/// Every method has a default implementation returning the English text used
+/// by [`EnglishMessages`], so implementors only need to override the text they
+/// translate. The error values and traces themselves are never changed.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::report::{self, ReportMessages};
+///
+/// struct French;
+///
+/// impl ReportMessages for French {
+///     fn error_heading(&self) -> &str {
+///         "Erreur :"
+///     }
+///
+///     fn trace_heading(&self) -> &str {
+///         "Trace de retour :"
+///     }
+///
+///     fn more_frames(&self, count: usize) -> String {
+///         format!("{} autre(s) cadre(s)", count)
+///     }
+/// }
+///
+/// report::set_messages(French);
+/// # report::reset_messages();
+/// ```
+pub trait ReportMessages: Send + Sync {
+    /// Returns the heading printed before the error, `"Error:"`.
+    fn error_heading(&self) -> &str {
+        "Error:"
+    }
+
+    /// Returns the heading printed before the trace, `"Return Trace:"`.
+    fn trace_heading(&self) -> &str {
+        "Return Trace:"
+    }
+
+    /// Returns the heading printed before the sources of the error,
+    /// `"Caused by:"`.
+    fn caused_by_heading(&self) -> &str {
+        "Caused by:"
+    }
+
+    /// Returns the text standing in for `count` frames that are not shown,
+    /// `"{count} more frame(s)"`.
+    fn more_frames(&self, count: usize) -> String {
+        format!("{} more frame(s)", count)
+    }
+
+    /// Returns the message printed when the program `name` has crashed and a
+    /// report has been written to `path` (see [`CrashReporter::report()`]).
+    fn crash_notice(&self, name: &str, path: &Path) -> String {
+        format!(
+            "Well, this is embarrassing.\n\n\
+             {} had a problem and crashed. A report has been written to \"{}\".",
+            name,
+            path.display()
+        )
+    }
+
+    /// Returns the message asking users to send a crash report to `homepage`.
+    fn crash_homepage(&self, homepage: &str) -> String {
+        format!(
+            "To help us fix the problem, please send the report to {}.",
+            homepage
+        )
+    }
+}
+
+/// The messages used when no other messages have been installed.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EnglishMessages;
+
+impl ReportMessages for EnglishMessages {}
+
+static MESSAGES: RwLock<Option<Arc<dyn ReportMessages>>> = RwLock::new(None);
+
+/// Installs `messages` as the text of reports, replacing any previously
+/// installed messages.
+pub fn set_messages(messages: impl ReportMessages + 'static) {
+    *MESSAGES.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(messages));
+}
+
+/// Restores [`EnglishMessages`] as the text of reports.
+pub fn reset_messages() {
+    *MESSAGES.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Returns the currently installed messages.
+pub fn messages() -> Arc<dyn ReportMessages> {
+    MESSAGES
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| Arc::new(EnglishMessages))
+}
+/// A propagation trace rendered together with a [`Backtrace`] of where the
+/// error was created.
+///
+/// The backtrace shows the call stack at the point the error was created, and
+/// the trace shows the path the error took back up that stack (and possibly
+/// beyond it, e.g. across threads). Instead of printing the two one after the
+/// other, this renders the frames of the trace, each followed by the function
+/// it belongs to when a frame of the backtrace at the same file and line can
+/// be found. When only the file matches, e.g. because a call spans several
+/// lines, the line of the backtrace frame is shown too.
+///
+/// Stacks that do not keep their frames (see [`Traced::frames()`]) render as
+/// empty.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::report::UnifiedTrace;
+/// use propagate::ErrorTrace;
+/// use std::backtrace::Backtrace;
+///
+/// let (trace, backtrace) = (ErrorTrace::new(), Backtrace::force_capture());
+/// let report = UnifiedTrace::new(&trace, &backtrace).to_string();
+/// assert!(report.starts_with(&format!("\n   0: {}:", file!())));
+/// ```
+pub struct UnifiedTrace<'a> {
+    frames: Vec<Cow<'a, CodeLocation>>,
+    backtrace: String,
+}
+
+impl<'a> UnifiedTrace<'a> {
+    /// Constructs a unified view of `trace` and `backtrace`.
+    pub fn new<S: Traced + ?Sized>(trace: &'a S, backtrace: &Backtrace) -> Self {
+        Self::from_rendered(trace, backtrace.to_string())
+    }
+
+    fn from_rendered<S: Traced + ?Sized>(trace: &'a S, backtrace: String) -> Self {
+        Self {
+            frames: trace.frames().into_iter().flatten().collect(),
+            backtrace,
+        }
+    }
+}
+
+impl fmt::Display for UnifiedTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbols = parse_backtrace(&self.backtrace);
+
+        // The trace and the backtrace run in the same direction, so matches
+        // are searched for after the previous one.
+        let mut next = 0;
+        for (index, frame) in self.frames.iter().enumerate() {
+            write!(f, "\n{:>4}: {}", index, frame)?;
+
+            let remaining = symbols.get(next..).unwrap_or_default();
+            let exact = remaining.iter().position(|symbol| {
+                symbol.matches_file(frame.file()) && symbol.line == frame.line()
+            });
+            let found = exact.or_else(|| {
+                remaining
+                    .iter()
+                    .position(|symbol| symbol.matches_file(frame.file()))
+            });
+            if let Some(offset) = found {
+                let symbol = &remaining[offset];
+                if exact.is_some() {
+                    write!(f, "\n             in {}", symbol.name)?;
+                } else {
+                    write!(
+                        f,
+                        "\n             in {} (line {})",
+                        symbol.name, symbol.line
+                    )?;
+                }
+                next += offset + 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for UnifiedTrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnifiedTrace")
+            .field("frames", &self.frames)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A function in a rendered [`Backtrace`], with the location it was at.
+struct BacktraceSymbol<'a> {
+    name: &'a str,
+    file: &'a str,
+    line: u32,
+}
+
+impl BacktraceSymbol<'_> {
+    /// Returns `true` if this symbol is in `file`, which may be relative to a
+    /// directory in the path of the symbol.
+    fn matches_file(&self, file: &str) -> bool {
+        let path = self.file.replace('\\', "/");
+        let file = file.replace('\\', "/");
+        path == file
+            || path
+                .strip_suffix(&file)
+                .is_some_and(|prefix| prefix.ends_with('/'))
+    }
+}
+
+/// Extracts the symbols that have a location from a rendered backtrace.
+///
+/// Each frame of a backtrace is rendered as `"  0: name"`, followed by
+/// `"        at file:line:column"` when the location is known. Inlined
+/// functions are rendered as separate frames.
+fn parse_backtrace(rendered: &str) -> Vec<BacktraceSymbol<'_>> {
+    let mut symbols = Vec::new();
+    let mut name = None;
+    for line in rendered.lines().map(str::trim) {
+        if let Some(location) = line.strip_prefix("at ") {
+            let mut parts = location.rsplitn(3, ':');
+            let (_column, line, file) = (parts.next(), parts.next(), parts.next());
+            if let (Some(name), Some(line), Some(file)) = (name.take(), line, file) {
+                if let Ok(line) = line.parse() {
+                    symbols.push(BacktraceSymbol { name, file, line });
+                }
+            }
+        } else if let Some((index, symbol)) = line.split_once(": ") {
+            if index.chars().all(|c| c.is_ascii_digit()) {
+                name = Some(symbol);
+            }
+        }
+    }
+    symbols
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::WrappedError;
+
+    #[test]
+    fn report_includes_source_chain_and_trace() {
+        let reporter = CrashReporter::new("propagate-test", "0.0.0");
+        let error = WrappedError::new("failed to load config", fmt::Error);
+        let path = reporter
+            .write_report(&error, &"\n   0: src/main.rs:10")
+            .unwrap();
+
+        let report = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(report.contains("error = failed to load config\n"));
+        assert!(report.contains("caused_by = an error occurred when formatting an argument\n"));
+        assert!(report.ends_with("Return Trace: \n   0: src/main.rs:10\n"));
+    }
+
+    #[test]
+    fn reports_are_written_to_new_files() {
+        let reporter = CrashReporter::new("propagate-test", "0.0.0");
+        let first = reporter.write_report(&fmt::Error, &"").unwrap();
+        let second = reporter.write_report(&fmt::Error, &"").unwrap();
+        let (first_exists, second_exists) = (first.exists(), second.exists());
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+
+        assert_ne!(first, second);
+        assert!(first_exists && second_exists);
+    }
+
+    #[test]
+    fn reports_show_registered_error_codes() {
+        use crate::error::{register_error_code, ErrorCode};
+        use std::borrow::Cow;
+
+        #[derive(Debug)]
+        struct Timeout;
+
+        impl fmt::Display for Timeout {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "timed out")
+            }
+        }
+
+        impl Error for Timeout {}
+
+        impl ErrorCode for Timeout {
+            fn code(&self) -> Cow<'_, str> {
+                "E0504".into()
+            }
+        }
+
+        register_error_code::<Timeout>();
+        let error = WrappedError::new("failed to sync", Timeout);
+        assert_eq!(
+            ErrorReport::new(&error).to_string(),
+            format!(
+                "failed to sync\n\n{}\n   0: E0504: timed out",
+                messages().caused_by_heading()
+            )
+        );
+
+        let reporter = CrashReporter::new("propagate-test", "0.0.0");
+        let path = reporter.write_report(&Timeout, &"").unwrap();
+        let report = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(report.contains("code = E0504\nerror = timed out\n"));
+    }
+
+    #[test]
+    fn reports_show_registered_error_categories() {
+        use crate::error::{register_error_category, Category, ErrorCategory};
+
+        #[derive(Debug)]
+        struct Busy;
+
+        impl fmt::Display for Busy {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "server busy")
+            }
+        }
+
+        impl Error for Busy {}
+
+        impl Category for Busy {
+            fn category(&self) -> ErrorCategory {
+                ErrorCategory::Transient
+            }
+        }
+
+        assert_eq!(ErrorReport::new(&Busy).to_string(), "server busy");
+
+        register_error_category::<Busy>();
+        let report = ErrorReport::new(&Busy);
+        assert_eq!(report.to_string(), "transient error: server busy");
+        assert!(report.shows_trace());
+
+        let error = WrappedError::new("failed to sync", Busy);
+        assert_eq!(
+            ErrorReport::new(&error).to_string(),
+            format!(
+                "failed to sync\n\n{}\n   0: server busy",
+                messages().caused_by_heading()
+            )
+        );
+
+        let reporter = CrashReporter::new("propagate-test", "0.0.0");
+        let path = reporter.write_report(&Busy, &"").unwrap();
+        let report = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(report.contains("category = transient error\nerror = server busy\n"));
+    }
+
+    #[test]
+    fn render_trace_shows_source_snippets() {
+        let line = line!();
+        let trace = crate::ErrorTrace(vec![crate::CodeLocation::new(file!(), line)]);
+        let options = ReportOptions::from_verbosity(2);
+        assert_eq!(
+            options.render_trace(&trace),
+            format!(
+                "\n   0: {}:{}\n         |         let line = line!();",
+                file!(),
+                line
+            )
+        );
+    }
+
+    #[test]
+    fn render_trace_counts_frames_not_lines() {
+        let mut trace = crate::TimedStack::<crate::ErrorTrace>::default();
+        trace.trace_context(std::panic::Location::caller(), "first\nsecond");
+        trace.trace(std::panic::Location::caller());
+        trace.trace(std::panic::Location::caller());
+        let line = line!() - 3;
+
+        let options = ReportOptions::new().with_max_frames(Some(2));
+        let rendered = options.render_trace(&trace);
+        let expected = format!(
+            "\n   0: {file}:{line}: first\nsecond\n   1: {file}:{next}\n   ... 1 more frame(s)\n   \
+             error created ",
+            file = file!(),
+            line = line,
+            next = line + 1,
+        );
+        assert!(rendered.starts_with(&expected), "{}", rendered);
+        assert!(rendered.ends_with(" before being reported"));
+    }
+
+    #[test]
+    fn unified_trace_matches_backtrace_frames() {
+        let trace = crate::ErrorTrace(vec![
+            CodeLocation::new("src/config.rs", 12),
+            CodeLocation::new("src/main.rs", 30),
+            CodeLocation::new("src/worker.rs", 7),
+        ]);
+        let backtrace = "\
+   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:310:13
+   1: app::config::load
+             at ./src/config.rs:12:9
+   2: app::main
+             at ./src/main.rs:28:5
+   3: core::ops::function::FnOnce::call_once
+"
+        .to_string();
+
+        assert_eq!(
+            UnifiedTrace::from_rendered(&trace, backtrace).to_string(),
+            "\n   0: src/config.rs:12\
+             \n             in app::config::load\
+             \n   1: src/main.rs:30\
+             \n             in app::main (line 28)\
+             \n   2: src/worker.rs:7"
+        );
+    }
+}
@@ -1,12 +1,17 @@
 //! Defines a new result type.
 
-use crate::trace::{ErrorTrace, Traced};
+use crate::error::{ErrorSet, TracedError, WrappedError};
+use crate::fmt::{compact_lines, Style};
+use crate::observe::{self, EventKind};
+use crate::trace::{self, Capture, ErrorTrace, Traced};
 
 use std::convert::Infallible;
 use std::fmt;
+use std::io;
 use std::ops::{ControlFlow, FromResidual, Try};
 use std::panic;
 use std::process::Termination;
+use std::task::Poll;
 
 pub use self::Result::Err;
 pub use self::Result::Ok;
@@ -182,6 +187,32 @@ pub enum Result<T, E, S = ErrorTrace> {
     Err(E, S),
 }
 
+/// A [`Result`] whose error trace is stored behind a [`Box`].
+///
+/// This keeps the size of the result close to that of a
+/// [`std::result::Result`] with the same types, which avoids moving a large
+/// value around on the success path, at the cost of an extra allocation when
+/// an error is created.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::result::BoxedResult;
+/// use std::mem::size_of;
+///
+/// fn parse(s: &str) -> BoxedResult<u64, &'static str> {
+///     match s.parse() {
+///         Ok(n) => propagate::Ok(n),
+///         Err(_) => propagate::Result::new_err("not a number"),
+///     }
+/// }
+///
+/// assert_eq!(parse("1").ok(), Some(1));
+/// assert_eq!(parse("x").err_trace().unwrap().1.depth(), 1);
+/// assert!(size_of::<BoxedResult<u64, &str>>() < size_of::<propagate::Result<u64, &str>>());
+/// ```
+pub type BoxedResult<T, E> = Result<T, E, Box<ErrorTrace>>;
+
 /*  _                 _   _____
  * (_)_ __ ___  _ __ | | |_   _| __ _   _
  * | | '_ ` _ \| '_ \| |   | || '__| | | |
@@ -233,7 +264,7 @@ where
         match residual {
             Ok(_) => unreachable!(),
             Err(err, mut trace) => {
-                trace.trace(panic::Location::caller());
+                push_propagated::<E, S>(&mut trace, panic::Location::caller());
                 Err(From::from(err), trace)
             }
         }
@@ -252,14 +283,84 @@ where
         match residual {
             std::result::Result::Ok(_) => unreachable!(),
             std::result::Result::Err(err) => {
-                let mut trace = S::default();
-                trace.trace(panic::Location::caller());
+                let trace = new_trace::<E, S>(panic::Location::caller());
                 Err(From::from(err), trace)
             }
         }
     }
 }
 
+/// Pushes an entry to the trace when a [`Result`] is propagated using `?` from
+/// a function returning `Poll<Result>`, such as a hand-written
+/// [`Future::poll()`](std::future::Future::poll).
+///
+/// This mirrors the standard library's impl for `Poll<std::result::Result>`.
+/// Because of the orphan rules, `?` cannot be applied to a
+/// `Poll<Result>` directly; use [`std::task::ready!`] to extract the result
+/// first:
+///
+/// ```
+/// use propagate::ErrorTrace;
+/// use std::future::Future;
+/// use std::pin::Pin;
+/// use std::task::{ready, Context, Poll};
+///
+/// struct Doubled<F>(F);
+///
+/// impl<F> Future for Doubled<F>
+/// where
+///     F: Future<Output = propagate::Result<u32, String>> + Unpin,
+/// {
+///     type Output = propagate::Result<u32, String>;
+///
+///     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+///         let n = ready!(Pin::new(&mut self.0).poll(cx))?;
+///         Poll::Ready(propagate::Ok(n * 2))
+///     }
+/// }
+/// ```
+impl<T, E, S, F> FromResidual<Result<Infallible, E, S>> for Poll<Result<T, F, S>>
+where
+    S: Traced,
+    F: From<E>,
+{
+    #[inline]
+    #[track_caller]
+    fn from_residual(residual: Result<Infallible, E, S>) -> Self {
+        Poll::Ready(Result::from_residual(residual))
+    }
+}
+
+/// Pushes an entry to the trace when a [`Result`] is propagated using `?` from
+/// a function returning `Poll<Option<Result>>`, such as a hand-written
+/// `Stream::poll_next()`.
+///
+/// This mirrors the standard library's impl for
+/// `Poll<Option<std::result::Result>>`.
+///
+/// ```
+/// use std::task::Poll;
+///
+/// fn poll_next(item: propagate::Result<u32, String>) -> Poll<Option<propagate::Result<u32, String>>> {
+///     let n = item?;
+///     Poll::Ready(Some(propagate::Ok(n * 2)))
+/// }
+///
+/// let next = poll_next(propagate::Result::new_err("bad item"));
+/// assert!(matches!(next, Poll::Ready(Some(propagate::Err(..)))));
+/// ```
+impl<T, E, S, F> FromResidual<Result<Infallible, E, S>> for Poll<Option<Result<T, F, S>>>
+where
+    S: Traced,
+    F: From<E>,
+{
+    #[inline]
+    #[track_caller]
+    fn from_residual(residual: Result<Infallible, E, S>) -> Self {
+        Poll::Ready(Some(Result::from_residual(residual)))
+    }
+}
+
 /*
   _                 _   _____                   _             _   _
  (_)_ __ ___  _ __ | | |_   _|__ _ __ _ __ ___ (_)_ __   __ _| |_(_) ___  _ __
@@ -270,14 +371,66 @@ where
  FIGLET: impl Termination
 */
 
-impl<T, E: std::error::Error, S: fmt::Display> Termination for Result<T, E, S> {
+/// Allows a [`Result`] to be returned from `main`.
+///
+/// On error, the error (including its chain of sources) and its trace are
+/// printed, and the process exits with a non-zero status. How much of the
+/// trace is printed is selected at runtime by the `PROPAGATE_STYLE`
+/// environment variable (see [`Style`][crate::fmt::Style]). The error type must
+/// implement [`std::error::Error`]; errors that only implement [`fmt::Debug`]
+/// can be wrapped in a [`DebugError`][crate::DebugError].
+///
+/// If report options are installed (see
+/// [`ReportOptions`][crate::report::ReportOptions]), they are used instead of
+/// the environment variable. If a crash reporter is installed (see
+/// [`report`][crate::report]), the report is written to a file instead, and
+/// only a short message is printed.
+///
+/// Errors whose type was registered with
+/// [`register_error_category()`][crate::error::register_error_category] are
+/// headed by their category. If the category does not
+/// [show a trace][crate::error::ErrorCategory::shows_trace], only the error is
+/// printed, and no crash report is written.
+impl<T, E, S> Termination for Result<T, E, S>
+where
+    E: std::error::Error + 'static,
+    S: Traced + fmt::Display,
+{
     fn report(self) -> i32 {
         match self {
             Ok(_) => 0,
             Err(err, trace) => {
-                println!("Error: {}", trial_and_error::Report::new(err).pretty(true));
-
-                println!("\nReturn Trace: {}", trace);
+                let report = crate::report::ErrorReport::new(&err);
+                if report.shows_trace() {
+                    if let Some(reporter) = crate::report::installed_crash_reporter() {
+                        reporter.report(&err, &trace);
+                        return 1;
+                    }
+                }
+
+                let messages = crate::report::messages();
+                if let Some(options) = crate::report::installed_report_options() {
+                    println!("{} {}", options.heading(), report);
+                    if options.shows_trace() && report.shows_trace() {
+                        let trace = options.render_trace(&trace);
+                        println!("\n{} {}", messages.trace_heading(), trace);
+                    }
+                    return 1;
+                }
+
+                println!("{} {}", messages.error_heading(), report);
+                if !report.shows_trace() {
+                    return 1;
+                }
+
+                match Style::from_env() {
+                    Style::Full => println!("\n{} {}", messages.trace_heading(), trace),
+                    Style::Compact => {
+                        let trace = compact_lines(&trace.to_string());
+                        println!("\n{} {}", messages.trace_heading(), trace)
+                    }
+                    Style::Off => {}
+                }
 
                 1
             }
@@ -285,6 +438,70 @@ impl<T, E: std::error::Error, S: fmt::Display> Termination for Result<T, E, S> {
     }
 }
 
+/*  _                 _   _____
+ * (_)_ __ ___  _ __ | | |  ___| __ ___  _ __ ___
+ * | | '_ ` _ \| '_ \| | | |_ | '__/ _ \| '_ ` _ \
+ * | | | | | | | |_) | | |  _|| | | (_) | | | | | |
+ * |_|_| |_| |_| .__/|_| |_|  |_|  \___/|_| |_| |_|
+ *             |_|
+ *  FIGLET: impl From
+ */
+
+/// Converts a [`ControlFlow`] into a [`Result`], treating `Break` as an error.
+///
+/// # Examples
+///
+/// ```
+/// # use propagate::result::Result;
+/// use propagate::TracedError;
+/// use std::ops::ControlFlow;
+///
+/// let flow: ControlFlow<TracedError<&str>, u32> = ControlFlow::Continue(2);
+/// assert_eq!(Result::from(flow), propagate::Ok(2));
+/// ```
+impl<T, E, S> From<ControlFlow<TracedError<E, S>, T>> for Result<T, E, S> {
+    #[inline]
+    fn from(flow: ControlFlow<TracedError<E, S>, T>) -> Self {
+        match flow {
+            ControlFlow::Continue(t) => Ok(t),
+            ControlFlow::Break(traced) => {
+                let (err, trace) = traced.into_parts();
+                Err(err, trace)
+            }
+        }
+    }
+}
+
+/// Converts a [`Result`] into a [`ControlFlow`], breaking with the error and
+/// its trace.
+///
+/// This allows visitor code built on [`ControlFlow`] to stop at the first
+/// traced error.
+///
+/// # Examples
+///
+/// ```
+/// # use propagate::result::Result;
+/// use propagate::TracedError;
+/// use std::ops::ControlFlow;
+///
+/// let x: Result<u32, &str> = Result::new_err("Nothing here");
+/// let flow: ControlFlow<TracedError<&str>, u32> = x.into();
+/// match flow {
+///     ControlFlow::Break(traced) => assert_eq!(*traced.error(), "Nothing here"),
+///     ControlFlow::Continue(_) => unreachable!(),
+/// }
+/// ```
+impl<T, E, S> From<Result<T, E, S>> for ControlFlow<TracedError<E, S>, T> {
+    #[inline]
+    fn from(result: Result<T, E, S>) -> Self {
+        match result {
+            Ok(t) => ControlFlow::Continue(t),
+            Err(err, trace) => ControlFlow::Break(TracedError::new(err, trace)),
+        }
+    }
+}
+
 /*  _                 _   ____                 _ _
  * (_)_ __ ___  _ __ | | |  _ \ ___  ___ _   _| | |_
  * | | '_ ` _ \| '_ \| | | |_) / _ \/ __| | | | | __|
@@ -312,10 +529,53 @@ impl<T, E, S: Traced + Default> Result<T, E, S> {
     where
         E: From<D>,
     {
-        let mut trace = S::default();
-        trace.trace(panic::Location::caller());
+        let trace = new_trace::<E, S>(panic::Location::caller());
         Err(E::from(error_value), trace)
     }
+
+    /// Combines `self` with another result, applying `f` to both contained
+    /// [`Ok`] values.
+    ///
+    /// If either result is an [`Err`], returns an [`ErrorSet`] holding every
+    /// error, each with its own trace, and starts a new trace at the caller.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// let y: Result<u32, &str> = propagate::Ok(3);
+    /// assert_eq!(x.zip_with(y, |a, b| a * b).ok(), Some(6));
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("bad width");
+    /// let y: Result<u32, &str> = Result::new_err("bad height");
+    /// let errors = x.zip_with(y, |a, b| a * b).unwrap_err();
+    /// assert_eq!(errors.errors().collect::<Vec<_>>(), vec![&"bad width", &"bad height"]);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn zip_with<U, R, F>(self, other: Result<U, E, S>, f: F) -> Result<R, ErrorSet<E, S>, S>
+    where
+        F: FnOnce(T, U) -> R,
+    {
+        let (t, u) = match (self, other) {
+            (Ok(t), Ok(u)) => return Ok(f(t, u)),
+            (t, u) => (t, u),
+        };
+
+        let mut errors = ErrorSet::new();
+        if let Err(err, trace) = t {
+            errors.push(err, trace);
+        }
+        if let Err(err, trace) = u {
+            errors.push(err, trace);
+        }
+
+        let trace = new_trace::<ErrorSet<E, S>, S>(panic::Location::caller());
+        Err(errors, trace)
+    }
 }
 
 impl<T, E, S> Result<T, E, S> {
@@ -337,10 +597,14 @@ impl<T, E, S> Result<T, E, S> {
     /// assert_eq!(x.to_std(), std::result::Result::Err("Nothing here"));
     /// ```
     #[inline]
+    #[track_caller]
     pub fn to_std(self) -> std::result::Result<T, E> {
         match self {
             Ok(t) => std::result::Result::Ok(t),
-            Err(err, _) => std::result::Result::Err(err),
+            Err(err, _) => {
+                observe::record::<E>(EventKind::Converted, panic::Location::caller());
+                std::result::Result::Err(err)
+            }
         }
     }
 
@@ -375,6 +639,130 @@ impl<T, E, S> Result<T, E, S> {
         }
     }
 
+    /// Converts from `Result<T, E, S>` to [`Option<TracedError<E, S>>`][Option].
+    ///
+    /// Converts `self` into an [`Option<TracedError<E, S>>`][Option],
+    /// consuming `self`, and discarding the success value, if any.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert!(x.traced_err().is_none());
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let traced = x.traced_err().unwrap();
+    /// assert_eq!(*traced.error(), "Nothing here");
+    /// assert_eq!(traced.stack().0.len(), 1);
+    /// ```
+    #[inline]
+    pub fn traced_err(self) -> Option<TracedError<E, S>> {
+        match self {
+            Ok(_) => None,
+            Err(err, trace) => Some(TracedError::new(err, trace)),
+        }
+    }
+
+    /// Splits `Result<T, E, S>` into a [`std::result::Result<T, E>`] and an
+    /// [`Option<S>`].
+    ///
+    /// Converts `self` into a [`std::result::Result<T, E>`], consuming `self`,
+    /// and returns the error trace separately, if any. This is useful when a
+    /// plain result must be handed to an external API, but the trace should be
+    /// kept for logging.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// let (result, trace) = x.split_trace();
+    /// assert_eq!(result, std::result::Result::Ok(2));
+    /// assert!(trace.is_none());
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let (result, trace) = x.split_trace();
+    /// assert_eq!(result, std::result::Result::Err("Nothing here"));
+    /// assert_eq!(trace.unwrap().0.len(), 1);
+    /// ```
+    #[inline]
+    pub fn split_trace(self) -> (std::result::Result<T, E>, Option<S>) {
+        match self {
+            Ok(t) => (std::result::Result::Ok(t), None),
+            Err(err, trace) => (std::result::Result::Err(err), Some(trace)),
+        }
+    }
+
+    /// Handles the error, if any, by passing it to `f` along with its trace.
+    ///
+    /// Converts `self` into an [`Option<T>`], consuming `self`. If the result
+    /// is [`Err`], `f` is called with the [`TracedError`] and `None` is
+    /// returned. This is the terminal operation for errors that are logged
+    /// and then swallowed.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let mut log = Vec::new();
+    ///
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.consume_err(|e| log.push(e.to_string())), Some(2));
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// assert_eq!(x.consume_err(|e| log.push(e.to_string())), None);
+    ///
+    /// assert_eq!(log, vec!["Nothing here"]);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn consume_err<F: FnOnce(TracedError<E, S>)>(self, f: F) -> Option<T> {
+        match self {
+            Ok(t) => Some(t),
+            Err(err, trace) => {
+                observe::record::<E>(EventKind::Handled, panic::Location::caller());
+                f(TracedError::new(err, trace));
+                None
+            }
+        }
+    }
+
+    /// Marks the caller as the place where a contained [`Err`] value is
+    /// handled.
+    ///
+    /// If an observer is installed (see [`observe`]), it is notified that the
+    /// error was handled at the location of the caller. Does nothing for an [`Ok`]
+    /// value, or if no observer is installed.
+    ///
+    /// [`observe`]: crate::observe
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// if let propagate::Err(err, trace) = &x {
+    ///     eprintln!("ignoring {}: {}", err, trace);
+    ///     x.handled_here();
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn handled_here(&self) {
+        if self.is_err() {
+            observe::record::<E>(EventKind::Handled, panic::Location::caller());
+        }
+    }
+
     /////////////////////////////////////////////////////////////////////////
     // Querying the contained values
     /////////////////////////////////////////////////////////////////////////
@@ -419,6 +807,38 @@ impl<T, E, S> Result<T, E, S> {
         !self.is_ok()
     }
 
+    /// Returns `true` if the result is `Err` and the error matches a
+    /// predicate.
+    ///
+    /// The trace is ignored. This is convenient for branching on the kind of
+    /// error inside an `if` condition.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::io::{Error, ErrorKind};
+    ///
+    /// let x: Result<u32, Error> = Result::new_err(Error::new(ErrorKind::NotFound, "!"));
+    /// assert_eq!(x.matches_err(|e| e.kind() == ErrorKind::NotFound), true);
+    ///
+    /// let x: Result<u32, Error> = Result::new_err(Error::new(ErrorKind::PermissionDenied, "!"));
+    /// assert_eq!(x.matches_err(|e| e.kind() == ErrorKind::NotFound), false);
+    ///
+    /// let x: Result<u32, Error> = propagate::Ok(123);
+    /// assert_eq!(x.matches_err(|e| e.kind() == ErrorKind::NotFound), false);
+    /// ```
+    #[must_use]
+    #[inline]
+    pub fn matches_err<P: FnOnce(&E) -> bool>(&self, predicate: P) -> bool {
+        match self {
+            Ok(_) => false,
+            Err(err, _) => predicate(err),
+        }
+    }
+
     /////////////////////////////////////////////////////////////////////////
     // Adapter for each variant
     /////////////////////////////////////////////////////////////////////////
@@ -441,10 +861,14 @@ impl<T, E, S> Result<T, E, S> {
     /// assert_eq!(x.ok(), None);
     /// ```
     #[inline]
+    #[track_caller]
     pub fn ok(self) -> Option<T> {
         match self {
             Ok(x) => Some(x),
-            Err(_, _) => None,
+            Err(_, _) => {
+                observe::record::<E>(EventKind::Handled, panic::Location::caller());
+                None
+            }
         }
     }
 
@@ -473,6 +897,52 @@ impl<T, E, S> Result<T, E, S> {
         }
     }
 
+    /// Returns a reference to the contained [`Ok`] value, if any, without
+    /// consuming `self`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.ok_ref(), Some(&2));
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// assert_eq!(x.ok_ref(), None);
+    /// ```
+    #[inline]
+    pub const fn ok_ref(&self) -> Option<&T> {
+        match *self {
+            Ok(ref t) => Some(t),
+            Err(_, _) => None,
+        }
+    }
+
+    /// Returns a reference to the contained [`Err`] value, if any, without
+    /// consuming `self`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.err_ref(), None);
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// assert_eq!(x.err_ref(), Some(&"Nothing here"));
+    /// ```
+    #[inline]
+    pub const fn err_ref(&self) -> Option<&E> {
+        match *self {
+            Ok(_) => None,
+            Err(ref err, _) => Some(err),
+        }
+    }
+
     /////////////////////////////////////////////////////////////////////////
     // Adapter for working with references
     /////////////////////////////////////////////////////////////////////////
@@ -533,20 +1003,526 @@ impl<T, E, S> Result<T, E, S> {
         }
     }
 
-    /////////////////////////////////////////////////////////////////////////
-    // Transforming contained values
-    /////////////////////////////////////////////////////////////////////////
-
-    // TODO: map
-    // TODO: map_or
-    // TODO: map_or_else
-
-    /// Maps a `Result<T, E>` to `Result<T, F>` by applying a function to a
-    /// contained [`Err`] value, leaving an [`Ok`] value untouched.
+    /// Converts from `&Result<T, E, S>` to
+    /// `std::result::Result<&T, TracedError<&E, &S>>`.
+    ///
+    /// This allows the error and its trace to be inspected together without
+    /// consuming the result or cloning anything.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.as_traced_ref().unwrap(), &2);
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let traced = x.as_traced_ref().unwrap_err();
+    /// assert_eq!(**traced.error(), "Nothing here");
+    /// assert_eq!(traced.stack().depth(), 1);
+    /// ```
+    #[inline]
+    pub const fn as_traced_ref(&self) -> std::result::Result<&T, TracedError<&E, &S>> {
+        match *self {
+            Ok(ref t) => std::result::Result::Ok(t),
+            Err(ref err, ref trace) => std::result::Result::Err(TracedError::new(err, trace)),
+        }
+    }
+
+    /// Returns references to the contained [`Err`] value and its trace, if
+    /// any, without consuming `self`.
+    ///
+    /// This is the borrowing counterpart of [`err_trace()`], which lets
+    /// logging code read both the error and its trace without cloning them.
+    ///
+    /// [`err_trace()`]: Result::err_trace
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert!(x.as_err_trace_ref().is_none());
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let (err, trace) = x.as_err_trace_ref().unwrap();
+    /// assert_eq!(*err, "Nothing here");
+    /// assert_eq!(trace.depth(), 1);
+    /// assert!(x.is_err());
+    /// ```
+    #[inline]
+    pub const fn as_err_trace_ref(&self) -> Option<(&E, &S)> {
+        match *self {
+            Ok(_) => None,
+            Err(ref err, ref trace) => Some((err, trace)),
+        }
+    }
+
+    /// Converts from `&mut Result<T, E, S>` to
+    /// `std::result::Result<&mut T, TracedError<&mut E, &mut S>>`.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let mut x: Result<u32, i32> = Result::new_err(13);
+    /// if let Err(mut traced) = x.as_traced_mut() {
+    ///     **traced.error_mut() += 1;
+    ///     traced.stack_mut().0.clear();
+    /// }
+    /// assert_eq!(x.err_trace().unwrap().0, 14);
+    /// ```
+    #[inline]
+    pub fn as_traced_mut(&mut self) -> std::result::Result<&mut T, TracedError<&mut E, &mut S>> {
+        match *self {
+            Ok(ref mut t) => std::result::Result::Ok(t),
+            Err(ref mut err, ref mut trace) => {
+                std::result::Result::Err(TracedError::new(err, trace))
+            }
+        }
+    }
+
+    /// Converts from `&Result<T, E, S>` to `ControlFlow<(&E, &S), &T>`.
+    ///
+    /// This is the by-reference counterpart of [`Try::branch()`], useful when
+    /// a [`ControlFlow`]-based visitor should stop at an error without taking
+    /// ownership of the result.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::ops::ControlFlow;
+    ///
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.branch_ref(), ControlFlow::Continue(&2));
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// assert!(matches!(x.branch_ref(), ControlFlow::Break((&"Nothing here", _))));
+    /// ```
+    #[inline]
+    pub const fn branch_ref(&self) -> ControlFlow<(&E, &S), &T> {
+        match *self {
+            Ok(ref t) => ControlFlow::Continue(t),
+            Err(ref err, ref trace) => ControlFlow::Break((err, trace)),
+        }
+    }
+
+    /////////////////////////////////////////////////////////////////////////
+    // Transforming contained values
+    /////////////////////////////////////////////////////////////////////////
+
+    // TODO: map
+    // TODO: map_or
+    // TODO: map_or_else
+
+    /// Maps a `Result<T, E>` to `Result<T, F>` by applying a function to a
+    /// contained [`Err`] value, leaving an [`Ok`] value untouched.
+    ///
+    /// This function can be used to pass through a successful result while handling
+    /// an error.
+    ///
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// fn stringify(x: i32) -> String { format!("error code: {}", x) }
+    ///
+    /// let x: Result<i32, i32> = propagate::Ok(2);
+    /// assert_eq!(x.map_err(stringify), propagate::Ok(2));
+    ///
+    /// let x: Result<i32, i32> = Result::new_err(13);
+    /// let y: Result<i32, String> = x.map_err(stringify);
+    /// assert_eq!(y.err().unwrap(), "error code: 13".to_string());
+    /// ```
+    #[inline]
+    pub fn map_err<F, O: FnOnce(E) -> F>(self, op: O) -> Result<T, F, S> {
+        // XXX: should this push_caller? I think probably not, as users will just use
+        // `?` with whatever comes out of this.
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, trace) => Err(op(err), trace),
+        }
+    }
+
+    /// Maps a `Result<T, E>` to `Result<T, F>` by applying a function to a
+    /// contained [`Err`] value and a reference to its trace, leaving an [`Ok`]
+    /// value untouched.
+    ///
+    /// This is like [`map_err`], but lets the new error value incorporate
+    /// information from the trace (e.g., where the error originated). The
+    /// trace itself is carried over unchanged.
+    ///
+    /// [`map_err`]: Result::map_err
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<i32, i32> = Result::new_err(13);
+    /// let y: Result<i32, String> = x.map_err_with_stack(|code, trace| {
+    ///     format!("error code {} ({} frames)", code, trace.depth())
+    /// });
+    /// assert_eq!(y.err().unwrap(), "error code 13 (1 frames)");
+    /// ```
+    #[inline]
+    pub fn map_err_with_stack<F, O: FnOnce(E, &S) -> F>(self, op: O) -> Result<T, F, S> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, trace) => {
+                let err = op(err, &trace);
+                Err(err, trace)
+            }
+        }
+    }
+
+    /// Converts the error type of a contained [`Err`] value using [`From`],
+    /// keeping its trace, and leaving an [`Ok`] value untouched.
+    ///
+    /// This is the conversion that the `?` operator performs, as an explicit
+    /// method that can be used in the middle of a chain of combinators, or
+    /// to return a result whose type only differs in the error parameter.
+    /// Unlike `?`, it does not add the caller to the trace.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// #[derive(Debug, PartialEq)]
+    /// struct MyError(i32);
+    ///
+    /// impl From<i32> for MyError {
+    ///     fn from(code: i32) -> Self {
+    ///         MyError(code)
+    ///     }
+    /// }
+    ///
+    /// let x: Result<u32, i32> = Result::new_err(13);
+    /// let (err, trace) = x.err_into::<MyError>().err_trace().unwrap();
+    /// assert_eq!(err, MyError(13));
+    /// assert_eq!(trace.depth(), 1);
+    /// ```
+    #[inline]
+    pub fn err_into<F: From<E>>(self) -> Result<T, F, S> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, trace) => Err(F::from(err), trace),
+        }
+    }
+
+    /// Applies `f` to the error trace of an [`Err`] value, leaving an [`Ok`]
+    /// value untouched.
+    ///
+    /// This allows middleware to append synthetic frames to, annotate, or trim
+    /// the trace without destructuring and rebuilding the result.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use propagate::CodeLocation;
+    ///
+    /// let x: Result<i32, &str> = Result::new_err("Nothing here");
+    /// let x = x.modify_stack(|trace| trace.0.clear());
+    /// assert_eq!(x.err_trace().unwrap().1.0.len(), 0);
+    ///
+    /// let x: Result<i32, &str> = Result::new_err("Nothing here");
+    /// let x = x.modify_stack(|trace| trace.0.push(CodeLocation::new("synthetic.rs", 1)));
+    /// assert_eq!(x.err_trace().unwrap().1.0.len(), 2);
+    /// ```
+    #[inline]
+    pub fn modify_stack<F: FnOnce(&mut S)>(self, f: F) -> Self {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, mut trace) => {
+                f(&mut trace);
+                Err(err, trace)
+            }
+        }
+    }
+
+    /// Wraps a contained [`Err`] value in a new outer error or message,
+    /// leaving an [`Ok`] value untouched.
+    ///
+    /// The resulting [`WrappedError`] displays as `wrapper`, and reports the
+    /// original error as its [`source()`]. The error trace accumulated so far
+    /// is kept as is.
+    ///
+    /// [`source()`]: std::error::Error::source
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::error::Error;
+    /// use std::io;
+    ///
+    /// let x: Result<u32, io::Error> = Result::new_err(io::Error::from(io::ErrorKind::NotFound));
+    /// let y = x.wrap_err("failed to read config");
+    ///
+    /// let err = y.unwrap_err();
+    /// assert_eq!(err.to_string(), "failed to read config");
+    /// assert_eq!(err.source().unwrap().to_string(), "entity not found");
+    /// ```
+    #[inline]
+    pub fn wrap_err<D>(self, wrapper: D) -> Result<T, WrappedError<D, E>, S> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, trace) => Err(WrappedError::new(wrapper, err), trace),
+        }
+    }
+
+    /// Wraps a contained [`Err`] value in a new outer error or message
+    /// produced by a closure, leaving an [`Ok`] value untouched.
+    ///
+    /// The closure is only called if `self` is an [`Err`]. See
+    /// [`wrap_err`][Result::wrap_err] for more information.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let path = "config.toml";
+    /// let x: Result<u32, &str> = Result::new_err("not found");
+    /// let y = x.wrap_err_with(|| format!("failed to read {}", path));
+    ///
+    /// let err = y.unwrap_err();
+    /// assert_eq!(err.to_string(), "failed to read config.toml");
+    /// assert_eq!(*err.inner(), "not found");
+    /// ```
+    #[inline]
+    pub fn wrap_err_with<D, F: FnOnce() -> D>(self, f: F) -> Result<T, WrappedError<D, E>, S> {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, trace) => Err(WrappedError::new(f(), err), trace),
+        }
+    }
+
+    /// Records `context` in the error trace of an [`Err`] value, leaving an
+    /// [`Ok`] value untouched.
+    ///
+    /// A new entry is pushed to the trace at the caller's location, annotated
+    /// with `context` (see [`Traced::trace_context()`]). Unlike [`wrap_err`],
+    /// the error value and its type are left unchanged.
+    ///
+    /// Arguments passed to `context` are eagerly evaluated; if you are passing
+    /// the result of a function call, it is recommended to use
+    /// [`with_context`], which is lazily evaluated.
+    ///
+    /// [`wrap_err`]: Result::wrap_err
+    /// [`with_context`]: Result::with_context
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let (err, trace) = x.context("loading config").err_trace().unwrap();
+    /// assert_eq!(err, "Nothing here");
+    /// assert_eq!(trace.latest().unwrap().message(), Some("loading config"));
+    /// ```
+    ///
+    /// Like the locations pushed by `?`, the frame is only recorded while
+    /// capture is enabled (see [`capture_from_env()`]); otherwise, `context`
+    /// is not even rendered:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// std::env::remove_var(propagate::trace::CAPTURE_VAR);
+    /// assert!(!propagate::trace::capture_from_env());
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let (_, trace) = x.context("loading config").err_trace().unwrap();
+    /// assert!(trace.0.is_empty());
+    /// ```
+    ///
+    /// [`capture_from_env()`]: crate::trace::capture_from_env
+    #[inline]
+    #[track_caller]
+    pub fn context<C: fmt::Display>(self, context: C) -> Self
+    where
+        S: Traced,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, mut trace) => {
+                push_context::<E, S>(&mut trace, panic::Location::caller(), &context);
+                Err(err, trace)
+            }
+        }
+    }
+
+    /// Records context computed by `f` in the error trace of an [`Err`]
+    /// value, leaving an [`Ok`] value untouched.
+    ///
+    /// This is the lazy version of [`context`]: `f` is only called if `self`
+    /// is an [`Err`], so it costs nothing on the success path.
+    ///
+    /// [`context`]: Result::context
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// fn describe(path: &str) -> String { format!("reading {}", path) }
+    ///
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.with_context(|| -> String { unreachable!() }), propagate::Ok(2));
+    ///
+    /// let x: Result<u32, &str> = Result::new_err("Nothing here");
+    /// let (_, trace) = x.with_context(|| describe("a.toml")).err_trace().unwrap();
+    /// assert_eq!(trace.latest().unwrap().message(), Some("reading a.toml"));
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn with_context<C: fmt::Display, F: FnOnce() -> C>(self, f: F) -> Self
+    where
+        S: Traced,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, mut trace) => {
+                push_context::<E, S>(&mut trace, panic::Location::caller(), &f());
+                Err(err, trace)
+            }
+        }
+    }
+
+    /////////////////////////////////////////////////////////////////////////
+    // Boolean operations on the values, eager and lazy
+    /////////////////////////////////////////////////////////////////////////
+
+    /// Calls `op` with the error and trace of an [`Err`] value to recover from
+    /// it, leaving an [`Ok`] value untouched.
+    ///
+    /// The handler either produces a replacement value, or returns a new
+    /// error. By returning its new error together with the original trace
+    /// (e.g., `propagate::Err(new_error, trace)`), the handler splices the
+    /// original trace into that of the new error. In either case, the
+    /// location of the caller is pushed to the trace of a returned error, so
+    /// reports show where recovery was attempted.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// fn parse(input: &str) -> Result<u32, String> {
+    ///     match input.parse() {
+    ///         Ok(n) => propagate::Ok(n),
+    ///         Err(_) => Result::new_err(format!("not a number: {}", input)),
+    ///     }
+    /// }
+    ///
+    /// let x: Result<u32, String> = parse("").recover(|_, _| propagate::Ok(0));
+    /// assert_eq!(x.ok(), Some(0));
+    ///
+    /// let x = parse("two").recover(|err, trace| propagate::Err(err.len(), trace));
+    /// let (err, trace) = x.err_trace().unwrap();
+    /// assert_eq!(err, 17);
+    /// assert_eq!(trace.depth(), 2);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn recover<F, O>(self, op: O) -> Result<T, F, S>
+    where
+        S: Traced,
+        O: FnOnce(E, S) -> Result<T, F, S>,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, trace) => match op(err, trace) {
+                Ok(t) => Ok(t),
+                Err(err, mut trace) => {
+                    push_propagated::<F, S>(&mut trace, panic::Location::caller());
+                    Err(err, trace)
+                }
+            },
+        }
+    }
+
+    /// Calls `op` with the error of an [`Err`] value to attempt a fallback,
+    /// leaving an [`Ok`] value untouched.
+    ///
+    /// This is like `or_else`, except that if the fallback also fails, the
+    /// location of the caller is pushed to the fallback's trace, so reports
+    /// show where recovery was attempted. The original error and its trace
+    /// are discarded; use [`or_else_with_cause`] to keep the original error.
+    ///
+    /// [`or_else_with_cause`]: Result::or_else_with_cause
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// fn from_cache(key: &str) -> Result<String, String> {
+    ///     Result::new_err(format!("{} not cached", key))
+    /// }
+    ///
+    /// fn from_disk(key: &str) -> Result<String, String> {
+    ///     Result::new_err(format!("{} not on disk", key))
+    /// }
+    ///
+    /// let x = from_cache("a").or_else_traced(|_| from_disk("a"));
+    /// let (err, trace) = x.err_trace().unwrap();
+    /// assert_eq!(err, "a not on disk");
+    /// assert_eq!(trace.depth(), 2);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn or_else_traced<F, O>(self, op: O) -> Result<T, F, S>
+    where
+        S: Traced,
+        O: FnOnce(E) -> Result<T, F, S>,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, _) => match op(err) {
+                Ok(t) => Ok(t),
+                Err(err, mut trace) => {
+                    push_propagated::<F, S>(&mut trace, panic::Location::caller());
+                    Err(err, trace)
+                }
+            },
+        }
+    }
+
+    /// Calls `op` with a reference to the error of an [`Err`] value to
+    /// attempt a fallback, leaving an [`Ok`] value untouched.
     ///
-    /// This function can be used to pass through a successful result while handling
-    /// an error.
+    /// If the fallback also fails, its error is wrapped in a
+    /// [`WrappedError`] that keeps the original error as its [`source()`],
+    /// and the location of the caller is pushed to the fallback's trace.
     ///
+    /// [`source()`]: std::error::Error::source
     ///
     /// # Examples
     ///
@@ -554,29 +1530,33 @@ impl<T, E, S> Result<T, E, S> {
     ///
     /// ```
     /// # use propagate::result::Result;
-    /// fn stringify(x: i32) -> String { format!("error code: {}", x) }
+    /// let x: Result<u32, &str> = Result::new_err("primary down");
+    /// let y = x.or_else_with_cause(|_| Result::<u32, &str>::new_err("replica down"));
     ///
-    /// let x: Result<i32, i32> = propagate::Ok(2);
-    /// assert_eq!(x.map_err(stringify), propagate::Ok(2));
-    ///
-    /// let x: Result<i32, i32> = Result::new_err(13);
-    /// let y: Result<i32, String> = x.map_err(stringify);
-    /// assert_eq!(y.err().unwrap(), "error code: 13".to_string());
+    /// let (err, trace) = y.err_trace().unwrap();
+    /// assert_eq!(*err.wrapper(), "replica down");
+    /// assert_eq!(*err.inner(), "primary down");
+    /// assert_eq!(trace.depth(), 2);
     /// ```
     #[inline]
-    pub fn map_err<F, O: FnOnce(E) -> F>(self, op: O) -> Result<T, F, S> {
-        // XXX: should this push_caller? I think probably not, as users will just use
-        // `?` with whatever comes out of this.
+    #[track_caller]
+    pub fn or_else_with_cause<F, O>(self, op: O) -> Result<T, WrappedError<F, E>, S>
+    where
+        S: Traced,
+        O: FnOnce(&E) -> Result<T, F, S>,
+    {
         match self {
             Ok(t) => Ok(t),
-            Err(err, trace) => Err(op(err), trace),
+            Err(cause, _) => match op(&cause) {
+                Ok(t) => Ok(t),
+                Err(err, mut trace) => {
+                    push_propagated::<WrappedError<F, E>, S>(&mut trace, panic::Location::caller());
+                    Err(WrappedError::new(err, cause), trace)
+                }
+            },
         }
     }
 
-    /////////////////////////////////////////////////////////////////////////
-    // Boolean operations on the values, eager and lazy
-    /////////////////////////////////////////////////////////////////////////
-
     /// Returns the contained [`Ok`] value or a provided default.
     ///
     /// Arguments passed to `unwrap_or` are eagerly evaluated; if you are passing
@@ -599,10 +1579,14 @@ impl<T, E, S> Result<T, E, S> {
     /// assert_eq!(x.unwrap_or(default), default);
     /// ```
     #[inline]
+    #[track_caller]
     pub fn unwrap_or(self, default: T) -> T {
         match self {
             Ok(t) => t,
-            Err(_, _) => default,
+            Err(_, _) => {
+                observe::record::<E>(EventKind::Handled, panic::Location::caller());
+                default
+            }
         }
     }
 
@@ -623,14 +1607,114 @@ impl<T, E, S> Result<T, E, S> {
     /// assert_eq!(x.unwrap_or_else(count), 3);
     /// ```
     #[inline]
+    #[track_caller]
     pub fn unwrap_or_else<F: FnOnce(E) -> T>(self, op: F) -> T {
         match self {
             Ok(t) => t,
-            Err(err, _) => op(err),
+            Err(err, _) => {
+                observe::record::<E>(EventKind::Handled, panic::Location::caller());
+                op(err)
+            }
+        }
+    }
+}
+
+impl<T, E> Result<T, E> {
+    /// Returns the number of locations in the trace of an [`Err`] value, or
+    /// `None` if the result is [`Ok`].
+    ///
+    /// This allows call sites to make decisions based on how far an error has
+    /// traveled (e.g., only adding context to deep traces, or alarming on an
+    /// unexpectedly long propagation chain) without destructuring the result.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// fn inner() -> Result<u32, &'static str> {
+    ///     Result::new_err("Nothing here")
+    /// }
+    ///
+    /// fn outer() -> Result<u32, &'static str> {
+    ///     propagate::Ok(inner()?)
+    /// }
+    ///
+    /// let x: Result<u32, &str> = propagate::Ok(2);
+    /// assert_eq!(x.trace_depth(), None);
+    /// assert_eq!(inner().trace_depth(), Some(1));
+    /// assert_eq!(outer().trace_depth(), Some(2));
+    /// ```
+    #[inline]
+    pub fn trace_depth(&self) -> Option<usize> {
+        match self {
+            Ok(_) => None,
+            Err(_, trace) => Some(trace.depth()),
+        }
+    }
+}
+
+impl<T, S> Result<T, io::Error, S> {
+    /// Returns the OS error code of an [`Err`] value, if it was created from
+    /// one, or `None` if the result is [`Ok`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::io;
+    ///
+    /// let x: Result<u32, io::Error> = Result::new_err(io::Error::from_raw_os_error(13));
+    /// assert_eq!(x.raw_os_error(), Some(13));
+    ///
+    /// let x: Result<u32, io::Error> = Result::new_err(io::ErrorKind::NotFound);
+    /// assert_eq!(x.raw_os_error(), None);
+    /// ```
+    #[inline]
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Ok(_) => None,
+            Err(err, _) => err.raw_os_error(),
         }
     }
 }
 
+impl<T, S: Traced + Default> Result<T, io::Error, S> {
+    /// Constructs an [`Err`] from the last OS error that occurred on the
+    /// current thread (see [`io::Error::last_os_error()`]), starting a new
+    /// trace at the caller's location.
+    ///
+    /// This is meant to be called right after a failed system call.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::io;
+    ///
+    /// fn check(ret: i32) -> Result<i32, io::Error> {
+    ///     if ret < 0 {
+    ///         return Result::last_os_error();
+    ///     }
+    ///     propagate::Ok(ret)
+    /// }
+    ///
+    /// assert_eq!(check(3).unwrap(), 3);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn last_os_error() -> Self {
+        let error = io::Error::last_os_error();
+        let trace = new_trace::<io::Error, S>(panic::Location::caller());
+        Err(error, trace)
+    }
+}
+
 impl<T, E: fmt::Debug> Result<T, E> {
     /// Returns the contained [`Ok`] value, consuming the `self` value.
     ///
@@ -774,8 +1858,8 @@ impl<T: Default, E> Result<T, E> {
     /// # use propagate::result::Result;
     /// let good_year_from_input = "1909";
     /// let bad_year_from_input = "190blarg";
-    /// let good_year = good_year_from_input.parse().unwrap_or_default();
-    /// let bad_year = bad_year_from_input.parse().unwrap_or_default();
+    /// let good_year: i32 = good_year_from_input.parse().unwrap_or_default();
+    /// let bad_year: i32 = bad_year_from_input.parse().unwrap_or_default();
     ///
     /// assert_eq!(1909, good_year);
     /// assert_eq!(0, bad_year);
@@ -784,10 +1868,78 @@ impl<T: Default, E> Result<T, E> {
     /// [`parse`]: str::parse
     /// [`FromStr`]: std::str::FromStr
     #[inline]
+    #[track_caller]
     pub fn unwrap_or_default(self) -> T {
         match self {
             Ok(x) => x,
-            Err(_, _) => Default::default(),
+            Err(_, _) => {
+                observe::record::<E>(EventKind::Handled, panic::Location::caller());
+                Default::default()
+            }
+        }
+    }
+}
+
+impl<T, S> Result<T, Infallible, S> {
+    /// Returns the contained [`Ok`] value, but never panics.
+    ///
+    /// Unlike [`unwrap`], this method is known to never panic on the
+    /// result types it is implemented for. Therefore, it can be used
+    /// instead of `unwrap` as a maintainability safeguard that will fail
+    /// to compile if the error type of the `Result` is later changed
+    /// to an error that can actually occur.
+    ///
+    /// [`unwrap`]: Result::unwrap
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::convert::Infallible;
+    ///
+    /// let x: Result<u32, Infallible> = propagate::Ok(2);
+    /// assert_eq!(x.into_ok(), 2);
+    /// ```
+    #[inline]
+    pub fn into_ok(self) -> T {
+        match self {
+            Ok(x) => x,
+            Err(e, _) => match e {},
+        }
+    }
+}
+
+impl<E, S> Result<Infallible, E, S> {
+    /// Returns the contained [`Err`] value and its trace, but never panics.
+    ///
+    /// Unlike [`unwrap_err`], this method is known to never panic on the
+    /// result types it is implemented for. Therefore, it can be used
+    /// instead of `unwrap_err` as a maintainability safeguard that will fail
+    /// to compile if the ok type of the `Result` is later changed
+    /// to a type that can actually occur.
+    ///
+    /// [`unwrap_err`]: Result::unwrap_err
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use std::convert::Infallible;
+    ///
+    /// let x: Result<Infallible, &str> = Result::new_err("Nothing here");
+    /// let traced = x.into_err();
+    /// assert_eq!(*traced.error(), "Nothing here");
+    /// assert_eq!(traced.stack().0.len(), 1);
+    /// ```
+    #[inline]
+    pub fn into_err(self) -> TracedError<E, S> {
+        match self {
+            Ok(x) => match x {},
+            Err(err, trace) => TracedError::new(err, trace),
         }
     }
 }
@@ -820,6 +1972,192 @@ impl<T, E, S> Result<Option<T>, E, S> {
     }
 }
 
+/// Interoperability with [`snafu`](https://docs.rs/snafu) context selectors.
+///
+/// Available with the `snafu` feature.
+#[cfg(feature = "snafu")]
+impl<T, E, S: Traced> Result<T, E, S> {
+    /// Wraps a contained [`Err`] value using a snafu context selector, keeping
+    /// the error trace and pushing the caller's location to it.
+    ///
+    /// This is the equivalent of snafu's `ResultExt::context()`, which would
+    /// otherwise drop the trace at the snafu boundary.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// # use propagate::result::Result;
+    /// use snafu::Snafu;
+    /// use std::io;
+    ///
+    /// #[derive(Debug, Snafu)]
+    /// #[snafu(display("could not read {}", path))]
+    /// struct ReadConfig {
+    ///     path: String,
+    ///     source: io::Error,
+    /// }
+    ///
+    /// let x: Result<u32, io::Error> = Result::new_err(io::Error::from(io::ErrorKind::NotFound));
+    /// let y = x.snafu_context(ReadConfigSnafu { path: "a.toml" });
+    ///
+    /// let (err, trace) = y.err_trace().unwrap();
+    /// assert_eq!(err.to_string(), "could not read a.toml");
+    /// assert_eq!(trace.depth(), 2);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn snafu_context<C, E2>(self, context: C) -> Result<T, E2, S>
+    where
+        C: ::snafu::IntoError<E2, Source = E>,
+        E2: std::error::Error + ::snafu::ErrorCompat,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(err, mut trace) => {
+                push_propagated::<E2, S>(&mut trace, panic::Location::caller());
+                Err(context.into_error(err), trace)
+            }
+        }
+    }
+
+    /// Wraps a contained [`Err`] value using a lazily-constructed snafu context
+    /// selector, keeping the error trace and pushing the caller's location to
+    /// it.
+    ///
+    /// This is the lazy version of [`snafu_context`], and the equivalent of
+    /// snafu's `ResultExt::with_context()`.
+    ///
+    /// [`snafu_context`]: Result::snafu_context
+    #[inline]
+    #[track_caller]
+    pub fn with_snafu_context<F, C, E2>(self, context: F) -> Result<T, E2, S>
+    where
+        F: FnOnce(&mut E) -> C,
+        C: ::snafu::IntoError<E2, Source = E>,
+        E2: std::error::Error + ::snafu::ErrorCompat,
+    {
+        match self {
+            Ok(t) => Ok(t),
+            Err(mut err, mut trace) => {
+                push_propagated::<E2, S>(&mut trace, panic::Location::caller());
+                let context = context(&mut err);
+                Err(context.into_error(err), trace)
+            }
+        }
+    }
+}
+
+// The trace is started and extended out of line, on a path marked as cold, so
+// that the success path of `?` compiles to the same code as for std results.
+
+/// Starts a new trace for an error of type `E` at `location`.
+#[inline(never)]
+#[cold]
+pub(crate) fn new_trace<E, S: Traced + Default>(location: &'static panic::Location<'static>) -> S {
+    let mut trace = S::default();
+    match trace::capture_created(location) {
+        Capture::Full => trace.trace(location),
+        Capture::Origin(note) => trace.trace_context(location, note),
+        Capture::Nothing => {}
+    }
+    observe::record_traced::<E, S>(EventKind::Created, location, &trace);
+    crate::debug::check_created::<E>(location);
+    trace
+}
+
+/// Pushes `location` to the trace of an error of type `E` that is being
+/// propagated by `?`.
+#[inline(never)]
+#[cold]
+pub(crate) fn push_propagated<E, S: Traced>(
+    trace: &mut S,
+    location: &'static panic::Location<'static>,
+) {
+    if trace::capture_propagated(trace, location) {
+        trace.trace(location);
+        crate::scope::label(trace);
+    }
+    observe::record_traced::<E, S>(EventKind::Propagated, location, trace);
+    crate::debug::propagated(location);
+}
+
+/// Pushes a boundary frame labeled `label` at `location` to the trace of an
+/// error of type `E` that crossed a thread, task or service boundary.
+///
+/// This is subject to the same configuration as [`push_propagated()`].
+#[inline(never)]
+#[cold]
+pub(crate) fn push_boundary<E, S: Traced>(
+    trace: &mut S,
+    location: &'static panic::Location<'static>,
+    label: &str,
+) {
+    if trace::capture_propagated(trace, location) {
+        trace.trace_boundary(location, label);
+    }
+    observe::record_traced::<E, S>(EventKind::Propagated, location, trace);
+    crate::debug::propagated(location);
+}
+
+/// Pushes a frame annotated with `context` at `location` to the trace of an
+/// error of type `E`.
+///
+/// This is subject to the same configuration as [`push_propagated()`], and
+/// `context` is only rendered if the frame is recorded.
+#[inline(never)]
+#[cold]
+pub(crate) fn push_context<E, S: Traced>(
+    trace: &mut S,
+    location: &'static panic::Location<'static>,
+    context: &dyn fmt::Display,
+) {
+    if trace::capture_propagated(trace, location) {
+        trace.trace_context(location, &context.to_string());
+    }
+    observe::record_traced::<E, S>(EventKind::Propagated, location, trace);
+    crate::debug::propagated(location);
+}
+
+// The code generated by `#[traced]` calls these, since the crates it is used
+// in cannot name the unstable `Try` traits.
+
+/// Splits the operand of a `?` in the body of a `#[traced]` function.
+#[doc(hidden)]
+#[inline]
+pub fn traced_branch<R: Try>(operand: R) -> ControlFlow<R::Residual, R::Output> {
+    operand.branch()
+}
+
+/// Converts the residual of a `?` in the body of a `#[traced]` function to
+/// the result of the function.
+#[doc(hidden)]
+#[inline]
+#[track_caller]
+pub fn traced_residual<R, T: FromResidual<R>>(residual: R) -> T {
+    T::from_residual(residual)
+}
+
+/// Pushes the frame of a `#[traced]` function, annotated with `context`, to
+/// the trace of an error that it returns.
+#[doc(hidden)]
+#[inline]
+#[track_caller]
+pub fn traced_return<T, E, S, C>(result: Result<T, E, S>, context: Option<C>) -> Result<T, E, S>
+where
+    S: Traced,
+    C: fmt::Display,
+{
+    match (result, context) {
+        (Err(err, mut trace), Some(context)) => {
+            push_context::<E, S>(&mut trace, panic::Location::caller(), &context);
+            Err(err, trace)
+        }
+        (result, _) => result,
+    }
+}
+
 // This is a separate function to reduce the code size of the methods
 // TODO: Include the error trace in the panic message.
 #[inline(never)]
@@ -839,11 +2177,13 @@ fn unwrap_failed(msg: &str, error: &dyn fmt::Debug) -> ! {
 
 #[cfg(test)]
 mod test {
+    use crate::result::BoxedResult;
     use crate::test::Fixture;
     use crate::CodeLocation;
     use crate::{Ok, Result};
     use std::fs;
     use std::io;
+    use std::task::Poll;
 
     /*  ____            _         __                  _   _
      * | __ )  __ _ ___(_) ___   / _|_   _ _ __   ___| |_(_) ___  _ __  ___
@@ -871,6 +2211,66 @@ mod test {
         assert_eq!(x.to_std(), std::result::Result::Err("Nothing here"));
     }
 
+    #[test]
+    fn recover_splices_original_trace() {
+        let origin = CodeLocation::here().down_by(1);
+        let x: Result<u32, &str> = Result::new_err("Nothing here");
+
+        let here = CodeLocation::here().down_by(1);
+        let x = x.recover(|err, trace| crate::Err(err.len(), trace));
+        let (err, trace) = x.err_trace().unwrap();
+        assert_eq!(err, 12);
+        assert_eq!(trace.0, vec![origin, here]);
+    }
+
+    #[test]
+    fn or_else_traced_records_recovery_site() {
+        let x: Result<u32, &str> = Result::new_err("primary down");
+
+        let here = CodeLocation::here().down_by(1);
+        let y = x.or_else_traced(|_| -> Result<u32, &str> { Result::new_err("replica down") });
+        let (err, trace) = y.err_trace().unwrap();
+        assert_eq!(err, "replica down");
+        assert_eq!(trace.0, vec![here.clone(), here]);
+    }
+
+    #[test]
+    fn boxed_result_is_close_to_std_size() {
+        use std::mem::size_of;
+
+        // The box is never null, so the discriminant is stored in its niche
+        // and the trace costs at most one pointer.
+        assert_eq!(size_of::<BoxedResult<(), ()>>(), size_of::<usize>());
+        assert_eq!(
+            size_of::<BoxedResult<u64, u64>>(),
+            size_of::<std::result::Result<u64, u64>>()
+        );
+        assert_eq!(
+            size_of::<BoxedResult<u64, &str>>(),
+            size_of::<std::result::Result<u64, &str>>() + size_of::<usize>()
+        );
+        assert_eq!(
+            size_of::<BoxedResult<u64, std::io::Error>>(),
+            size_of::<std::result::Result<u64, std::io::Error>>()
+        );
+        assert!(size_of::<BoxedResult<(), ()>>() < size_of::<Result<(), ()>>());
+    }
+
+    #[test]
+    fn zip_with_collects_both_errors() {
+        let width_at = CodeLocation::here().down_by(1);
+        let width: Result<u32, &str> = Result::new_err("bad width");
+        let height_at = CodeLocation::here().down_by(1);
+        let height: Result<u32, &str> = Result::new_err("bad height");
+
+        let here = CodeLocation::here().down_by(1);
+        let (errors, trace) = width.zip_with(height, |w, h| w * h).err_trace().unwrap();
+        assert_eq!(trace.0, vec![here]);
+
+        let traces: Vec<_> = errors.traces().map(|t| t.0.clone()).collect();
+        assert_eq!(traces, vec![vec![width_at], vec![height_at]]);
+    }
+
     /*   ____ _           _       _
      *  / ___| |__   __ _(_)_ __ (_)_ __   __ _
      * | |   | '_ \ / _` | | '_ \| | '_ \ / _` |
@@ -917,6 +2317,80 @@ mod test {
         fix.assert_result_has_stack(result, &["io_error", "bottom"]);
     }
 
+    #[test]
+    fn with_context_pushes_message_frame() {
+        let mut fix = Fixture::default();
+
+        let mut bottom = || -> Result<(), io::Error> {
+            fix.tag_location("context", CodeLocation::here().down_by(1));
+            let result = maybe_io_error(&mut fix, true).with_context(|| "opening file");
+            Ok(result?)
+        };
+
+        let (_, trace) = bottom().err_trace().unwrap();
+        let expected = fix
+            .get_location("context")
+            .clone()
+            .with_message("opening file");
+        assert_eq!(trace.0.len(), 3);
+        assert_eq!(trace.0[1], expected);
+    }
+
+    #[cfg(feature = "snafu")]
+    #[test]
+    fn snafu_context_extends_inner_trace() {
+        use snafu::Snafu;
+
+        #[derive(Debug, Snafu)]
+        struct Outer {
+            source: io::Error,
+        }
+
+        let mut fix = Fixture::default();
+
+        let mut bottom = || -> Result<(), Outer> {
+            fix.tag_location("context", CodeLocation::here().down_by(1));
+            maybe_io_error(&mut fix, true).snafu_context(OuterSnafu)
+        };
+
+        let result = bottom();
+        fix.assert_result_has_stack(result, &["io_error", "context"]);
+    }
+
+    #[test]
+    fn question_mark_operator_coerces_to_poll() {
+        let mut fix = Fixture::default();
+
+        let mut poll = || -> Poll<Result<(), io::Error>> {
+            fix.tag_location("poll", CodeLocation::here().down_by(1));
+            maybe_io_error(&mut fix, true)?;
+            Poll::Ready(Ok(()))
+        };
+
+        match poll() {
+            Poll::Ready(result) => fix.assert_result_has_stack(result, &["io_error", "poll"]),
+            Poll::Pending => panic!("expected Poll::Ready"),
+        }
+    }
+
+    #[test]
+    fn question_mark_operator_coerces_to_poll_option() {
+        let mut fix = Fixture::default();
+
+        let mut poll_next = || -> Poll<Option<Result<(), io::Error>>> {
+            fix.tag_location("poll_next", CodeLocation::here().down_by(1));
+            maybe_io_error(&mut fix, true)?;
+            Poll::Ready(None)
+        };
+
+        match poll_next() {
+            Poll::Ready(Some(result)) => {
+                fix.assert_result_has_stack(result, &["io_error", "poll_next"])
+            }
+            _ => panic!("expected Poll::Ready(Some(_))"),
+        }
+    }
+
     #[test]
     fn return_without_propagate_does_not_append_to_stack() {
         let mut fix = Fixture::default();
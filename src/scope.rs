@@ -0,0 +1,211 @@
+//! Labeling the errors that pass through a region of code.
+//!
+//! [`scope()`] returns a guard that describes what the current thread is
+//! doing. While the guard is alive, every error propagated by the `?` operator
+//! on the thread gets a context frame with the label of the scope, at the
+//! location where the scope was entered, the first time it is propagated
+//! inside the scope. This groups the frames of a trace by task, like
+//! [`Result::context()`], without every function along the way having to add
+//! its own context:
+//!
+//! ```
+//! fn parse(text: &str) -> propagate::Result<u32, std::num::ParseIntError> {
+//!     propagate::Ok(text.trim().parse()?)
+//! }
+//!
+//! fn read_port(text: &str) -> propagate::Result<u32, std::num::ParseIntError> {
+//!     propagate::Ok(parse(text)?)
+//! }
+//!
+//! fn load_config() -> propagate::Result<u32, std::num::ParseIntError> {
+//!     let _scope = propagate::scope("loading config");
+//!     propagate::Ok(read_port("eighty")?)
+//! }
+//!
+//! let (_, trace) = load_config().err_trace().unwrap();
+//! let messages: Vec<_> = trace.0.iter().filter_map(|frame| frame.message()).collect();
+//! assert_eq!(messages, ["loading config"]);
+//! ```
+//!
+//! Scopes can be nested; the frame of an inner scope precedes the frame of an
+//! outer scope in the trace. Errors that are returned from a scope without
+//! being propagated by `?` inside it are not labeled.
+//!
+//! Since a stack has to be searched for the frame of a scope to avoid adding
+//! it twice, only stacks that keep their frames (see [`Traced::frames()`])
+//! are labeled, and only by the 64 innermost scopes of the thread.
+//!
+//! [`Result::context()`]: crate::Result::context
+//! [`Traced::frames()`]: crate::Traced::frames
+
+use crate::trace::{CodeLocation, Traced};
+
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::panic;
+
+struct Scope {
+    id: u64,
+    label: Cow<'static, str>,
+    location: &'static panic::Location<'static>,
+}
+
+thread_local! {
+    static SCOPES: RefCell<Vec<Scope>> = const { RefCell::new(Vec::new()) };
+    static NEXT_ID: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Enters a scope labeled `label` on the current thread, until the returned
+/// guard is dropped.
+///
+/// See the [module documentation](self) for more information.
+#[track_caller]
+pub fn scope(label: impl Into<Cow<'static, str>>) -> ScopeGuard {
+    let location = panic::Location::caller();
+    let id = NEXT_ID.with(|next| {
+        let id = next.get();
+        next.set(id + 1);
+        id
+    });
+    SCOPES.with(|scopes| {
+        scopes.borrow_mut().push(Scope {
+            id,
+            label: label.into(),
+            location,
+        })
+    });
+    ScopeGuard {
+        id,
+        _not_send: PhantomData,
+    }
+}
+
+/// Leaves the scope entered by [`scope()`] when dropped.
+#[must_use = "the scope is left as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct ScopeGuard {
+    id: u64,
+    // The scope belongs to the thread that entered it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        let _ = SCOPES.try_with(|scopes| {
+            let mut scopes = scopes.borrow_mut();
+            if let Some(index) = scopes.iter().rposition(|scope| scope.id == self.id) {
+                scopes.remove(index);
+            }
+        });
+    }
+}
+
+/// The number of innermost scopes of a thread that label errors.
+const MAX_LABELING_SCOPES: usize = 64;
+
+impl Scope {
+    /// Returns `true` if `frame` is the frame of this scope.
+    fn is_labeled_by(&self, frame: &CodeLocation) -> bool {
+        frame.line() == self.location.line()
+            && frame.file() == self.location.file()
+            && frame.message() == Some(self.label.as_ref())
+    }
+}
+
+/// Appends a frame to `stack` for each scope on the current thread that it
+/// does not already have a frame for, innermost first.
+pub(crate) fn label<S: Traced + ?Sized>(stack: &mut S) {
+    let _ = SCOPES.try_with(|scopes| {
+        let scopes = scopes.borrow();
+        let scopes = &scopes[scopes.len().saturating_sub(MAX_LABELING_SCOPES)..];
+        if scopes.is_empty() {
+            return;
+        }
+
+        // The scopes that already have a frame are found in a single pass
+        // over the trace, which stops as soon as all of them are found.
+        let all = u64::MAX >> (64 - scopes.len());
+        let mut labeled = 0u64;
+        match stack.frames() {
+            Some(frames) => {
+                for frame in frames.filter(|frame| frame.message().is_some()) {
+                    for (index, scope) in scopes.iter().enumerate() {
+                        if scope.is_labeled_by(&frame) {
+                            labeled |= 1 << index;
+                        }
+                    }
+                    if labeled == all {
+                        return;
+                    }
+                }
+            }
+            None => return,
+        }
+
+        for (index, scope) in scopes.iter().enumerate().rev() {
+            if labeled & (1 << index) == 0 {
+                stack.trace_context(scope.location, &scope.label);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{CodeLocation, Result};
+
+    fn fails() -> Result<(), &'static str> {
+        Result::new_err("Nothing here")
+    }
+
+    fn propagates() -> Result<(), &'static str> {
+        fails()?;
+        crate::Ok(())
+    }
+
+    fn propagates_twice() -> Result<(), &'static str> {
+        propagates()?;
+        crate::Ok(())
+    }
+
+    #[test]
+    fn nested_scopes_label_once() {
+        let outer_at = CodeLocation::here().down_by(1);
+        let outer = scope("outer");
+        let inner_at = CodeLocation::here().down_by(1);
+        let inner = scope(format!("inner {}", 1));
+        let (_, trace) = propagates_twice().err_trace().unwrap();
+        drop(inner);
+        drop(outer);
+
+        let labels: Vec<_> = trace
+            .0
+            .iter()
+            .filter(|frame| frame.message().is_some())
+            .cloned()
+            .collect();
+        assert_eq!(
+            labels,
+            vec![
+                inner_at.with_message("inner 1"),
+                outer_at.with_message("outer")
+            ]
+        );
+        SCOPES.with(|scopes| assert!(scopes.borrow().is_empty()));
+    }
+
+    #[test]
+    fn frames_from_other_files_do_not_label() {
+        let at = CodeLocation::here().down_by(1);
+        let guard = scope("outer");
+        let other = CodeLocation::new("other.rs", at.line()).with_message("outer");
+        let mut trace = crate::ErrorTrace(vec![other.clone()]);
+        label(&mut trace);
+        label(&mut trace);
+        drop(guard);
+
+        assert_eq!(trace.0, vec![other, at.with_message("outer")]);
+    }
+}
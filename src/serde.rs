@@ -0,0 +1,177 @@
+//! Traced errors as [`serde`] errors.
+//!
+//! A [`TracedError`] implements [`ser::Error`] and [`de::Error`] when its
+//! error value does, so it can be the `Error` type of a custom serializer or
+//! deserializer. Code inside of it can then use traced results internally,
+//! propagate them with `?`, and still satisfy serde's bounds. The trace of an
+//! error constructed through these traits starts at the location where serde
+//! (or the implementation) reported it.
+//!
+//! For implementations that do not have an error type of their own,
+//! [`SerdeError`] keeps only the message of the error.
+//!
+//! Available with the `serde` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::serde::SerdeError;
+//! use propagate::TracedError;
+//! use serde::de::value::StrDeserializer;
+//! use serde::de::{Deserialize, IntoDeserializer};
+//!
+//! let deserializer: StrDeserializer<TracedError<SerdeError>> = "eighty".into_deserializer();
+//! let error = u16::deserialize(deserializer).unwrap_err();
+//!
+//! assert!(error.to_string().starts_with("invalid type: string \"eighty\""));
+//! assert_eq!(error.depth(), 1);
+//! ```
+//!
+//! [`ser::Error`]: ::serde::ser::Error
+//! [`de::Error`]: ::serde::de::Error
+
+use crate::error::TracedError;
+use crate::trace::Traced;
+
+use std::error::Error;
+use std::fmt;
+use std::panic;
+
+use ::serde::de::{self, Expected, Unexpected};
+use ::serde::ser;
+
+/// Wraps `error` in a traced error whose trace starts at the caller's
+/// location.
+#[track_caller]
+fn traced<E, S: Traced + Default>(error: E) -> TracedError<E, S> {
+    let stack = crate::result::new_trace::<E, S>(panic::Location::caller());
+    TracedError::new(error, stack)
+}
+
+impl<E, S> ser::Error for TracedError<E, S>
+where
+    E: ser::Error,
+    S: Traced + Default + fmt::Debug,
+{
+    #[track_caller]
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        traced(E::custom(msg))
+    }
+}
+
+/// Each method forwards to the same method of `E`, so that the messages of
+/// `E` are kept.
+impl<E, S> de::Error for TracedError<E, S>
+where
+    E: de::Error,
+    S: Traced + Default + fmt::Debug,
+{
+    #[track_caller]
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        traced(E::custom(msg))
+    }
+
+    #[track_caller]
+    fn invalid_type(unexp: Unexpected<'_>, exp: &dyn Expected) -> Self {
+        traced(E::invalid_type(unexp, exp))
+    }
+
+    #[track_caller]
+    fn invalid_value(unexp: Unexpected<'_>, exp: &dyn Expected) -> Self {
+        traced(E::invalid_value(unexp, exp))
+    }
+
+    #[track_caller]
+    fn invalid_length(len: usize, exp: &dyn Expected) -> Self {
+        traced(E::invalid_length(len, exp))
+    }
+
+    #[track_caller]
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        traced(E::unknown_variant(variant, expected))
+    }
+
+    #[track_caller]
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
+        traced(E::unknown_field(field, expected))
+    }
+
+    #[track_caller]
+    fn missing_field(field: &'static str) -> Self {
+        traced(E::missing_field(field))
+    }
+
+    #[track_caller]
+    fn duplicate_field(field: &'static str) -> Self {
+        traced(E::duplicate_field(field))
+    }
+}
+
+/// A serde error that only keeps its message.
+///
+/// `TracedError<SerdeError>` can be used as the error type of a serializer or
+/// deserializer that does not need to tell errors apart.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::serde::SerdeError;
+/// use serde::de::Error as _;
+///
+/// let error = SerdeError::missing_field("port");
+/// assert_eq!(error.message(), "missing field `port`");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SerdeError {
+    message: String,
+}
+
+impl SerdeError {
+    /// Returns the message of the error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self {
+            message: msg.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CodeLocation;
+
+    #[test]
+    fn traced_error_starts_at_reporting_site() {
+        let created_at = CodeLocation::here().down_by(1);
+        let error: TracedError<SerdeError> = de::Error::duplicate_field("port");
+        assert_eq!(error.error().message(), "duplicate field `port`");
+        assert_eq!(error.stack().0, vec![created_at]);
+
+        let created_at = CodeLocation::here().down_by(1);
+        let error: TracedError<SerdeError> = ser::Error::custom("unsupported key");
+        assert_eq!(error.to_string(), "unsupported key");
+        assert_eq!(error.stack().0, vec![created_at]);
+    }
+}
@@ -0,0 +1,622 @@
+//! Destinations for reporting traced errors.
+
+use crate::error::TracedError;
+#[cfg(any(feature = "syslog", all(feature = "journald", unix)))]
+use crate::redact::{self, Redactor};
+#[cfg(feature = "syslog")]
+use crate::trace::Traced;
+
+use std::collections::hash_map::{DefaultHasher, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A sink that writes traced errors to a writer, suppressing duplicates.
+///
+/// Each error is logged under a key. Once an error has been written for a
+/// key, further errors logged under the same key are suppressed until
+/// `period` has elapsed. The next error written for that key is preceded by a
+/// summary of how many errors were suppressed in the meantime.
+///
+/// This prevents an error in a tight loop from flooding logs with identical
+/// traces.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::sink::RateLimitedSink;
+/// use std::time::Duration;
+///
+/// let sink = RateLimitedSink::new(Vec::new(), Duration::from_secs(60));
+///
+/// for _ in 0..100 {
+///     let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+///     sink.log("lookup", &result.traced_err().unwrap()).unwrap();
+/// }
+///
+/// let output = String::from_utf8(sink.into_inner()).unwrap();
+/// assert_eq!(output.matches("Nothing here").count(), 1);
+/// ```
+pub struct RateLimitedSink<W> {
+    writer: Mutex<W>,
+    limiter: Mutex<RateLimiter>,
+    period: Duration,
+}
+
+impl<W: io::Write> RateLimitedSink<W> {
+    /// Constructs a new sink that writes to `writer`, suppressing duplicates
+    /// within `period`.
+    pub fn new(writer: W, period: Duration) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+            limiter: Mutex::new(RateLimiter::default()),
+            period,
+        }
+    }
+
+    /// Writes `error` and its trace to the sink, unless another error was
+    /// written under the same `key` less than one period ago.
+    ///
+    /// Returns `true` if the error was written, or `false` if it was
+    /// suppressed.
+    pub fn log<K, E, S>(&self, key: K, error: &TracedError<E, S>) -> io::Result<bool>
+    where
+        K: Hash,
+        E: fmt::Display,
+        S: fmt::Display,
+    {
+        self.log_at(key, error, Instant::now())
+    }
+
+    fn log_at<K, E, S>(&self, key: K, error: &TracedError<E, S>, now: Instant) -> io::Result<bool>
+    where
+        K: Hash,
+        E: fmt::Display,
+        S: fmt::Display,
+    {
+        let admission = lock(&self.limiter).admit(hash_key(key), self.period, now);
+        match admission {
+            Some(suppressed) => {
+                let mut writer = lock(&self.writer);
+                write_error(&mut *writer, error, suppressed, self.period)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Returns the underlying writer, consuming `self`.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<E: fmt::Display, S: fmt::Display> TracedError<E, S> {
+    /// Writes the error and its trace to standard error, unless another error
+    /// was logged under the same `key` less than `period` ago.
+    ///
+    /// See [`RateLimitedSink`] for details. Returns `true` if the error was
+    /// written, or `false` if it was suppressed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// for _ in 0..100 {
+    ///     let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+    ///     if let Some(err) = result.traced_err() {
+    ///         err.log_rate_limited("hot loop", Duration::from_secs(10));
+    ///     }
+    /// }
+    /// ```
+    pub fn log_rate_limited<K: Hash>(&self, key: K, period: Duration) -> bool {
+        static LIMITER: Mutex<Option<RateLimiter>> = Mutex::new(None);
+
+        let admission = lock(&LIMITER)
+            .get_or_insert_with(RateLimiter::default)
+            .admit(hash_key(key), period, Instant::now());
+        match admission {
+            Some(suppressed) => {
+                let stderr = io::stderr();
+                let mut stderr = stderr.lock();
+                let _ = write_error(&mut stderr, self, suppressed, period);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A sink that sends traced errors to syslog.
+///
+/// Each error is sent as a single message containing the error and a compacted,
+/// single-line rendering of its trace. This is meant for daemons that must not
+/// write to stdout or stderr.
+///
+/// Available with the `syslog` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use propagate::sink::SyslogSink;
+/// use syslog::{Facility, Formatter3164};
+///
+/// let formatter = Formatter3164 {
+///     facility: Facility::LOG_DAEMON,
+///     hostname: None,
+///     process: "mydaemon".into(),
+///     pid: std::process::id(),
+/// };
+/// let sink = SyslogSink::new(syslog::unix(formatter).unwrap());
+///
+/// let result: propagate::Result<(), &str> = propagate::Result::new_err("disk full");
+/// sink.log(&result.traced_err().unwrap()).unwrap();
+/// ```
+#[cfg(feature = "syslog")]
+pub struct SyslogSink<W: io::Write, F> {
+    logger: Mutex<::syslog::Logger<W, F>>,
+}
+
+#[cfg(feature = "syslog")]
+impl<W, F> SyslogSink<W, F>
+where
+    W: io::Write,
+    F: ::syslog::LogFormat<String>,
+{
+    /// Constructs a new sink that sends messages through `logger`.
+    pub fn new(logger: ::syslog::Logger<W, F>) -> Self {
+        Self {
+            logger: Mutex::new(logger),
+        }
+    }
+
+    /// Sends `error` and its trace to syslog with severity `LOG_ERR`.
+    pub fn log<E, S>(&self, error: &TracedError<E, S>) -> ::syslog::Result<()>
+    where
+        E: fmt::Display + 'static,
+        S: Traced + fmt::Display,
+    {
+        self.log_with_severity(::syslog::Severity::LOG_ERR, error)
+    }
+
+    /// Sends `error` and its trace to syslog with the given severity.
+    ///
+    /// Errors that stop the daemon from working should typically be logged
+    /// with `LOG_CRIT`, and errors that are recovered from with `LOG_WARNING`.
+    ///
+    /// Errors whose type was registered with
+    /// [`register_error_code()`][crate::error::register_error_code] are
+    /// preceded by their code. The frames of the trace are passed through the
+    /// installed [redactor](crate::redact), if any.
+    pub fn log_with_severity<E, S>(
+        &self,
+        severity: ::syslog::Severity,
+        error: &TracedError<E, S>,
+    ) -> ::syslog::Result<()>
+    where
+        E: fmt::Display + 'static,
+        S: Traced + fmt::Display,
+    {
+        let message = syslog_message(error, redact::redactor().as_deref());
+
+        let mut logger = lock(&self.logger);
+        let logger = &mut *logger;
+        logger
+            .formatter
+            .format(&mut logger.backend, severity, message)
+    }
+}
+
+/// A sink that sends traced errors to the systemd journal as structured
+/// entries.
+///
+/// The `CODE_FILE` and `CODE_LINE` fields of each entry are set from the
+/// origin of the trace, so `journalctl` shows where the error was created. The
+/// rest of the trace is stored in the `PROPAGATE_TRACE` field, one frame per
+/// line. Errors whose type was registered with
+/// [`register_error_code()`][crate::error::register_error_code] have their
+/// code in the `ERROR_CODE` field. File names and notes are passed through the
+/// installed [redactor](crate::redact), if any.
+///
+/// Entries are sent using the journal's native protocol, so no additional
+/// libraries are needed. Entries that are too large for a single datagram are
+/// not supported.
+///
+/// Available with the `journald` feature on Unix platforms.
+///
+/// # Examples
+///
+/// ```no_run
+/// use propagate::sink::JournaldSink;
+///
+/// let sink = JournaldSink::new().unwrap();
+///
+/// let result: propagate::Result<(), &str> = propagate::Result::new_err("disk full");
+/// sink.log(&result.traced_err().unwrap()).unwrap();
+/// ```
+#[cfg(all(feature = "journald", unix))]
+pub struct JournaldSink {
+    socket: std::os::unix::net::UnixDatagram,
+    path: std::path::PathBuf,
+}
+
+#[cfg(all(feature = "journald", unix))]
+impl JournaldSink {
+    /// Constructs a sink that sends entries to the default journal socket,
+    /// `/run/systemd/journal/socket`.
+    pub fn new() -> io::Result<Self> {
+        Self::with_path("/run/systemd/journal/socket")
+    }
+
+    /// Constructs a sink that sends entries to the journal socket at `path`.
+    pub fn with_path(path: impl Into<std::path::PathBuf>) -> io::Result<Self> {
+        Ok(Self {
+            socket: std::os::unix::net::UnixDatagram::unbound()?,
+            path: path.into(),
+        })
+    }
+
+    /// Sends `error` and its trace to the journal with priority 3 (`err`).
+    pub fn log<E: fmt::Display + 'static>(&self, error: &TracedError<E>) -> io::Result<()> {
+        let entry = journal_entry(error, redact::redactor().as_deref());
+        self.socket.send_to(&entry, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Serializes `error` as a journal entry in the native protocol format.
+#[cfg(all(feature = "journald", unix))]
+fn journal_entry<E: fmt::Display + 'static>(
+    error: &TracedError<E>,
+    redactor: Option<&dyn Redactor>,
+) -> Vec<u8> {
+    let frames = &error.stack().0;
+    let mut fields = vec![
+        ("MESSAGE", error.error().to_string()),
+        ("PRIORITY", "3".to_string()),
+    ];
+    if let Some(code) = crate::error::code_of(error.error()) {
+        fields.push(("ERROR_CODE", code));
+    }
+    if let Some(origin) = frames.first() {
+        let file = crate::fmt::remap_path(origin.file());
+        let file = match redactor {
+            Some(redactor) => redactor.redact_path(&file).into_owned(),
+            None => file.into_owned(),
+        };
+        fields.push(("CODE_FILE", file));
+        fields.push(("CODE_LINE", origin.line().to_string()));
+    }
+    if frames.len() > 1 {
+        let rest: Vec<String> = frames[1..]
+            .iter()
+            .map(|frame| redact::display_frame(frame, redactor))
+            .collect();
+        fields.push(("PROPAGATE_TRACE", rest.join("\n")));
+    }
+
+    let mut entry = Vec::new();
+    for (name, value) in fields {
+        entry.extend_from_slice(name.as_bytes());
+        if value.contains('\n') {
+            // Values containing newlines are length-prefixed.
+            entry.push(b'\n');
+            entry.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            entry.push(b'=');
+        }
+        entry.extend_from_slice(value.as_bytes());
+        entry.push(b'\n');
+    }
+
+    entry
+}
+
+/// Formats the message that [`SyslogSink`] sends for `error`.
+#[cfg(feature = "syslog")]
+fn syslog_message<E, S>(error: &TracedError<E, S>, redactor: Option<&dyn Redactor>) -> String
+where
+    E: fmt::Display + 'static,
+    S: Traced + fmt::Display,
+{
+    let trace = match error.stack().frames() {
+        Some(frames) => frames
+            .enumerate()
+            .map(|(index, frame)| format!("{}: {}", index, redact::display_frame(&frame, redactor)))
+            .collect::<Vec<_>>()
+            .join("; "),
+        None => crate::fmt::compact_lines(&error.stack().to_string()),
+    };
+    match crate::error::code_of(error.error()) {
+        Some(code) => format!("{}: {} [trace: {}]", code, error.error(), trace),
+        None => format!("{} [trace: {}]", error.error(), trace),
+    }
+}
+
+fn write_error<W, E, S>(
+    writer: &mut W,
+    error: &TracedError<E, S>,
+    suppressed: usize,
+    period: Duration,
+) -> io::Result<()>
+where
+    W: io::Write + ?Sized,
+    E: fmt::Display,
+    S: fmt::Display,
+{
+    if suppressed > 0 {
+        writeln!(
+            writer,
+            "({} similar error(s) suppressed in the last {:?})",
+            suppressed, period
+        )?;
+    }
+    writeln!(writer, "Error: {}", error.error())?;
+    writeln!(writer, "Return Trace: {}", error.stack())
+}
+
+fn hash_key<K: Hash>(key: K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Tracks, for each key, when an error was last emitted and how many have
+/// been suppressed since.
+///
+/// Windows that have ended are dropped whenever the number of keys doubles,
+/// so that keys that are seen once (e.g. containing IDs) do not grow the map
+/// without bound. The errors suppressed for a key whose window was dropped are
+/// not summarized when it comes back.
+#[derive(Default)]
+struct RateLimiter {
+    entries: HashMap<u64, Window>,
+    prune_at: usize,
+}
+
+struct Window {
+    start: Instant,
+    period: Duration,
+    suppressed: usize,
+}
+
+impl Window {
+    fn new(start: Instant, period: Duration) -> Self {
+        Self {
+            start,
+            period,
+            suppressed: 0,
+        }
+    }
+
+    fn is_open(&self, now: Instant) -> bool {
+        now.duration_since(self.start) < self.period
+    }
+}
+
+impl RateLimiter {
+    /// The number of keys below which ended windows are not dropped.
+    const MIN_PRUNE_LEN: usize = 64;
+
+    /// Returns the number of errors suppressed since the last emission if an
+    /// error should be emitted for `key`, or `None` if it should be
+    /// suppressed.
+    fn admit(&mut self, key: u64, period: Duration, now: Instant) -> Option<usize> {
+        match self.entries.get_mut(&key) {
+            Some(window) if window.is_open(now) => {
+                window.suppressed += 1;
+                None
+            }
+            Some(window) => {
+                let suppressed = window.suppressed;
+                *window = Window::new(now, period);
+                Some(suppressed)
+            }
+            None => {
+                if self.entries.len() >= self.prune_at {
+                    self.entries.retain(|_, window| window.is_open(now));
+                    self.prune_at = (self.entries.len() * 2).max(Self::MIN_PRUNE_LEN);
+                }
+                self.entries.insert(key, Window::new(now, period));
+                Some(0)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Result;
+
+    fn error(msg: &'static str) -> TracedError<&'static str> {
+        let result: Result<(), &str> = Result::new_err(msg);
+        result.traced_err().unwrap()
+    }
+
+    #[test]
+    fn suppresses_within_period_and_summarizes_after() {
+        let sink = RateLimitedSink::new(Vec::new(), Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!(sink.log_at("a", &error("first"), start).unwrap());
+        assert!(!sink.log_at("a", &error("second"), start).unwrap());
+        assert!(!sink
+            .log_at("a", &error("third"), start + Duration::from_secs(5))
+            .unwrap());
+        assert!(sink.log_at("b", &error("other key"), start).unwrap());
+        assert!(sink
+            .log_at("a", &error("fourth"), start + Duration::from_secs(10))
+            .unwrap());
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let errors: Vec<&str> = output
+            .lines()
+            .filter(|line| line.starts_with("Error: ") || line.starts_with('('))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Error: first",
+                "Error: other key",
+                "(2 similar error(s) suppressed in the last 10s)",
+                "Error: fourth",
+            ]
+        );
+    }
+
+    #[test]
+    fn ended_windows_are_dropped() {
+        let period = Duration::from_secs(10);
+        let start = Instant::now();
+        let mut limiter = RateLimiter::default();
+
+        let keys = RateLimiter::MIN_PRUNE_LEN as u64;
+        for key in 0..keys {
+            assert_eq!(limiter.admit(key, period, start), Some(0));
+        }
+        assert_eq!(limiter.admit(0, period, start), None);
+        assert_eq!(limiter.entries.len(), keys as usize);
+
+        let later = start + period;
+        assert_eq!(limiter.admit(keys, Duration::from_secs(60), later), Some(0));
+        assert_eq!(limiter.entries.len(), 1);
+        for key in 0..keys {
+            assert_eq!(limiter.admit(key, period, later), Some(0));
+        }
+        assert_eq!(limiter.admit(keys, period, later), None);
+    }
+
+    #[cfg(all(feature = "journald", unix))]
+    #[test]
+    fn journal_entry_sets_code_location_from_origin() {
+        use crate::CodeLocation;
+
+        let error = error("disk full").modify_stack(|trace| {
+            trace.0 = vec![
+                CodeLocation::new("a.rs", 1),
+                CodeLocation::new("b.rs", 2),
+                CodeLocation::new("c.rs", 3),
+            ];
+        });
+
+        let mut expected = b"MESSAGE=disk full\nPRIORITY=3\nCODE_FILE=a.rs\nCODE_LINE=1\n".to_vec();
+        expected.extend_from_slice(b"PROPAGATE_TRACE\n");
+        expected.extend_from_slice(&13u64.to_le_bytes());
+        expected.extend_from_slice(b"b.rs:2\nc.rs:3\n");
+        assert_eq!(journal_entry(&error, None), expected);
+    }
+
+    #[cfg(all(feature = "journald", unix))]
+    #[test]
+    fn journal_entry_redacts_frames() {
+        use crate::redact::{PathRedaction, Redaction};
+        use crate::CodeLocation;
+
+        let error = error("disk full").modify_stack(|trace| {
+            trace.0 = vec![
+                CodeLocation::new("src/a.rs", 1),
+                CodeLocation::new("src/b.rs", 2).with_message("user alice"),
+            ];
+        });
+        let redaction = Redaction::new()
+            .paths(PathRedaction::FileName)
+            .scrub_notes(true);
+
+        let entry = journal_entry(&error, Some(&redaction));
+        let entry = String::from_utf8_lossy(&entry);
+        assert!(entry.contains("CODE_FILE=a.rs\n"));
+        assert!(entry.ends_with("PROPAGATE_TRACE=b.rs:2: [redacted]\n"));
+    }
+
+    #[cfg(all(feature = "journald", unix))]
+    #[test]
+    fn journal_entry_includes_registered_error_code() {
+        use crate::error::{register_error_code, ErrorCode};
+        use std::borrow::Cow;
+
+        #[derive(Debug)]
+        struct DiskFull;
+
+        impl fmt::Display for DiskFull {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "disk full")
+            }
+        }
+
+        impl std::error::Error for DiskFull {}
+
+        impl ErrorCode for DiskFull {
+            fn code(&self) -> Cow<'_, str> {
+                "E0028".into()
+            }
+        }
+
+        register_error_code::<DiskFull>();
+        let result: Result<(), DiskFull> = Result::new_err(DiskFull);
+        let entry = journal_entry(&result.traced_err().unwrap(), None);
+        assert!(entry.starts_with(b"MESSAGE=disk full\nPRIORITY=3\nERROR_CODE=E0028\n"));
+    }
+
+    #[cfg(feature = "syslog")]
+    #[test]
+    fn syslog_message_redacts_frames() {
+        use crate::redact::{PathRedaction, Redaction};
+        use crate::CodeLocation;
+
+        let error = error("disk full").modify_stack(|trace| {
+            trace.0 = vec![
+                CodeLocation::new("src/a.rs", 1).labeled("thread 'worker'"),
+                CodeLocation::new("src/b.rs", 2),
+            ];
+        });
+        let redaction = Redaction::new()
+            .paths(PathRedaction::FileName)
+            .scrub_notes(true);
+
+        assert_eq!(
+            syslog_message(&error, Some(&redaction)),
+            "disk full [trace: 0: a.rs:1 ([redacted]); 1: b.rs:2]"
+        );
+    }
+
+    #[cfg(feature = "syslog")]
+    #[test]
+    fn syslog_sink_sends_compacted_trace() {
+        use crate::CodeLocation;
+        use ::syslog::{LogFormat, Logger, Severity};
+
+        struct Plain;
+
+        impl LogFormat<String> for Plain {
+            fn format<W: io::Write>(
+                &self,
+                w: &mut W,
+                severity: Severity,
+                message: String,
+            ) -> ::syslog::Result<()> {
+                write!(w, "<{}> {}", severity as u8, message)?;
+                Ok(())
+            }
+        }
+
+        let sink = SyslogSink::new(Logger::new(Vec::new(), Plain));
+        let error = error("disk full").modify_stack(|trace| {
+            trace.0 = vec![CodeLocation::new("a.rs", 1), CodeLocation::new("b.rs", 2)];
+        });
+        sink.log(&error).unwrap();
+
+        let logger = sink.logger.into_inner().unwrap();
+        assert_eq!(
+            String::from_utf8(logger.backend).unwrap(),
+            "<3> disk full [trace: 0: a.rs:1; 1: b.rs:2]"
+        );
+    }
+}
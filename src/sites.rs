@@ -0,0 +1,211 @@
+//! A registry of the sites where traced errors are created and propagated.
+//!
+//! Sites written with the [`new_err!`] and [`propagate!`] macros (instead of
+//! [`Result::new_err()`] and the `?` operator) are collected into a static
+//! table at link time, which can be listed at runtime with [`trace_sites()`].
+//! Each site also records whether it has been reached, so tooling can report
+//! the error paths that a test suite never exercised:
+//!
+//! ```
+//! use propagate::sites;
+//!
+//! fn parse(s: &str) -> propagate::Result<u32, String> {
+//!     if s.is_empty() {
+//!         return propagate::new_err!("empty input".to_string());
+//!     }
+//!     propagate::Ok(s.len() as u32)
+//! }
+//!
+//! fn load() -> propagate::Result<u32, String> {
+//!     let n = propagate::propagate!(parse(""));
+//!     propagate::Ok(n)
+//! }
+//!
+//! assert!(load().is_err());
+//! assert!(sites::trace_sites().all(|site| site.exercised()));
+//! ```
+//!
+//! Plain `?` operators cannot be registered, since nothing runs at their call
+//! sites until an error is actually propagated.
+//!
+//! Available with the `trace-sites` feature.
+//!
+//! [`Result::new_err()`]: crate::Result::new_err
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[doc(hidden)]
+pub use ::inventory as __inventory;
+
+/// A call site that can create or propagate a traced error.
+#[derive(Debug)]
+pub struct TraceSite {
+    file: &'static str,
+    line: u32,
+    column: u32,
+    kind: SiteKind,
+    exercised: AtomicBool,
+}
+
+/// What a [`TraceSite`] does to an error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SiteKind {
+    /// The site creates new errors, with [`new_err!`].
+    Created,
+    /// The site propagates errors, with [`propagate!`].
+    Propagated,
+}
+
+impl TraceSite {
+    #[doc(hidden)]
+    pub const fn new(file: &'static str, line: u32, column: u32, kind: SiteKind) -> Self {
+        Self {
+            file,
+            line,
+            column,
+            kind,
+            exercised: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the name of the source file.
+    pub const fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// Returns the line number in the source file.
+    pub const fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns the column number in the source file.
+    pub const fn column(&self) -> u32 {
+        self.column
+    }
+
+    /// Returns what the site does to an error.
+    pub const fn kind(&self) -> SiteKind {
+        self.kind
+    }
+
+    /// Returns `true` if the site has created or propagated an error.
+    pub fn exercised(&self) -> bool {
+        self.exercised.load(Ordering::Relaxed)
+    }
+
+    #[doc(hidden)]
+    pub fn mark_exercised(&self) {
+        self.exercised.store(true, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for TraceSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            crate::fmt::remap_path(self.file),
+            self.line,
+            self.column
+        )
+    }
+}
+
+#[doc(hidden)]
+pub struct Registered(pub &'static TraceSite);
+
+::inventory::collect!(Registered);
+
+/// Returns every site registered by [`new_err!`] and [`propagate!`] in the
+/// program, in no particular order.
+pub fn trace_sites() -> impl Iterator<Item = &'static TraceSite> {
+    ::inventory::iter::<Registered>
+        .into_iter()
+        .map(|registered| registered.0)
+}
+
+/// Registers the site of the invocation, of the given kind, and returns a
+/// reference to it.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __register_site {
+    ($kind:ident) => {{
+        static SITE: $crate::sites::TraceSite = $crate::sites::TraceSite::new(
+            ::std::file!(),
+            ::std::line!(),
+            ::std::column!(),
+            $crate::sites::SiteKind::$kind,
+        );
+        $crate::sites::__inventory::submit! {
+            $crate::sites::Registered(&SITE)
+        }
+        &SITE
+    }};
+}
+
+/// Constructs a new traced error, like [`Result::new_err()`], and registers
+/// the site in the table of [`trace_sites()`].
+///
+/// See the [module documentation](crate::sites) for an example.
+///
+/// [`Result::new_err()`]: crate::Result::new_err
+/// [`trace_sites()`]: crate::sites::trace_sites
+#[macro_export]
+macro_rules! new_err {
+    ($error:expr $(,)?) => {{
+        $crate::__register_site!(Created).mark_exercised();
+        $crate::Result::new_err($error)
+    }};
+}
+
+/// Applies the `?` operator to a result, and registers the site in the table
+/// of [`trace_sites()`].
+///
+/// See the [module documentation](crate::sites) for an example.
+///
+/// [`trace_sites()`]: crate::sites::trace_sites
+#[macro_export]
+macro_rules! propagate {
+    ($result:expr $(,)?) => {{
+        let site = $crate::__register_site!(Propagated);
+        match $result {
+            result => {
+                if result.is_err() {
+                    site.mark_exercised();
+                }
+                result?
+            }
+        }
+    }};
+}
+
+#[doc(inline)]
+pub use crate::{new_err, propagate};
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Result;
+
+    fn never_fails() -> Result<(), &'static str> {
+        crate::propagate!(Result::<(), &str>::Ok(()));
+        if false {
+            return crate::new_err!("unreachable");
+        }
+        crate::Ok(())
+    }
+
+    #[test]
+    fn reports_unexercised_sites() {
+        assert!(never_fails().is_ok());
+
+        let unexercised: Vec<_> = trace_sites()
+            .filter(|site| site.file() == file!() && !site.exercised())
+            .map(TraceSite::kind)
+            .collect();
+        assert_eq!(unexercised.len(), 2);
+        assert!(unexercised.contains(&SiteKind::Propagated));
+        assert!(unexercised.contains(&SiteKind::Created));
+    }
+}
@@ -0,0 +1,184 @@
+//! Global counters of the errors in a program, by where they came from.
+//!
+//! Every traced error updates a process-wide table, which can be read with
+//! [`stats()`] (e.g. to list the top origins of errors on a debug endpoint)
+//! without installing an observer or a metrics stack:
+//!
+//! * [`created`][SiteStats::created] counts the traces started at a location.
+//! * [`propagated`][SiteStats::propagated] counts the locations pushed by the
+//!   `?` operator to the traces of errors created at a location. Only stacks
+//!   that keep their frames (see [`Traced::frames()`]) can be attributed to
+//!   their origin; the hops of other stacks are not counted.
+//! * [`handled`][SiteStats::handled] counts the errors handled at a location,
+//!   in the sense of [`EventKind::Handled`].
+//!
+//! # Examples
+//!
+//! ```
+//! fn load() -> propagate::Result<(), &'static str> {
+//!     propagate::Result::new_err("Nothing here")
+//! }
+//!
+//! fn run() -> propagate::Result<(), &'static str> {
+//!     load()?;
+//!     propagate::Ok(())
+//! }
+//!
+//! for _ in 0..3 {
+//!     run().ok();
+//! }
+//!
+//! let top = &propagate::stats()[0];
+//! assert_eq!(top.created(), 3);
+//! assert_eq!(top.propagated(), 3);
+//! println!("{} errors created at {}", top.created(), top.location());
+//! ```
+//!
+//! Available with the `stats` feature.
+//!
+//! [`Traced::frames()`]: crate::Traced::frames
+//! [`EventKind::Handled`]: crate::observe::EventKind::Handled
+
+use crate::observe::EventKind;
+use crate::trace::{CodeLocation, Traced};
+
+use std::collections::BTreeMap;
+use std::panic;
+use std::sync::{Mutex, MutexGuard};
+
+/// The counters of a single location in the program.
+///
+/// See the [module documentation](self) for what is counted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SiteStats {
+    location: CodeLocation,
+    created: u64,
+    propagated: u64,
+    handled: u64,
+}
+
+impl SiteStats {
+    /// Returns the location the counters belong to.
+    pub fn location(&self) -> &CodeLocation {
+        &self.location
+    }
+
+    /// Returns the number of traces started at the location.
+    pub const fn created(&self) -> u64 {
+        self.created
+    }
+
+    /// Returns the number of times errors created at the location were
+    /// propagated by the `?` operator.
+    pub const fn propagated(&self) -> u64 {
+        self.propagated
+    }
+
+    /// Returns the number of errors handled at the location.
+    pub const fn handled(&self) -> u64 {
+        self.handled
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    created: u64,
+    propagated: u64,
+    handled: u64,
+}
+
+static STATS: Mutex<BTreeMap<(&'static str, u32), Counters>> = Mutex::new(BTreeMap::new());
+
+/// Returns the counters of every location that has seen an error, with the
+/// locations that created the most errors first.
+pub fn stats() -> Vec<SiteStats> {
+    let mut stats: Vec<SiteStats> = lock()
+        .iter()
+        .map(|(&(file, line), counters)| SiteStats {
+            location: CodeLocation::new(file, line),
+            created: counters.created,
+            propagated: counters.propagated,
+            handled: counters.handled,
+        })
+        .collect();
+    stats.sort_by_key(|site| std::cmp::Reverse(site.created));
+    stats
+}
+
+/// Sets all counters back to zero.
+pub fn reset_stats() {
+    lock().clear();
+}
+
+/// Counts an event for an error whose stack is not available.
+#[cold]
+pub(crate) fn count(kind: EventKind, location: &'static panic::Location<'static>) {
+    if kind == EventKind::Handled {
+        update(location.file(), location.line(), |counters| {
+            counters.handled += 1
+        });
+    }
+}
+
+/// Counts an event for an error with the given stack.
+#[cold]
+pub(crate) fn count_traced<S: Traced + ?Sized>(
+    kind: EventKind,
+    location: &'static panic::Location<'static>,
+    stack: &S,
+) {
+    match kind {
+        EventKind::Created => update(location.file(), location.line(), |counters| {
+            counters.created += 1
+        }),
+        EventKind::Propagated => {
+            let origin = stack
+                .frames()
+                .and_then(|mut frames| frames.next())
+                .map(|frame| (frame.file(), frame.line()));
+            if let Some((file, line)) = origin {
+                update(file, line, |counters| counters.propagated += 1);
+            }
+        }
+        EventKind::Converted | EventKind::Handled => count(kind, location),
+    }
+}
+
+fn update<F: FnOnce(&mut Counters)>(file: &'static str, line: u32, f: F) {
+    f(lock().entry((file, line)).or_default());
+}
+
+fn lock() -> MutexGuard<'static, BTreeMap<(&'static str, u32), Counters>> {
+    STATS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Result;
+
+    fn site(location: &CodeLocation) -> SiteStats {
+        stats()
+            .into_iter()
+            .find(|site| site.location() == location)
+            .unwrap()
+    }
+
+    #[test]
+    fn counts_by_origin() {
+        let created_at = CodeLocation::here().down_by(1);
+        let inner = || -> Result<u32, &str> { Result::new_err("Nothing here") };
+        let outer = || -> Result<u32, &str> { crate::Ok(inner()? + 1) };
+        let outer = || -> Result<u32, &str> { crate::Ok(outer()? + 1) };
+
+        let handled_at = CodeLocation::here().down_by(2);
+        for _ in 0..2 {
+            assert_eq!(outer().unwrap_or(0), 0);
+        }
+
+        let origin = site(&created_at);
+        assert_eq!(origin.created(), 2);
+        assert_eq!(origin.propagated(), 4);
+        assert_eq!(site(&handled_at).handled(), 2);
+    }
+}
@@ -0,0 +1,165 @@
+//! Integration with [`tracing-subscriber`](https://docs.rs/tracing-subscriber).
+//!
+//! Traced errors are attached to `tracing` events using a field named
+//! [`TRACE_FIELD`], whose value is the error trace:
+//!
+//! ```
+//! # let result: propagate::Result<(), &str> = propagate::Result::new_err("Nothing here");
+//! if let propagate::Err(err, trace) = result {
+//!     tracing::error!(propagate.trace = %trace, "request failed: {}", err);
+//! }
+//! ```
+//!
+//! Formatting layers such as `tracing_subscriber::fmt` print the field like any
+//! other. Adding a [`TraceLayer`] additionally records the frames of the trace
+//! in the extensions of the span the event occurred in, so that other layers
+//! can find them as a [`RecordedTrace`].
+//!
+//! Available with the `tracing` feature.
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use std::fmt;
+
+/// The name of the event field that carries an error trace.
+pub const TRACE_FIELD: &str = "propagate.trace";
+
+/// A [`Layer`] that records error traces carried by events in the extensions
+/// of the current span.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::subscriber::TraceLayer;
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let subscriber = tracing_subscriber::registry().with(TraceLayer::new());
+/// tracing::subscriber::with_default(subscriber, || {
+///     // ...
+/// });
+/// ```
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TraceLayer {
+    _private: (),
+}
+
+impl TraceLayer {
+    /// Constructs a new layer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// The frames of the error traces recorded in a span by [`TraceLayer`], oldest
+/// frame first.
+///
+/// If several events in the same span carry a trace, the frames of the most
+/// recent one are kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecordedTrace(pub Vec<String>);
+
+impl<S> Layer<S> for TraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = TraceVisitor(None);
+        event.record(&mut visitor);
+        let frames = match visitor.0 {
+            Some(frames) => frames,
+            None => return,
+        };
+
+        if let Some(span) = ctx.event_span(event) {
+            let mut extensions = span.extensions_mut();
+            match extensions.get_mut::<RecordedTrace>() {
+                Some(recorded) => recorded.0 = frames,
+                None => extensions.insert(RecordedTrace(frames)),
+            }
+        }
+    }
+}
+
+/// Extracts the frames of the [`TRACE_FIELD`] field of an event.
+struct TraceVisitor(Option<Vec<String>>);
+
+impl TraceVisitor {
+    fn record_trace(&mut self, rendered: &str) {
+        let frames = rendered
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(strip_index)
+            .map(String::from)
+            .collect();
+        self.0 = Some(frames);
+    }
+}
+
+impl Visit for TraceVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TRACE_FIELD {
+            self.record_trace(value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == TRACE_FIELD {
+            self.record_trace(&format!("{:?}", value));
+        }
+    }
+}
+
+/// Removes the `N: ` prefix that the default trace formatter puts in front of
+/// each frame.
+fn strip_index(line: &str) -> &str {
+    match line.split_once(": ") {
+        Some((index, frame)) if index.bytes().all(|b| b.is_ascii_digit()) => frame,
+        _ => line,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Result;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Copies the recorded trace out of each span when it closes.
+    struct Collect(Arc<Mutex<Option<RecordedTrace>>>);
+
+    impl<S> Layer<S> for Collect
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_close(&self, id: tracing_core::span::Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            *self.0.lock().unwrap() = span.extensions().get::<RecordedTrace>().cloned();
+        }
+    }
+
+    #[test]
+    fn records_trace_in_event_span() {
+        let collected = Arc::new(Mutex::new(None));
+        let subscriber = tracing_subscriber::registry()
+            .with(TraceLayer::new())
+            .with(Collect(collected.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request");
+            let _guard = span.enter();
+
+            let result: Result<(), &str> = Result::new_err("Nothing here");
+            let (err, trace) = result.err_trace().unwrap();
+            tracing::error!(propagate.trace = %trace, "failed: {}", err);
+        });
+
+        let recorded = collected.lock().unwrap().take().unwrap();
+        assert_eq!(recorded.0.len(), 1);
+        assert!(recorded.0[0].starts_with("src/subscriber.rs:"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Helpers for acquiring locks with traced errors.
+//!
+//! The standard library reports a poisoned lock with a [`PoisonError`] that
+//! borrows the lock, which makes it awkward to propagate with `?`. The
+//! extension traits in this module acquire a lock and turn poisoning into a
+//! traced [`LockPoisoned`] error that starts at the location of the caller.
+
+use crate::result::Result::{self, Ok};
+
+use std::error::Error;
+use std::fmt;
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// The error returned when a lock could not be acquired because another thread
+/// panicked while holding it.
+///
+/// Any [`PoisonError`] can be converted into a `LockPoisoned`, which discards
+/// the guard it carries.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LockPoisoned {
+    _private: (),
+}
+
+impl LockPoisoned {
+    /// Constructs a new `LockPoisoned` error.
+    pub const fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+impl<G> From<PoisonError<G>> for LockPoisoned {
+    #[inline]
+    fn from(_: PoisonError<G>) -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for LockPoisoned {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("poisoned lock: another task failed inside")
+    }
+}
+
+impl Error for LockPoisoned {}
+
+/// Traced lock acquisition for [`Mutex`].
+pub trait TracedMutex<T: ?Sized> {
+    /// Acquires the mutex, blocking the current thread until it is able to do
+    /// so.
+    ///
+    /// If the mutex is poisoned, returns a [`LockPoisoned`] error whose trace
+    /// starts at the location of the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::sync::{LockPoisoned, TracedMutex};
+    /// use std::sync::Mutex;
+    ///
+    /// fn increment(counter: &Mutex<u32>) -> propagate::Result<u32, LockPoisoned> {
+    ///     let mut count = counter.lock_traced()?;
+    ///     *count += 1;
+    ///     propagate::Ok(*count)
+    /// }
+    ///
+    /// let counter = Mutex::new(0);
+    /// assert_eq!(increment(&counter).ok(), Some(1));
+    /// ```
+    #[track_caller]
+    fn lock_traced(&self) -> Result<MutexGuard<'_, T>, LockPoisoned>;
+}
+
+impl<T: ?Sized> TracedMutex<T> for Mutex<T> {
+    #[inline]
+    #[track_caller]
+    fn lock_traced(&self) -> Result<MutexGuard<'_, T>, LockPoisoned> {
+        match self.lock() {
+            std::result::Result::Ok(guard) => Ok(guard),
+            std::result::Result::Err(err) => Result::new_err(err),
+        }
+    }
+}
+
+/// Traced lock acquisition for [`RwLock`].
+pub trait TracedRwLock<T: ?Sized> {
+    /// Locks this rwlock with shared read access, blocking the current thread
+    /// until it can be acquired.
+    ///
+    /// If the lock is poisoned, returns a [`LockPoisoned`] error whose trace
+    /// starts at the location of the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::sync::TracedRwLock;
+    /// use std::sync::RwLock;
+    ///
+    /// let lock = RwLock::new(5);
+    /// assert_eq!(*lock.read_traced().unwrap(), 5);
+    /// ```
+    #[track_caller]
+    fn read_traced(&self) -> Result<RwLockReadGuard<'_, T>, LockPoisoned>;
+
+    /// Locks this rwlock with exclusive write access, blocking the current
+    /// thread until it can be acquired.
+    ///
+    /// If the lock is poisoned, returns a [`LockPoisoned`] error whose trace
+    /// starts at the location of the caller.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::sync::TracedRwLock;
+    /// use std::sync::RwLock;
+    ///
+    /// let lock = RwLock::new(5);
+    /// *lock.write_traced().unwrap() += 1;
+    /// assert_eq!(*lock.read_traced().unwrap(), 6);
+    /// ```
+    #[track_caller]
+    fn write_traced(&self) -> Result<RwLockWriteGuard<'_, T>, LockPoisoned>;
+}
+
+impl<T: ?Sized> TracedRwLock<T> for RwLock<T> {
+    #[inline]
+    #[track_caller]
+    fn read_traced(&self) -> Result<RwLockReadGuard<'_, T>, LockPoisoned> {
+        match self.read() {
+            std::result::Result::Ok(guard) => Ok(guard),
+            std::result::Result::Err(err) => Result::new_err(err),
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn write_traced(&self) -> Result<RwLockWriteGuard<'_, T>, LockPoisoned> {
+        match self.write() {
+            std::result::Result::Ok(guard) => Ok(guard),
+            std::result::Result::Err(err) => Result::new_err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CodeLocation;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn lock_traced_reports_poisoning() {
+        let mutex = Arc::new(Mutex::new(0));
+        let poisoner = mutex.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.lock().unwrap();
+            panic!("poisoning the mutex");
+        })
+        .join();
+
+        let here = CodeLocation::here().down_by(1);
+        let (err, trace) = mutex.lock_traced().err_trace().unwrap();
+        assert_eq!(err, LockPoisoned::new());
+        assert_eq!(trace.0, vec![here]);
+    }
+
+    #[test]
+    fn rwlock_traced_reports_poisoning() {
+        let lock = Arc::new(RwLock::new(0));
+        let poisoner = lock.clone();
+        let _ = thread::spawn(move || {
+            let _guard = poisoner.write().unwrap();
+            panic!("poisoning the rwlock");
+        })
+        .join();
+
+        let here = CodeLocation::here().down_by(1);
+        let (_, trace) = lock.write_traced().err_trace().unwrap();
+        assert_eq!(trace.0, vec![here]);
+        assert!(lock.read_traced().is_err());
+    }
+}
@@ -37,8 +37,9 @@ impl Fixture {
     }
 
     pub fn assert_stack_matches_tags(&self, stack: &ErrorTrace, tags: &[&'static str]) {
-        let tags_to_locations = ErrorTrace(tags.iter().map(|t| *self.get_location(t)).collect());
-        assert_eq!(stack, &tags_to_locations);
+        let tags_to_locations =
+            ErrorTrace(tags.iter().map(|t| self.get_location(t).clone()).collect());
+        crate::assert_trace_eq!(stack.0, tags_to_locations.0);
     }
 
     pub fn assert_result_has_stack<T: fmt::Debug, E: fmt::Debug>(
@@ -50,3 +51,31 @@ impl Fixture {
         self.assert_stack_matches_tags(&stack, tags);
     }
 }
+
+/// Runs a future to completion on the current thread.
+#[allow(dead_code)]
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::thread::{self, Thread};
+
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match Pin::as_mut(&mut future).poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
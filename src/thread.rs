@@ -0,0 +1,214 @@
+//! Helpers for threads that produce traced results.
+
+use crate::error::ErrorSet;
+use crate::result::Result::{self, Err, Ok};
+use crate::trace::Traced;
+
+use std::cell::RefCell;
+use std::panic;
+use std::thread::{self, ScopedJoinHandle, Thread};
+
+/// Creates a scope for spawning scoped threads that return traced results.
+///
+/// This is a wrapper around [`std::thread::scope`]. Threads are spawned with
+/// [`Scope::spawn()`], and all of them are joined before this function
+/// returns. The successful outputs are returned in the order that the threads
+/// were spawned.
+///
+/// If any of the threads fail, the output is an `Err` containing an
+/// [`ErrorSet`] of every failure. Each failed thread's trace has a boundary
+/// frame appended for the location of the call to `scope`, labeled with the
+/// identity of the thread. The trace of the aggregated error begins at the
+/// location of the call to `scope`.
+///
+/// # Panics
+///
+/// If any of the spawned threads panicked, the panic is propagated to the
+/// caller once all threads have been joined.
+///
+/// # Examples
+///
+/// ```
+/// let inputs = ["1", "2", "three"];
+///
+/// let result = propagate::thread::scope(|s| {
+///     for input in &inputs {
+///         s.spawn(move || -> propagate::Result<u32, std::num::ParseIntError> {
+///             propagate::Ok(input.parse()?)
+///         });
+///     }
+/// });
+///
+/// match result {
+///     propagate::Ok(_) => unreachable!(),
+///     propagate::Err(errors, trace) => {
+///         assert_eq!(errors.len(), 1);
+///         println!("{}", errors);
+///         println!("Return Trace: {}", trace);
+///     }
+/// }
+/// ```
+///
+/// Like the locations pushed by `?`, these frames are only recorded while
+/// capture is enabled (see [`capture_from_env()`]), and are reported to the
+/// installed [observer](crate::observe):
+///
+/// ```
+/// std::env::remove_var(propagate::trace::CAPTURE_VAR);
+/// assert!(!propagate::trace::capture_from_env());
+///
+/// let result = propagate::thread::scope(|s| {
+///     s.spawn(|| propagate::Result::<(), &str>::new_err("oops"));
+/// });
+///
+/// let (errors, trace) = result.err_trace().unwrap();
+/// assert!(trace.0.is_empty());
+/// assert!(errors.traces().all(|trace| trace.0.is_empty()));
+/// ```
+///
+/// [`capture_from_env()`]: crate::trace::capture_from_env
+#[track_caller]
+pub fn scope<'env, F, T, E, S>(f: F) -> Result<Vec<T>, ErrorSet<E, S>, S>
+where
+    F: for<'scope> FnOnce(&Scope<'scope, 'env, T, E, S>),
+    T: Send + 'env,
+    E: Send + 'env,
+    S: Traced + Default + Send + 'env,
+{
+    let location = panic::Location::caller();
+
+    let results = thread::scope(|s| {
+        let scope = Scope {
+            inner: s,
+            handles: RefCell::new(Vec::new()),
+        };
+        f(&scope);
+
+        scope
+            .handles
+            .into_inner()
+            .into_iter()
+            .map(|handle| {
+                let thread = handle.thread().clone();
+                match handle.join() {
+                    std::result::Result::Ok(result) => (thread, result),
+                    std::result::Result::Err(payload) => panic::resume_unwind(payload),
+                }
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut oks = Vec::with_capacity(results.len());
+    let mut errors = ErrorSet::new();
+    for (thread, result) in results {
+        match result {
+            Ok(t) => oks.push(t),
+            Err(err, mut trace) => {
+                crate::result::push_boundary::<E, S>(&mut trace, location, &thread_label(&thread));
+                errors.push(err, trace);
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(oks)
+    } else {
+        let trace = crate::result::new_trace::<ErrorSet<E, S>, S>(location);
+        Err(errors, trace)
+    }
+}
+
+/// A scope to spawn scoped threads in.
+///
+/// See [`scope`] for details.
+pub struct Scope<'scope, 'env: 'scope, T, E, S> {
+    inner: &'scope thread::Scope<'scope, 'env>,
+    handles: RefCell<Vec<ScopedJoinHandle<'scope, Result<T, E, S>>>>,
+}
+
+impl<'scope, 'env, T, E, S> Scope<'scope, 'env, T, E, S>
+where
+    T: Send + 'scope,
+    E: Send + 'scope,
+    S: Send + 'scope,
+{
+    /// Spawns a new thread within the scope.
+    ///
+    /// The result of the thread is collected when the enclosing [`scope`]
+    /// ends.
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() -> Result<T, E, S> + Send + 'scope,
+    {
+        let handle = self.inner.spawn(f);
+        self.handles.borrow_mut().push(handle);
+    }
+
+    /// Spawns a new named thread within the scope.
+    ///
+    /// The name is used to identify the thread in the error trace.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the OS fails to create a thread.
+    pub fn spawn_named<F>(&self, name: impl Into<String>, f: F)
+    where
+        F: FnOnce() -> Result<T, E, S> + Send + 'scope,
+    {
+        let handle = thread::Builder::new()
+            .name(name.into())
+            .spawn_scoped(self.inner, f)
+            .expect("failed to spawn thread");
+        self.handles.borrow_mut().push(handle);
+    }
+}
+
+fn thread_label(thread: &Thread) -> String {
+    match thread.name() {
+        Some(name) => format!("thread '{}'", name),
+        None => format!("thread {:?}", thread.id()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::Fixture;
+    use crate::{CodeLocation, ErrorTrace};
+
+    #[test]
+    fn scope_collects_successes_in_order() {
+        let result: Result<Vec<u32>, ErrorSet<String>> = scope(|s| {
+            for i in 0..4 {
+                s.spawn(move || Ok(i));
+            }
+        });
+        assert_eq!(result, Ok(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn scope_appends_boundary_to_each_error() {
+        let mut fix = Fixture::default();
+
+        fix.tag_location("scope", CodeLocation::here().down_by(1));
+        let result: Result<Vec<u32>, ErrorSet<String>> = scope(|s| {
+            s.spawn_named("first", || Result::new_err("first"));
+            s.spawn(|| Ok(1));
+            s.spawn_named("second", || Result::new_err("second"));
+        });
+
+        let (errors, trace) = result.err_trace().unwrap();
+        fix.assert_stack_matches_tags(&trace, &["scope"]);
+
+        let errors: Vec<(String, ErrorTrace)> = errors.into_iter().collect();
+        assert_eq!(errors.len(), 2);
+        for (err, trace) in errors {
+            let expected = fix
+                .get_location("scope")
+                .clone()
+                .labeled(format!("thread '{}'", err));
+            assert_eq!(trace.0.len(), 2);
+            assert_eq!(trace.0[1], expected);
+        }
+    }
+}
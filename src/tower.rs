@@ -0,0 +1,212 @@
+//! Recording service boundaries in [`tower`] middleware stacks.
+//!
+//! When a request passes through several layers of middleware, the trace of a
+//! failure only shows where the error was created and propagated inside each
+//! service, not which layers it went through. Wrapping a service with a
+//! [`BoundaryLayer`] adds a boundary frame (see [`Traced::trace_boundary()`])
+//! naming the layer to the trace of every [`TracedError`] the service returns.
+//!
+//! Available with the `tower` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::tower::BoundaryLayer;
+//! use propagate::{ErrorTrace, TracedError};
+//! use std::future::{ready, Ready};
+//! use std::task::{Context, Poll};
+//! use tower_layer::Layer;
+//! use tower_service::Service;
+//!
+//! struct Lookup;
+//!
+//! impl Service<u32> for Lookup {
+//!     type Response = String;
+//!     type Error = TracedError<String>;
+//!     type Future = Ready<Result<String, Self::Error>>;
+//!
+//!     fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+//!         Poll::Ready(Ok(()))
+//!     }
+//!
+//!     fn call(&mut self, id: u32) -> Self::Future {
+//!         ready(Err(TracedError::new(format!("no user {}", id), ErrorTrace::new())))
+//!     }
+//! }
+//!
+//! let service = BoundaryLayer::new("auth").layer(Lookup);
+//! # let _ = service;
+//! ```
+//!
+//! [`Traced::trace_boundary()`]: crate::Traced::trace_boundary
+
+use crate::error::TracedError;
+use crate::result::push_boundary;
+use crate::trace::Traced;
+
+use std::future::Future;
+use std::panic;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// A [`Layer`] that records a boundary frame in the trace of every error
+/// returned by the services it wraps.
+///
+/// The frame is located where the layer was constructed, and labeled with the
+/// name of the layer.
+#[derive(Clone, Debug)]
+pub struct BoundaryLayer {
+    label: Arc<str>,
+    location: &'static panic::Location<'static>,
+}
+
+impl BoundaryLayer {
+    /// Constructs a layer that labels boundary frames with `label`, located at
+    /// the caller.
+    #[inline]
+    #[track_caller]
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: Arc::from(label.into()),
+            location: panic::Location::caller(),
+        }
+    }
+
+    /// Returns the label of the boundary frames.
+    #[inline]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+}
+
+impl<Svc> Layer<Svc> for BoundaryLayer {
+    type Service = Boundary<Svc>;
+
+    fn layer(&self, inner: Svc) -> Self::Service {
+        Boundary {
+            inner,
+            label: self.label.clone(),
+            location: self.location,
+        }
+    }
+}
+
+/// A service that records a boundary frame in the trace of every error
+/// returned by the inner service.
+///
+/// This is produced by [`BoundaryLayer`].
+#[derive(Clone, Debug)]
+pub struct Boundary<Svc> {
+    inner: Svc,
+    label: Arc<str>,
+    location: &'static panic::Location<'static>,
+}
+
+impl<Svc> Boundary<Svc> {
+    /// Returns a reference to the inner service.
+    #[inline]
+    pub fn get_ref(&self) -> &Svc {
+        &self.inner
+    }
+
+    /// Returns the inner service, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> Svc {
+        self.inner
+    }
+}
+
+impl<Svc, Req, E, S> Service<Req> for Boundary<Svc>
+where
+    Svc: Service<Req, Error = TracedError<E, S>>,
+    S: Traced,
+{
+    type Response = Svc::Response;
+    type Error = TracedError<E, S>;
+    type Future = BoundaryFuture<Svc::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<std::result::Result<(), Self::Error>> {
+        let (label, location) = (&self.label, self.location);
+        self.inner
+            .poll_ready(cx)
+            .map_err(|err| err.modify_stack(|stack| push_boundary::<E, S>(stack, location, label)))
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        BoundaryFuture {
+            future: Box::pin(self.inner.call(request)),
+            label: self.label.clone(),
+            location: self.location,
+        }
+    }
+}
+
+/// Future for the [`Boundary`] service.
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct BoundaryFuture<F> {
+    future: Pin<Box<F>>,
+    label: Arc<str>,
+    location: &'static panic::Location<'static>,
+}
+
+impl<F, T, E, S> Future for BoundaryFuture<F>
+where
+    F: Future<Output = std::result::Result<T, TracedError<E, S>>>,
+    S: Traced,
+{
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let (label, location) = (self.label.clone(), self.location);
+        self.future.as_mut().poll(cx).map(|output| {
+            output.map_err(|err| {
+                err.modify_stack(|stack| push_boundary::<E, S>(stack, location, &label))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test::block_on;
+    use crate::{CodeLocation, ErrorTrace};
+    use std::future::{ready, Ready};
+
+    struct Fail;
+
+    impl Service<()> for Fail {
+        type Response = ();
+        type Error = TracedError<&'static str>;
+        type Future = Ready<std::result::Result<(), Self::Error>>;
+
+        fn poll_ready(
+            &mut self,
+            _: &mut Context<'_>,
+        ) -> Poll<std::result::Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            ready(Err(TracedError::new("Nothing here", ErrorTrace::default())))
+        }
+    }
+
+    #[test]
+    fn appends_boundary_frame_per_layer() {
+        let inner_at = CodeLocation::here().down_by(1);
+        let inner = BoundaryLayer::new("inner").layer(Fail);
+        let outer_at = CodeLocation::here().down_by(1);
+        let mut service = BoundaryLayer::new("outer").layer(inner);
+
+        let err = block_on(service.call(())).unwrap_err();
+        assert_eq!(
+            err.stack().0,
+            vec![inner_at.labeled("inner"), outer_at.labeled("outer")]
+        );
+    }
+}
@@ -1,12 +1,672 @@
 //! Defines types for error tracing.
 
+use crate::diff::TraceChanges;
+
+use std::alloc::{self, AllocError, Allocator, Global, Layout};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::fmt;
+use std::iter::FromIterator;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops;
 use std::panic;
+use std::ptr::{self, NonNull};
+use std::slice::SliceIndex;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
 /// A trait denoting "stack-like" types that can be used with
 /// [`Result<T, E, S>`][crate::Result].
 pub trait Traced {
     fn trace(&mut self, location: &'static panic::Location);
+
+    /// Records that the error crossed a boundary (e.g., passed between
+    /// threads) at `location`, where `label` identifies the other side of the
+    /// boundary.
+    ///
+    /// The default implementation ignores the label and calls
+    /// [`trace()`][Traced::trace].
+    fn trace_boundary(&mut self, location: &'static panic::Location, label: &str) {
+        let _ = label;
+        self.trace(location);
+    }
+
+    /// Records `context` describing what was being done at `location` when
+    /// the error passed through it (see [`Result::context()`]).
+    ///
+    /// The default implementation ignores the context and calls
+    /// [`trace()`][Traced::trace].
+    ///
+    /// [`Result::context()`]: crate::Result::context
+    fn trace_context(&mut self, location: &'static panic::Location, context: &str) {
+        let _ = context;
+        self.trace(location);
+    }
+
+    /// Returns an identifier that is unique to the error this stack belongs
+    /// to, used to correlate lifecycle events (see [`observe`]).
+    ///
+    /// The default implementation returns `None`. A stack type that assigns
+    /// itself an identifier when it is created can return it here:
+    ///
+    /// ```
+    /// use propagate::Traced;
+    /// use std::panic;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    ///
+    /// static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+    ///
+    /// struct IdStack {
+    ///     id: u64,
+    ///     depth: usize,
+    /// }
+    ///
+    /// impl Default for IdStack {
+    ///     fn default() -> Self {
+    ///         let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    ///         Self { id, depth: 0 }
+    ///     }
+    /// }
+    ///
+    /// impl Traced for IdStack {
+    ///     fn trace(&mut self, _: &'static panic::Location) {
+    ///         self.depth += 1;
+    ///     }
+    ///
+    ///     fn correlation_id(&self) -> Option<u64> {
+    ///         Some(self.id)
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// [`observe`]: crate::observe
+    fn correlation_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns when the error this stack belongs to was created, if known.
+    ///
+    /// The default implementation returns `None`. See [`TimedStack`] for a
+    /// stack that records it.
+    fn created_at(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Returns an iterator over the frames of the stack, oldest first, or
+    /// `None` if the stack does not keep its frames.
+    ///
+    /// This lets generic reporting and exporting code walk any stack type.
+    /// The default implementation returns `None`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use propagate::trace::{LocationStack, Traced};
+    ///
+    /// fn print_frames<S: Traced>(stack: &S) {
+    ///     for frame in stack.frames().into_iter().flatten() {
+    ///         println!("{}", frame);
+    ///     }
+    /// }
+    ///
+    /// print_frames(&LocationStack::new());
+    /// ```
+    fn frames(&self) -> Option<Frames<'_>> {
+        None
+    }
+}
+
+/// An iterator over the frames of a stack (see [`Traced::frames()`]).
+///
+/// Frames are borrowed from stacks that store [`CodeLocation`]s, and
+/// converted from stacks that store other location types.
+pub type Frames<'a> = Box<dyn Iterator<Item = Cow<'a, CodeLocation>> + 'a>;
+
+/// A [`Traced`] stack that can be extended with the frames of another stack.
+///
+/// This makes joining two segments of a trace a supported operation, e.g.
+/// when an error is resumed in another process, or when the trace of a cause
+/// is spliced into the trace of the error that wraps it.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::{Append, CodeLocation, ErrorTrace};
+///
+/// let mut trace = ErrorTrace(vec![CodeLocation::new("src/a.rs", 1)]);
+/// trace.append(ErrorTrace(vec![CodeLocation::new("src/b.rs", 2)]));
+/// assert_eq!(
+///     trace.0,
+///     vec![CodeLocation::new("src/a.rs", 1), CodeLocation::new("src/b.rs", 2)]
+/// );
+/// ```
+pub trait Append: Traced {
+    /// Adds the frames of `other` after the frames of `self`, as if the error
+    /// had propagated through them after the locations already in `self`.
+    fn append(&mut self, other: Self)
+    where
+        Self: Sized;
+}
+
+/// The environment variable that controls trace capture once
+/// [`capture_from_env()`] has been called.
+pub const CAPTURE_VAR: &str = "PROPAGATE_CAPTURE";
+
+const CAPTURE_ALWAYS: u8 = 0;
+const CAPTURE_ENABLED: u8 = 1;
+const CAPTURE_DISABLED: u8 = 2;
+
+static CAPTURE: AtomicU8 = AtomicU8::new(CAPTURE_ALWAYS);
+
+/// Makes trace capture conditional on the [`PROPAGATE_CAPTURE`][CAPTURE_VAR]
+/// environment variable, like `RUST_BACKTRACE` does for backtraces.
+///
+/// By default, locations are always recorded. After this is called (typically
+/// at the start of `main`), they are only recorded if the variable is set to
+/// `1`, `true` or `full`; otherwise, errors are created with empty traces and
+/// the `?` operator does not add to them. The variable is read once, when
+/// this function is called, and the result is returned.
+///
+/// This lets the same binary run with tracing disabled by default, and enabled
+/// when debugging an incident.
+///
+/// # Examples
+///
+/// ```
+/// if propagate::trace::capture_from_env() {
+///     eprintln!("error traces enabled");
+/// }
+/// ```
+pub fn capture_from_env() -> bool {
+    let state = if env_capture_enabled() {
+        CAPTURE_ENABLED
+    } else {
+        CAPTURE_DISABLED
+    };
+    CAPTURE.store(state, Ordering::Relaxed);
+    capture_enabled()
+}
+
+/// Returns `true` if [`CAPTURE_VAR`] enables capture.
+fn env_capture_enabled() -> bool {
+    std::env::var(CAPTURE_VAR)
+        .map(|value| matches!(value.trim(), "1" | "true" | "full"))
+        .unwrap_or(false)
+}
+
+/// Returns `true` if locations are currently being recorded in traces.
+///
+/// With the `debug-only-trace` feature, this is always `false` in builds
+/// without `debug_assertions` (e.g. the `release` profile), so that the `?`
+/// operator compiles to the same code as for std results. The API is the same
+/// either way, so libraries can depend on propagate unconditionally and leave
+/// the choice to the application.
+///
+/// See [`capture_from_env()`].
+#[inline]
+pub fn capture_enabled() -> bool {
+    if cfg!(all(feature = "debug-only-trace", not(debug_assertions))) {
+        return false;
+    }
+    CAPTURE.load(Ordering::Relaxed) != CAPTURE_DISABLED
+}
+
+/// A function that decides which [`CodeLocation`] is recorded for a location
+/// the error passed through.
+pub type LocationProvider = fn(&'static panic::Location<'static>) -> CodeLocation;
+
+thread_local! {
+    static LOCATION_PROVIDER: Cell<Option<LocationProvider>> = const { Cell::new(None) };
+}
+
+/// Installs `provider` to decide which locations are recorded in traces on the
+/// current thread, replacing any previously installed provider.
+///
+/// This is meant for tests that assert on traces: instead of computing the
+/// expected line numbers with [`CodeLocation::here()`], which breaks whenever
+/// a line is inserted above, a test can record mock locations, or normalize
+/// them with [`file_only()`]. The provider is per-thread so that tests running
+/// in parallel do not affect each other.
+///
+/// It applies to the stacks that store [`CodeLocation`]s ([`ErrorTrace`] and
+/// [`CowStack`]); other stacks keep recording the real locations.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::trace::{self, CodeLocation};
+///
+/// fn inner() -> propagate::Result<(), &'static str> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// fn outer() -> propagate::Result<(), &'static str> {
+///     propagate::Ok(inner()?)
+/// }
+///
+/// trace::set_location_provider(trace::file_only);
+/// let (_, trace) = outer().err_trace().unwrap();
+/// trace::clear_location_provider();
+///
+/// let here = CodeLocation::new(file!(), 0);
+/// assert_eq!(trace.0, vec![here.clone(), here]);
+/// ```
+pub fn set_location_provider(provider: LocationProvider) {
+    LOCATION_PROVIDER.with(|cell| cell.set(Some(provider)));
+}
+
+/// Removes the location provider installed on the current thread, if any, so
+/// that the real locations are recorded again.
+pub fn clear_location_provider() {
+    LOCATION_PROVIDER.with(|cell| cell.set(None));
+}
+
+/// A [`LocationProvider`] that only records the file of each location, with
+/// its line number set to 0.
+pub fn file_only(location: &'static panic::Location<'static>) -> CodeLocation {
+    CodeLocation::new(location.file(), 0)
+}
+
+/// Returns the location to record for `location`, using the provider installed
+/// on the current thread, if any.
+#[inline]
+fn record_location(location: &'static panic::Location<'static>) -> CodeLocation {
+    match LOCATION_PROVIDER.try_with(Cell::get).ok().flatten() {
+        Some(provider) => provider(location),
+        None => CodeLocation::from(location),
+    }
+}
+
+/// The context message of the origin frame of a trace that was throttled by
+/// [`throttle_sites()`].
+pub const THROTTLED_NOTE: &str = "trace throttled";
+
+struct SiteThrottle {
+    limit: u32,
+    window: Duration,
+    sites: HashMap<(&'static str, u32, u32), (Instant, u32)>,
+}
+
+static THROTTLE_ENABLED: AtomicBool = AtomicBool::new(false);
+static THROTTLE: Mutex<Option<SiteThrottle>> = Mutex::new(None);
+
+/// Limits full trace capture to the first `limit` errors created at each call
+/// site per `window`.
+///
+/// Once a call site has created `limit` errors in the current window, further
+/// errors created there only record their origin, as a context frame with the
+/// message [`THROTTLED_NOTE`], and the `?` operator does not add to their
+/// traces until the window of the site is over. This keeps a single hot
+/// failing call site from spending memory and time on traces that all look
+/// the same, while errors from every other site are still traced in full. It
+/// applies on top of [`capture_from_env()`]: nothing is recorded while capture
+/// is disabled.
+///
+/// Stacks that do not record context messages (e.g. [`LocationStack`]) still
+/// only record the origin of a throttled error, but keep recording the
+/// locations it is propagated through.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::trace::{self, THROTTLED_NOTE};
+/// use std::time::Duration;
+///
+/// fn fails() -> propagate::Result<(), &'static str> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// fn propagates() -> propagate::Result<(), &'static str> {
+///     propagate::Ok(fails()?)
+/// }
+///
+/// trace::throttle_sites(2, Duration::from_secs(60));
+/// let traces: Vec<_> = (0..3)
+///     .map(|_| propagates().err_trace().unwrap().1)
+///     .collect();
+/// trace::clear_site_throttle();
+///
+/// assert_eq!(traces[1].0.len(), 2);
+/// assert_eq!(traces[2].0.len(), 1);
+/// assert_eq!(traces[2].0[0].message(), Some(THROTTLED_NOTE));
+/// ```
+pub fn throttle_sites(limit: u32, window: Duration) {
+    *THROTTLE.lock().unwrap_or_else(|e| e.into_inner()) = Some(SiteThrottle {
+        limit,
+        window,
+        sites: HashMap::new(),
+    });
+    THROTTLE_ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Removes the throttle installed by [`throttle_sites()`], if any, so that
+/// every error is traced in full again.
+pub fn clear_site_throttle() {
+    THROTTLE_ENABLED.store(false, Ordering::Relaxed);
+    *THROTTLE.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Counts an error created at `location`, and returns `true` if its trace
+/// should only record its origin.
+pub(crate) fn throttle_created(location: &'static panic::Location<'static>) -> bool {
+    if !THROTTLE_ENABLED.load(Ordering::Relaxed) {
+        return false;
+    }
+    let mut throttle = THROTTLE.lock().unwrap_or_else(|e| e.into_inner());
+    let throttle = match throttle.as_mut() {
+        Some(throttle) => throttle,
+        None => return false,
+    };
+
+    let now = Instant::now();
+    let key = (location.file(), location.line(), location.column());
+    let (start, count) = throttle.sites.entry(key).or_insert((now, 0));
+    if now.duration_since(*start) >= throttle.window {
+        *start = now;
+        *count = 0;
+    }
+    *count = count.saturating_add(1);
+    *count > throttle.limit
+}
+
+/// The context message of the origin frame of a trace that was not sampled
+/// (see [`TraceConfig::with_sample_rate()`]).
+pub const UNSAMPLED_NOTE: &str = "trace not sampled";
+
+/// A function that decides whether a location is recorded in traces.
+pub type CaptureFilter = fn(&'static panic::Location<'static>) -> bool;
+
+/// Options controlling how traces are captured, in one place.
+///
+/// A configuration is built at startup and installed with
+/// [`set_trace_config()`]. The defaults record every location of every
+/// error, as when no configuration is installed:
+///
+/// * [`with_enabled()`][TraceConfig::with_enabled] turns capture on or off,
+///   like [`capture_from_env()`] (see also [`from_env()`][TraceConfig::from_env]).
+/// * [`with_max_depth()`][TraceConfig::with_max_depth] caps the number of
+///   frames an [`ErrorTrace`] keeps; the frames closest to the origin are
+///   kept.
+/// * [`with_sample_rate()`][TraceConfig::with_sample_rate] records the full
+///   trace of only a fraction of errors. The others only record their origin,
+///   as a context frame with the message [`UNSAMPLED_NOTE`], so that they can
+///   still be told apart.
+/// * [`with_filter()`][TraceConfig::with_filter] skips the locations for
+///   which a [`CaptureFilter`] returns `false`, e.g. those in generated code.
+/// * [`with_timestamps()`][TraceConfig::with_timestamps] makes the default
+///   stacks of [`DynResult`] errors [`TimedStack`]s, which record when the
+///   error was created.
+///
+/// Per-site throttling is configured separately, with [`throttle_sites()`].
+///
+/// # Examples
+///
+/// ```
+/// use propagate::trace::{self, TraceConfig};
+///
+/// fn fails() -> propagate::Result<(), &'static str> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// fn propagates() -> propagate::Result<(), &'static str> {
+///     propagate::Ok(fails()?)
+/// }
+///
+/// fn twice() -> propagate::Result<(), &'static str> {
+///     propagate::Ok(propagates()?)
+/// }
+///
+/// trace::set_trace_config(TraceConfig::new().with_max_depth(Some(2)));
+/// let (_, trace) = twice().err_trace().unwrap();
+/// trace::reset_trace_config();
+///
+/// assert_eq!(trace.0.len(), 2);
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct TraceConfig {
+    enabled: bool,
+    max_depth: Option<usize>,
+    sample_rate: f64,
+    filter: Option<CaptureFilter>,
+    timestamps: bool,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TraceConfig {
+    /// Constructs a configuration that records every location of every error.
+    pub const fn new() -> Self {
+        Self {
+            enabled: true,
+            max_depth: None,
+            sample_rate: 1.0,
+            filter: None,
+            timestamps: false,
+        }
+    }
+
+    /// Constructs the default configuration, with capture enabled only if the
+    /// [`PROPAGATE_CAPTURE`][CAPTURE_VAR] environment variable is set, as
+    /// described in [`capture_from_env()`].
+    pub fn from_env() -> Self {
+        Self::new().with_enabled(env_capture_enabled())
+    }
+
+    /// Sets whether locations are recorded at all.
+    pub const fn with_enabled(self, enabled: bool) -> Self {
+        Self { enabled, ..self }
+    }
+
+    /// Sets the maximum number of frames an [`ErrorTrace`] keeps, or `None`
+    /// to keep every frame.
+    pub const fn with_max_depth(self, max_depth: Option<usize>) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Sets the fraction of errors, between 0 and 1, whose full trace is
+    /// recorded.
+    ///
+    /// The sampled errors are evenly spaced: with a rate of `0.25`, every
+    /// fourth error is sampled. Values outside of the range are clamped to it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::trace::{self, TraceConfig, UNSAMPLED_NOTE};
+    ///
+    /// fn fails() -> propagate::Result<(), &'static str> {
+    ///     propagate::Result::new_err("Nothing here")
+    /// }
+    ///
+    /// trace::set_trace_config(TraceConfig::new().with_sample_rate(0.0));
+    /// let (_, trace) = fails().err_trace().unwrap();
+    /// trace::reset_trace_config();
+    ///
+    /// assert_eq!(trace.0[0].message(), Some(UNSAMPLED_NOTE));
+    /// ```
+    pub fn with_sample_rate(self, sample_rate: f64) -> Self {
+        Self {
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    /// Sets the filter deciding which locations are recorded, or `None` to
+    /// record every location.
+    pub const fn with_filter(self, filter: Option<CaptureFilter>) -> Self {
+        Self { filter, ..self }
+    }
+
+    /// Sets whether the default stacks of [`DynResult`] errors record when
+    /// the error was created.
+    pub const fn with_timestamps(self, timestamps: bool) -> Self {
+        Self { timestamps, ..self }
+    }
+
+    /// Returns `true` if locations are recorded.
+    pub const fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns the maximum number of frames an [`ErrorTrace`] keeps, if
+    /// limited.
+    pub const fn max_depth(&self) -> Option<usize> {
+        self.max_depth
+    }
+
+    /// Returns the fraction of errors whose full trace is recorded.
+    pub const fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Returns the filter deciding which locations are recorded, if any.
+    pub const fn filter(&self) -> Option<CaptureFilter> {
+        self.filter
+    }
+
+    /// Returns `true` if the default stacks of [`DynResult`] errors record
+    /// when the error was created.
+    pub const fn has_timestamps(&self) -> bool {
+        self.timestamps
+    }
+
+    fn records(&self, location: &'static panic::Location<'static>) -> bool {
+        self.filter.is_none_or(|filter| filter(location))
+    }
+
+    fn sampled(&self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        let count = SAMPLE_COUNT.fetch_add(1, Ordering::Relaxed) as f64;
+        ((count + 1.0) * self.sample_rate).floor() > (count * self.sample_rate).floor()
+    }
+}
+
+static CONFIGURED: AtomicBool = AtomicBool::new(false);
+static TRACE_CONFIG: RwLock<TraceConfig> = RwLock::new(TraceConfig::new());
+static SAMPLE_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Installs `config` to control how traces are captured, replacing any
+/// previously installed configuration.
+///
+/// This also replaces the setting of [`capture_from_env()`].
+pub fn set_trace_config(config: TraceConfig) {
+    *TRACE_CONFIG.write().unwrap_or_else(|e| e.into_inner()) = config;
+    let state = if config.enabled {
+        CAPTURE_ENABLED
+    } else {
+        CAPTURE_DISABLED
+    };
+    CAPTURE.store(state, Ordering::Relaxed);
+    CONFIGURED.store(true, Ordering::Release);
+}
+
+/// Restores the default configuration, which records every location of every
+/// error.
+pub fn reset_trace_config() {
+    CONFIGURED.store(false, Ordering::Release);
+    CAPTURE.store(CAPTURE_ALWAYS, Ordering::Relaxed);
+    *TRACE_CONFIG.write().unwrap_or_else(|e| e.into_inner()) = TraceConfig::new();
+}
+
+/// Returns the configuration currently in effect.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::trace::{self, TraceConfig};
+///
+/// trace::set_trace_config(TraceConfig::new().with_max_depth(Some(16)));
+/// assert_eq!(trace::trace_config().max_depth(), Some(16));
+///
+/// trace::reset_trace_config();
+/// assert_eq!(trace::trace_config().max_depth(), None);
+/// ```
+pub fn trace_config() -> TraceConfig {
+    let config = *TRACE_CONFIG.read().unwrap_or_else(|e| e.into_inner());
+    config.with_enabled(capture_enabled())
+}
+
+/// Returns the installed configuration, or `None` if the defaults apply.
+#[inline]
+fn installed_config() -> Option<TraceConfig> {
+    if CONFIGURED.load(Ordering::Acquire) {
+        Some(*TRACE_CONFIG.read().unwrap_or_else(|e| e.into_inner()))
+    } else {
+        None
+    }
+}
+
+/// How much of the trace of a new error is recorded.
+pub(crate) enum Capture {
+    /// Every location is recorded.
+    Full,
+    /// Only the origin is recorded, as a context frame with the given note.
+    Origin(&'static str),
+    /// Nothing is recorded at the origin.
+    Nothing,
+}
+
+/// Decides how much of the trace of an error created at `location` is
+/// recorded.
+pub(crate) fn capture_created(location: &'static panic::Location<'static>) -> Capture {
+    if !capture_enabled() {
+        return Capture::Nothing;
+    }
+    if let Some(config) = installed_config() {
+        if !config.records(location) {
+            return Capture::Nothing;
+        }
+        if !config.sampled() {
+            return Capture::Origin(UNSAMPLED_NOTE);
+        }
+    }
+    if throttle_created(location) {
+        return Capture::Origin(THROTTLED_NOTE);
+    }
+    Capture::Full
+}
+
+/// Returns `true` if `location` should be pushed to `stack` when the error is
+/// propagated through it.
+pub(crate) fn capture_propagated<S: Traced + ?Sized>(
+    stack: &S,
+    location: &'static panic::Location<'static>,
+) -> bool {
+    if !capture_enabled() {
+        return false;
+    }
+    let config = installed_config();
+    if config.is_some_and(|config| !config.records(location)) {
+        return false;
+    }
+    !((config.is_some() || THROTTLE_ENABLED.load(Ordering::Relaxed)) && is_origin_only(stack))
+}
+
+/// Returns `true` if `stack` was started with [`Capture::Origin`], so that
+/// the `?` operator should not add to it.
+fn is_origin_only<S: Traced + ?Sized>(stack: &S) -> bool {
+    stack
+        .frames()
+        .and_then(|mut frames| frames.next())
+        .is_some_and(|origin| matches!(origin.message(), Some(THROTTLED_NOTE | UNSAMPLED_NOTE)))
+}
+
+/// Returns `true` if a trace of `len` frames can take another frame under the
+/// installed configuration.
+#[inline]
+fn has_room(len: usize) -> bool {
+    installed_config()
+        .and_then(|config| config.max_depth)
+        .is_none_or(|max_depth| len < max_depth)
 }
 
 /*   ____          _      _                    _   _
@@ -17,16 +677,55 @@ pub trait Traced {
  *  FIGLET: CodeLocation
  */
 
+/// What a frame of an error trace records about its location.
+///
+/// Messages and labels that are `&'static str` to begin with are borrowed;
+/// any other text is owned by the frame, so it is freed along with the trace.
+///
+/// # Example
+///
+/// ```
+/// # use propagate::trace::*;
+/// let frame = CodeLocation::new("foo.rs", 1).with_message(format!("item {}", 3));
+/// assert_eq!(frame.kind(), &FrameKind::Message("item 3".into()));
+/// assert_eq!(frame.clone(), frame);
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum FrameKind {
+    /// The error was created or propagated through the location.
+    Location,
+    /// Describes what was being done at the location when the error passed
+    /// through it (see [`Traced::trace_context()`]).
+    Message(Cow<'static, str>),
+    /// The error crossed a boundary at the location, such as being passed
+    /// between threads (see [`Traced::trace_boundary()`]).
+    Boundary {
+        /// Identifies the other side of the boundary.
+        label: Cow<'static, str>,
+    },
+}
+
 /// Represents a location (filename, line number) in the source code.
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+///
+/// Each location is one frame of an [`ErrorTrace`], and has a [`FrameKind`]
+/// saying whether the error was simply propagated there, whether a message
+/// was attached there, or whether the error crossed a boundary there. This
+/// lets context messages and boundaries appear in order with the rest of the
+/// trace.
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CodeLocation {
     file: &'static str,
     line: u32,
+    kind: FrameKind,
 }
 
 impl CodeLocation {
-    pub fn new(file: &'static str, line: u32) -> Self {
-        Self { file, line }
+    pub const fn new(file: &'static str, line: u32) -> Self {
+        Self {
+            file,
+            line,
+            kind: FrameKind::Location,
+        }
     }
 
     /// Returns the code location at the site of the caller.
@@ -58,24 +757,118 @@ impl CodeLocation {
     /// ```
     pub fn down_by(self, lines: u32) -> Self {
         Self {
-            file: self.file,
             line: self.line + lines,
+            ..self
+        }
+    }
+
+    /// Returns `self` as a [`Boundary`][FrameKind::Boundary] frame with the
+    /// given label, consuming `self`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let loc = CodeLocation::new("foo.rs", 1).labeled("thread 'worker'");
+    /// assert_eq!(loc.label(), Some("thread 'worker'"));
+    /// assert_eq!(format!("{}", &loc), "foo.rs:1 (thread 'worker')");
+    /// ```
+    pub fn labeled(self, label: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            kind: FrameKind::Boundary {
+                label: label.into(),
+            },
+            ..self
+        }
+    }
+
+    /// Returns `self` as a [`Message`][FrameKind::Message] frame with the
+    /// given message, consuming `self`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let loc = CodeLocation::new("foo.rs", 1).with_message("loading config");
+    /// assert_eq!(loc.message(), Some("loading config"));
+    /// assert_eq!(format!("{}", &loc), "foo.rs:1: loading config");
+    /// ```
+    pub fn with_message(self, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            kind: FrameKind::Message(message.into()),
+            ..self
+        }
+    }
+
+    /// Returns the name of the source file.
+    pub const fn file(&self) -> &'static str {
+        self.file
+    }
+
+    /// Returns the line number in the source file.
+    pub const fn line(&self) -> u32 {
+        self.line
+    }
+
+    /// Returns what this frame records about its location.
+    pub const fn kind(&self) -> &FrameKind {
+        &self.kind
+    }
+
+    /// Returns the label of the boundary crossed at this location, if any.
+    pub fn label(&self) -> Option<&str> {
+        match &self.kind {
+            FrameKind::Boundary { label } => Some(label),
+            _ => None,
+        }
+    }
+
+    /// Returns the message attached to this location, if any.
+    pub fn message(&self) -> Option<&str> {
+        match &self.kind {
+            FrameKind::Message(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Returns `self` as a boundary frame labeled with a copy of `label`.
+    fn with_boundary(self, label: &str) -> Self {
+        Self {
+            kind: FrameKind::Boundary {
+                label: Cow::Owned(label.to_owned()),
+            },
+            ..self
+        }
+    }
+
+    /// Returns `self` as a message frame with a copy of `message`.
+    fn with_context(self, message: &str) -> Self {
+        Self {
+            kind: FrameKind::Message(Cow::Owned(message.to_owned())),
+            ..self
         }
     }
 }
 
 impl From<&'static panic::Location<'static>> for CodeLocation {
     fn from(loc: &'static panic::Location<'static>) -> Self {
-        CodeLocation {
-            file: loc.file(),
-            line: loc.line(),
-        }
+        CodeLocation::new(loc.file(), loc.line())
     }
 }
 
 impl fmt::Display for CodeLocation {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(formatter, "{}:{}", self.file, self.line)
+        write!(
+            formatter,
+            "{}:{}",
+            crate::fmt::remap_path(self.file),
+            self.line
+        )?;
+        match &self.kind {
+            FrameKind::Location => Ok(()),
+            FrameKind::Message(message) => write!(formatter, ": {}", message),
+            FrameKind::Boundary { label } => write!(formatter, " ({})", label),
+        }
     }
 }
 
@@ -95,7 +888,32 @@ pub struct ErrorTrace(pub Vec<CodeLocation>);
 
 impl Traced for ErrorTrace {
     fn trace(&mut self, location: &'static panic::Location) {
-        self.0.push(location.into());
+        if has_room(self.0.len()) {
+            self.0.push(record_location(location));
+        }
+    }
+
+    fn trace_boundary(&mut self, location: &'static panic::Location, label: &str) {
+        if has_room(self.0.len()) {
+            self.0.push(record_location(location).with_boundary(label));
+        }
+    }
+
+    fn trace_context(&mut self, location: &'static panic::Location, context: &str) {
+        if has_room(self.0.len()) {
+            self.0.push(record_location(location).with_context(context));
+        }
+    }
+
+    fn frames(&self) -> Option<Frames<'_>> {
+        Some(Box::new(self.0.iter().map(Cow::Borrowed)))
+    }
+}
+
+impl Append for ErrorTrace {
+    #[inline]
+    fn append(&mut self, mut other: Self) {
+        self.0.append(&mut other.0);
     }
 }
 
@@ -104,21 +922,1523 @@ impl ErrorTrace {
     #[inline]
     #[track_caller]
     pub fn new() -> Self {
-        let caller = CodeLocation::from(panic::Location::caller());
+        let caller = record_location(panic::Location::caller());
         Self(vec![caller])
     }
 
     pub fn to_strings(&self) -> Vec<String> {
         self.0.iter().map(|loc| format!("{}", loc)).collect()
     }
-}
 
-impl fmt::Display for ErrorTrace {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (index, location) in self.0.iter().enumerate() {
-            write!(f, "\n   {}: {}", index, location)?;
-        }
+    /// Returns the location where the error was created (the bottom of the
+    /// stack), or `None` if the trace is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let trace = ErrorTrace(vec![CodeLocation::new("foo.rs", 1), CodeLocation::new("bar.rs", 2)]);
+    /// assert_eq!(trace.origin(), Some(&CodeLocation::new("foo.rs", 1)));
+    /// ```
+    #[inline]
+    pub fn origin(&self) -> Option<&CodeLocation> {
+        self.0.first()
+    }
+
+    /// Returns the location the error most recently propagated through (the
+    /// top of the stack), or `None` if the trace is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let trace = ErrorTrace(vec![CodeLocation::new("foo.rs", 1), CodeLocation::new("bar.rs", 2)]);
+    /// assert_eq!(trace.latest(), Some(&CodeLocation::new("bar.rs", 2)));
+    /// ```
+    #[inline]
+    pub fn latest(&self) -> Option<&CodeLocation> {
+        self.0.last()
+    }
+
+    /// Returns the number of locations in the trace.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let trace = ErrorTrace(vec![CodeLocation::new("foo.rs", 1), CodeLocation::new("bar.rs", 2)]);
+    /// assert_eq!(trace.depth(), 2);
+    /// ```
+    #[inline]
+    pub fn depth(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if any location in the trace is in the file `file`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let trace = ErrorTrace(vec![CodeLocation::new("src/foo.rs", 1)]);
+    /// assert!(trace.contains_file("src/foo.rs"));
+    /// assert!(!trace.contains_file("src/bar.rs"));
+    /// ```
+    #[inline]
+    pub fn contains_file(&self, file: &str) -> bool {
+        self.any(|location| location.file() == file)
+    }
 
-        Ok(())
+    /// Returns `true` if any location in the trace matches the predicate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let trace = ErrorTrace(vec![CodeLocation::new("src/parser.rs", 88)]);
+    /// assert!(trace.any(|location| location.file().ends_with("parser.rs")));
+    /// ```
+    #[inline]
+    pub fn any<P: FnMut(&CodeLocation) -> bool>(&self, predicate: P) -> bool {
+        self.0.iter().any(predicate)
     }
+
+    /// Returns a stable 64-bit fingerprint of the propagation path (the origin
+    /// and every subsequent location).
+    ///
+    /// The fingerprint only depends on the file names and line numbers of the
+    /// locations (not their labels), so it can be used to group occurrences of
+    /// the same failure across runs and releases without comparing formatted
+    /// traces. See also [`HashingStack`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let a = ErrorTrace(vec![CodeLocation::new("foo.rs", 1), CodeLocation::new("bar.rs", 2)]);
+    /// let b = ErrorTrace(vec![CodeLocation::new("foo.rs", 1), CodeLocation::new("bar.rs", 3)]);
+    /// assert_eq!(a.fingerprint(), a.fingerprint());
+    /// assert_ne!(a.fingerprint(), b.fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> u64 {
+        self.0.iter().fold(FNV_OFFSET_BASIS, |hash, location| {
+            fold_fingerprint(hash, location.file, location.line)
+        })
+    }
+
+    /// Compares `self` with a newer trace `other`, returning which frames were
+    /// added, removed, or moved.
+    ///
+    /// Unlike [`TraceDiff`], which compares frames position by position, this
+    /// lines up the frames the two traces have in common, so inserting a frame
+    /// near the origin only shows up as one added frame.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let old = ErrorTrace(vec![CodeLocation::new("a.rs", 1), CodeLocation::new("c.rs", 3)]);
+    /// let new = ErrorTrace(vec![
+    ///     CodeLocation::new("a.rs", 1),
+    ///     CodeLocation::new("b.rs", 2),
+    ///     CodeLocation::new("c.rs", 3),
+    /// ]);
+    ///
+    /// let changes = old.diff(&new);
+    /// assert_eq!(changes.added().collect::<Vec<_>>(), vec![&CodeLocation::new("b.rs", 2)]);
+    /// assert_eq!(changes.removed().count(), 0);
+    /// assert_eq!(changes.to_string(), "    a.rs:1\n  + 1: b.rs:2\n    c.rs:3\n");
+    /// ```
+    ///
+    /// [`TraceDiff`]: crate::diff::TraceDiff
+    pub fn diff(&self, other: &ErrorTrace) -> TraceChanges {
+        TraceChanges::new(&self.0, &other.0)
+    }
+
+    /// Returns the frame at `index` (where 0 is the origin), or the frames in
+    /// a range of positions, or `None` if out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use propagate::trace::*;
+    /// let trace = ErrorTrace(vec![CodeLocation::new("src/parser.rs", 88), CodeLocation::new("src/main.rs", 3)]);
+    /// assert_eq!(trace.get(0).map(CodeLocation::file), Some("src/parser.rs"));
+    /// assert_eq!(trace.get(1..).map(<[_]>::len), Some(1));
+    /// assert_eq!(trace.get(2), None);
+    /// ```
+    #[inline]
+    pub fn get<I: SliceIndex<[CodeLocation]>>(&self, index: I) -> Option<&I::Output> {
+        self.0.get(index)
+    }
+}
+
+/// Collects frames into a trace, oldest first.
+///
+/// # Example
+///
+/// ```
+/// # use propagate::trace::*;
+/// let trace: ErrorTrace = (1..=3).map(|line| CodeLocation::new("src/a.rs", line)).collect();
+/// assert_eq!(trace.depth(), 3);
+/// assert_eq!(trace.latest(), Some(&CodeLocation::new("src/a.rs", 3)));
+/// ```
+impl FromIterator<CodeLocation> for ErrorTrace {
+    fn from_iter<I: IntoIterator<Item = CodeLocation>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+/// Adds frames to the top of the trace.
+///
+/// # Example
+///
+/// ```
+/// # use propagate::trace::*;
+/// let mut trace = ErrorTrace(vec![CodeLocation::new("src/a.rs", 1)]);
+/// trace.extend(vec![CodeLocation::new("src/b.rs", 2)]);
+/// assert_eq!(trace.latest(), Some(&CodeLocation::new("src/b.rs", 2)));
+/// ```
+impl Extend<CodeLocation> for ErrorTrace {
+    fn extend<I: IntoIterator<Item = CodeLocation>>(&mut self, iter: I) {
+        self.0.extend(iter);
+    }
+}
+
+/// Addresses the frames of the trace by position, where 0 is the origin.
+///
+/// Both single positions and ranges of positions are supported.
+///
+/// # Panics
+///
+/// Panics if the index is out of bounds.
+///
+/// # Example
+///
+/// ```
+/// # use propagate::trace::*;
+/// let trace = ErrorTrace(vec![CodeLocation::new("src/parser.rs", 88), CodeLocation::new("src/main.rs", 3)]);
+/// assert_eq!(trace[0].file(), "src/parser.rs");
+/// assert_eq!(trace[0..2].len(), 2);
+/// ```
+impl<I: SliceIndex<[CodeLocation]>> ops::Index<I> for ErrorTrace {
+    type Output = I::Output;
+
+    #[inline]
+    fn index(&self, index: I) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+/// Renders the trace using the installed [`TraceFormatter`].
+///
+/// [`TraceFormatter`]: crate::fmt::TraceFormatter
+impl fmt::Display for ErrorTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::fmt::formatter().fmt_trace(&self.0, f)
+    }
+}
+
+/*
+  _                    _   _             ____  _             _
+ | |    ___   ___ __ _| |_(_) ___  _ __ / ___|| |_ __ _  ___| | __
+ | |   / _ \ / __/ _` | __| |/ _ \| '_ \\___ \| __/ _` |/ __| |/ /
+ | |__| (_) | (_| (_| | |_| | (_) | | | |___) | || (_| | (__|   <
+ |_____\___/ \___\__,_|\__|_|\___/|_| |_|____/ \__\__,_|\___|_|\_\
+
+ FIGLET: LocationStack
+*/
+
+/// A stack of code locations that stores `&'static` [`panic::Location`]s
+/// directly.
+///
+/// Compared to [`ErrorTrace`], pushing a frame is cheaper (no conversion takes
+/// place), each frame is the size of a single pointer, and the column number
+/// of each location is preserved. Boundary labels (see
+/// [`Traced::trace_boundary()`]) are not recorded.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::LocationStack;
+///
+/// fn gives_error() -> propagate::Result<(), &'static str, LocationStack> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// let (_, stack) = gives_error().err_trace().unwrap();
+/// assert_eq!(stack.0.len(), 1);
+/// assert!(stack.0[0].column() > 0);
+/// ```
+#[derive(PartialEq, Eq, Default, Debug, Clone)]
+pub struct LocationStack(pub Vec<&'static panic::Location<'static>>);
+
+impl Traced for LocationStack {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        self.0.push(location);
+    }
+
+    fn frames(&self) -> Option<Frames<'_>> {
+        Some(Box::new(
+            self.0.iter().map(|&location| Cow::Owned(location.into())),
+        ))
+    }
+}
+
+impl Append for LocationStack {
+    #[inline]
+    fn append(&mut self, mut other: Self) {
+        self.0.append(&mut other.0);
+    }
+}
+
+impl LocationStack {
+    /// Constructs a new location stack with the caller at the top.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        Self(vec![panic::Location::caller()])
+    }
+}
+
+impl From<LocationStack> for ErrorTrace {
+    fn from(stack: LocationStack) -> Self {
+        Self(stack.0.into_iter().map(CodeLocation::from).collect())
+    }
+}
+
+/// Renders the stack with one frame per line. Column numbers are included
+/// with the `+` flag (see [Formatting Flags](crate::fmt#formatting-flags)).
+impl fmt::Display for LocationStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::fmt::fmt_locations(&self.0, f)
+    }
+}
+
+/*
+   ____                  _   _             ____  _             _
+  / ___|___  _   _ _ __ | |_(_)_ __   __ _/ ___|| |_ __ _  ___| | __
+ | |   / _ \| | | | '_ \| __| | '_ \ / _` \___ \| __/ _` |/ __| |/ /
+ | |__| (_) | |_| | | | | |_| | | | | (_| |___) | || (_| | (__|   <
+  \____\___/ \__,_|_| |_|\__|_|_| |_|\__, |____/ \__\__,_|\___|_|\_\
+                                     |___/
+ FIGLET: CountingStack
+*/
+
+/// A minimal-overhead stack that only remembers where the error was created
+/// and counts how many times it was propagated.
+///
+/// This is meant for hot paths where storing every location is too
+/// expensive, but knowing how far an error traveled is still useful.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::CountingStack;
+///
+/// fn inner() -> propagate::Result<(), &'static str, CountingStack> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// fn outer() -> propagate::Result<(), &'static str, CountingStack> {
+///     propagate::Ok(inner()?)
+/// }
+///
+/// let (_, stack) = outer().err_trace().unwrap();
+/// assert_eq!(stack.depth(), 2);
+/// assert_eq!(stack.hops(), 1);
+/// assert!(stack.origin().is_some());
+/// ```
+#[derive(PartialEq, Eq, Default, Debug, Copy, Clone)]
+pub struct CountingStack {
+    origin: Option<&'static panic::Location<'static>>,
+    depth: usize,
+}
+
+impl Traced for CountingStack {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        if self.origin.is_none() {
+            self.origin = Some(location);
+        }
+        self.depth += 1;
+    }
+}
+
+impl Append for CountingStack {
+    #[inline]
+    fn append(&mut self, other: Self) {
+        if self.origin.is_none() {
+            self.origin = other.origin;
+        }
+        self.depth += other.depth;
+    }
+}
+
+impl CountingStack {
+    /// Constructs a new counting stack with the caller as the origin.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        Self {
+            origin: Some(panic::Location::caller()),
+            depth: 1,
+        }
+    }
+
+    /// Returns the location where the error was created, if any.
+    #[inline]
+    pub const fn origin(&self) -> Option<&'static panic::Location<'static>> {
+        self.origin
+    }
+
+    /// Returns the total number of locations that were traced, including the
+    /// origin.
+    #[inline]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the number of times the error was propagated after it was
+    /// created.
+    #[inline]
+    pub const fn hops(&self) -> usize {
+        self.depth.saturating_sub(1)
+    }
+}
+
+impl fmt::Display for CountingStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.origin {
+            Some(origin) => write!(
+                f,
+                "\n   origin: {}:{} (propagated {} time(s))",
+                crate::fmt::remap_path(origin.file()),
+                origin.line(),
+                self.hops()
+            ),
+            None => Ok(()),
+        }
+    }
+}
+
+/*
+  _   _           _     _             ____  _             _
+ | | | | __ _ ___| |__ (_)_ __   __ _/ ___|| |_ __ _  ___| | __
+ | |_| |/ _` / __| '_ \| | '_ \ / _` \___ \| __/ _` |/ __| |/ /
+ |  _  | (_| \__ \ | | | | | | | (_| |___) | || (_| | (__|   <
+ |_| |_|\__,_|___/_| |_|_|_| |_|\__, |____/ \__\__,_|\___|_|\_\
+                                |___/
+ FIGLET: HashingStack
+*/
+
+/// A constant-size stack that folds every location into a 64-bit fingerprint
+/// of the propagation path.
+///
+/// Two errors that were created at the same location and propagated through
+/// the same sequence of locations have the same fingerprint. The fingerprint
+/// only depends on the file names and line numbers, so it is stable across
+/// runs and platforms, and can be used to group identical failures in logs and
+/// alerting systems.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::HashingStack;
+///
+/// fn gives_error() -> propagate::Result<(), &'static str, HashingStack> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// let (_, first) = gives_error().err_trace().unwrap();
+/// let (_, second) = gives_error().err_trace().unwrap();
+/// assert_eq!(first.fingerprint(), second.fingerprint());
+/// assert_ne!(first.fingerprint(), HashingStack::default().fingerprint());
+/// ```
+#[derive(PartialEq, Eq, Debug, Copy, Clone, Hash)]
+pub struct HashingStack {
+    fingerprint: u64,
+    depth: usize,
+}
+
+impl Default for HashingStack {
+    fn default() -> Self {
+        Self {
+            fingerprint: FNV_OFFSET_BASIS,
+            depth: 0,
+        }
+    }
+}
+
+impl Traced for HashingStack {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        self.fingerprint = fold_fingerprint(self.fingerprint, location.file(), location.line());
+        self.depth += 1;
+    }
+}
+
+impl HashingStack {
+    /// Constructs a new hashing stack with the caller as the origin.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        let mut stack = Self::default();
+        stack.trace(panic::Location::caller());
+        stack
+    }
+
+    /// Returns the fingerprint of the propagation path.
+    #[inline]
+    pub const fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Returns the number of locations that were folded into the fingerprint.
+    #[inline]
+    pub const fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl fmt::Display for HashingStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\n   fingerprint: {:016x} ({} location(s))",
+            self.fingerprint, self.depth
+        )
+    }
+}
+
+/*
+   ____               ____  _             _
+  / ___|_____      __/ ___|| |_ __ _  ___| | __
+ | |   / _ \ \ /\ / /\___ \| __/ _` |/ __| |/ /
+ | |__| (_) \ V  V /  ___) | || (_| | (__|   <
+  \____\___/ \_/\_/  |____/ \__\__,_|\___|_|\_\
+
+ FIGLET: CowStack
+*/
+
+/// A stack of code locations that can start out as a borrowed, precomputed
+/// `'static` slice.
+///
+/// This is useful for commonly-constructed sentinel errors: the trace prefix
+/// describing where such an error comes from can be computed once and stored
+/// in a `static`, and only gets copied into an allocation when the error is
+/// propagated further.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::CowStack;
+/// use propagate::CodeLocation;
+///
+/// static NOT_FOUND_TRACE: [CodeLocation; 2] = [
+///     CodeLocation::new("src/store.rs", 10),
+///     CodeLocation::new("src/store.rs", 42),
+/// ];
+///
+/// fn not_found() -> propagate::Result<(), &'static str, CowStack> {
+///     propagate::Err("not found", CowStack::from_static(&NOT_FOUND_TRACE))
+/// }
+///
+/// fn lookup() -> propagate::Result<(), &'static str, CowStack> {
+///     propagate::Ok(not_found()?)
+/// }
+///
+/// let (_, stack) = not_found().err_trace().unwrap();
+/// assert!(stack.is_borrowed());
+///
+/// let (_, stack) = lookup().err_trace().unwrap();
+/// assert!(!stack.is_borrowed());
+/// assert_eq!(stack.0.len(), 3);
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct CowStack(pub Cow<'static, [CodeLocation]>);
+
+impl Default for CowStack {
+    fn default() -> Self {
+        Self(Cow::Borrowed(&[]))
+    }
+}
+
+impl Traced for CowStack {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        self.0.to_mut().push(record_location(location));
+    }
+
+    fn trace_boundary(&mut self, location: &'static panic::Location, label: &str) {
+        self.0
+            .to_mut()
+            .push(record_location(location).with_boundary(label));
+    }
+
+    fn trace_context(&mut self, location: &'static panic::Location, context: &str) {
+        self.0
+            .to_mut()
+            .push(record_location(location).with_context(context));
+    }
+
+    fn frames(&self) -> Option<Frames<'_>> {
+        Some(Box::new(self.0.iter().map(Cow::Borrowed)))
+    }
+}
+
+impl Append for CowStack {
+    #[inline]
+    fn append(&mut self, other: Self) {
+        match other.0 {
+            Cow::Borrowed(frames) => self.0.to_mut().extend_from_slice(frames),
+            Cow::Owned(mut frames) => self.0.to_mut().append(&mut frames),
+        }
+    }
+}
+
+impl CowStack {
+    /// Constructs a new stack with the caller at the top.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        let caller = record_location(panic::Location::caller());
+        Self(Cow::Owned(vec![caller]))
+    }
+
+    /// Constructs a stack that borrows the precomputed locations in `prefix`
+    /// until it is extended.
+    #[inline]
+    pub const fn from_static(prefix: &'static [CodeLocation]) -> Self {
+        Self(Cow::Borrowed(prefix))
+    }
+
+    /// Returns `true` if the stack still borrows its locations (i.e., it has
+    /// not been extended since it was constructed with
+    /// [`from_static()`][CowStack::from_static]).
+    #[inline]
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self.0, Cow::Borrowed(_))
+    }
+}
+
+impl From<CowStack> for ErrorTrace {
+    fn from(stack: CowStack) -> Self {
+        Self(stack.0.into_owned())
+    }
+}
+
+/// Renders the stack using the installed [`TraceFormatter`].
+///
+/// [`TraceFormatter`]: crate::fmt::TraceFormatter
+impl fmt::Display for CowStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::fmt::formatter().fmt_trace(&self.0, f)
+    }
+}
+
+/*
+  ____             _          _ ____  _             _
+ |  _ \ ___   ___ | | ___  __| / ___|| |_ __ _  ___| | __
+ | |_) / _ \ / _ \| |/ _ \/ _` \___ \| __/ _` |/ __| |/ /
+ |  __/ (_) | (_) | |  __/ (_| |___) | || (_| | (__|   <
+ |_|   \___/ \___/|_|\___|\__,_|____/ \__\__,_|\___|_|\_\
+
+ FIGLET: PooledStack
+*/
+
+/// The maximum number of buffers kept in each thread's pool.
+const POOL_CAPACITY: usize = 64;
+
+thread_local! {
+    static POOL: RefCell<Vec<Vec<&'static panic::Location<'static>>>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// A stack of locations whose buffer is recycled when the stack is dropped.
+///
+/// Services that create and handle thousands of short-lived errors per second
+/// spend a noticeable amount of time allocating and freeing the frame vectors
+/// of their traces. A pooled stack returns its buffer to a small per-thread
+/// pool when it is dropped, and a new stack takes a buffer from that pool
+/// instead of allocating, so a thread that handles errors as fast as it
+/// creates them stops hitting the allocator.
+///
+/// Each thread keeps at most 64 buffers. Buffers dropped on a thread other
+/// than the one that allocated them join the pool of the dropping thread.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::PooledStack;
+///
+/// fn gives_error() -> propagate::Result<(), &'static str, PooledStack> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// let (_, stack) = gives_error().err_trace().unwrap();
+/// assert_eq!(stack.locations().len(), 1);
+///
+/// drop(stack);
+/// assert_eq!(PooledStack::pooled(), 1);
+///
+/// let (_, stack) = gives_error().err_trace().unwrap();
+/// assert_eq!(PooledStack::pooled(), 0);
+/// # drop(stack);
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct PooledStack {
+    locations: Vec<&'static panic::Location<'static>>,
+}
+
+impl Default for PooledStack {
+    #[inline]
+    fn default() -> Self {
+        let recycled = POOL.try_with(|pool| pool.borrow_mut().pop()).ok().flatten();
+        Self {
+            locations: recycled.unwrap_or_default(),
+        }
+    }
+}
+
+impl Drop for PooledStack {
+    fn drop(&mut self) {
+        if self.locations.capacity() == 0 {
+            return;
+        }
+
+        let mut buffer = std::mem::take(&mut self.locations);
+        buffer.clear();
+        let _ = POOL.try_with(|pool| {
+            let mut pool = pool.borrow_mut();
+            if pool.len() < POOL_CAPACITY {
+                pool.push(buffer);
+            }
+        });
+    }
+}
+
+impl Traced for PooledStack {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        self.locations.push(location);
+    }
+
+    fn frames(&self) -> Option<Frames<'_>> {
+        Some(Box::new(
+            self.locations
+                .iter()
+                .map(|&location| Cow::Owned(location.into())),
+        ))
+    }
+}
+
+impl Append for PooledStack {
+    #[inline]
+    fn append(&mut self, other: Self) {
+        self.locations.extend_from_slice(&other.locations);
+    }
+}
+
+impl PooledStack {
+    /// Constructs a new pooled stack with the caller at the top.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        let mut stack = Self::default();
+        stack.trace(panic::Location::caller());
+        stack
+    }
+
+    /// Returns the traced locations, oldest first.
+    #[inline]
+    pub fn locations(&self) -> &[&'static panic::Location<'static>] {
+        &self.locations
+    }
+
+    /// Returns the number of buffers waiting to be reused on the current
+    /// thread.
+    pub fn pooled() -> usize {
+        POOL.try_with(|pool| pool.borrow().len()).unwrap_or(0)
+    }
+}
+
+impl From<PooledStack> for ErrorTrace {
+    fn from(stack: PooledStack) -> Self {
+        Self(
+            stack
+                .locations
+                .iter()
+                .copied()
+                .map(CodeLocation::from)
+                .collect(),
+        )
+    }
+}
+
+/// Renders the stack with one frame per line. Column numbers are included
+/// with the `+` flag (see [Formatting Flags](crate::fmt#formatting-flags)).
+impl fmt::Display for PooledStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::fmt::fmt_locations(&self.locations, f)
+    }
+}
+
+/*
+     _                         ____  _             _
+    / \   _ __ ___ _ __   __ _/ ___|| |_ __ _  ___| | __
+   / _ \ | '__/ _ \ '_ \ / _` \___ \| __/ _` |/ __| |/ /
+  / ___ \| | |  __/ | | | (_| |___) | || (_| | (__|   <
+ /_/   \_\_|  \___|_| |_|\__,_|____/ \__\__,_|\___|_|\_\
+
+ FIGLET: ArenaStack
+*/
+
+thread_local! {
+    static CURRENT_ARENA: RefCell<Vec<TraceArena>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The number of frames that [`TraceArena::new()`] makes room for.
+pub const DEFAULT_ARENA_CAPACITY: usize = 1024;
+
+type ArenaFrame = &'static panic::Location<'static>;
+
+/// A fixed-size buffer that allocations are bumped from.
+struct ArenaBuffer {
+    start: NonNull<u8>,
+    layout: Layout,
+    /// The number of bytes from `start` that are allocated.
+    used: AtomicUsize,
+}
+
+// The buffer is only written through the disjoint allocations handed out by
+// `bump()`, which claims them with an atomic operation.
+unsafe impl Send for ArenaBuffer {}
+unsafe impl Sync for ArenaBuffer {}
+
+impl ArenaBuffer {
+    fn with_capacity(capacity: usize) -> Self {
+        let layout = Layout::array::<ArenaFrame>(capacity).expect("arena capacity overflow");
+        let start = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            // SAFETY: the layout has a non-zero size.
+            NonNull::new(unsafe { alloc::alloc(layout) })
+                .unwrap_or_else(|| alloc::handle_alloc_error(layout))
+        };
+        Self {
+            start,
+            layout,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Claims a block for `layout` at the end of the allocated bytes, if it
+    /// fits in the buffer.
+    fn bump(&self, layout: Layout) -> Option<NonNull<[u8]>> {
+        let start = self.start.as_ptr() as usize;
+        let mut used = self.used.load(Ordering::Acquire);
+        loop {
+            let offset = (start + used).checked_next_multiple_of(layout.align())? - start;
+            let end = offset.checked_add(layout.size())?;
+            if end > self.layout.size() {
+                return None;
+            }
+            match self
+                .used
+                .compare_exchange_weak(used, end, Ordering::AcqRel, Ordering::Acquire)
+            {
+                std::result::Result::Ok(_) => {
+                    // SAFETY: `offset + layout.size()` is within the buffer.
+                    let ptr = unsafe { self.start.as_ptr().add(offset) };
+                    let ptr = ptr::slice_from_raw_parts_mut(ptr, layout.size());
+                    return NonNull::new(ptr);
+                }
+                std::result::Result::Err(actual) => used = actual,
+            }
+        }
+    }
+
+    /// Moves the end of the allocated bytes from `old_end` to `new_end`, if
+    /// the block at `offset` is the last one allocated.
+    fn resize_last(&self, offset: usize, old_end: usize, new_end: usize) -> bool {
+        new_end <= self.layout.size()
+            && offset <= new_end
+            && self
+                .used
+                .compare_exchange(old_end, new_end, Ordering::AcqRel, Ordering::Relaxed)
+                .is_ok()
+    }
+
+    /// Returns the offset of `ptr` in the buffer, if it points into it.
+    fn offset_of(&self, ptr: NonNull<u8>) -> Option<usize> {
+        (ptr.as_ptr() as usize)
+            .checked_sub(self.start.as_ptr() as usize)
+            .filter(|&offset| offset < self.layout.size())
+    }
+}
+
+impl Drop for ArenaBuffer {
+    fn drop(&mut self) {
+        if self.layout.size() != 0 {
+            // SAFETY: the buffer was allocated with this layout.
+            unsafe { alloc::dealloc(self.start.as_ptr(), self.layout) }
+        }
+    }
+}
+
+/// An arena that the frames of [`ArenaStack`]s are allocated from, and freed
+/// with all at once.
+///
+/// A server that handles many failing requests spends a lot of time
+/// allocating and freeing the frames of short-lived traces. Giving each
+/// request an arena, and [entering](TraceArena::enter) it while the request
+/// is being handled, allocates the frames of every error of the request from
+/// a buffer of fixed size by bumping a pointer, without taking any lock.
+///
+/// The buffer is retained until the arena and every stack allocated in it are
+/// dropped. When a stack is dropped, its frames are reclaimed only if they are
+/// the last ones allocated, as they are when errors are handled in the order
+/// opposite to their creation; other frames are reclaimed all at once by
+/// [`reset()`](TraceArena::reset), which makes the buffer available to the
+/// next request without going through the allocator at all. Once the buffer
+/// is full, new frames are allocated on the heap as usual, so an arena that is
+/// never reset stops being useful but does not grow.
+///
+/// # Example
+///
+/// ```
+/// use propagate::trace::{ArenaStack, TraceArena};
+///
+/// fn gives_error() -> propagate::Result<(), &'static str, ArenaStack> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// fn propagates() -> propagate::Result<(), &'static str, ArenaStack> {
+///     propagate::Ok(gives_error()?)
+/// }
+///
+/// let mut arena = TraceArena::with_capacity(64);
+/// {
+///     let _entered = arena.enter();
+///     let (_, first) = propagates().err_trace().unwrap();
+///     let (_, second) = propagates().err_trace().unwrap();
+///     assert_eq!(first.locations().len(), 2);
+///     assert!(first.in_arena(&arena) && second.in_arena(&arena));
+///
+///     // `first` is not the last one allocated, so its frames are kept until
+///     // the arena is reset, but those of `second` are reclaimed.
+///     let used = arena.len();
+///     drop(first);
+///     assert_eq!(arena.len(), used);
+///     drop(second);
+///     assert!(arena.len() < used && !arena.is_empty());
+/// }
+///
+/// // All stacks are gone, so the buffer can be reused.
+/// assert!(arena.reset());
+/// assert!(arena.is_empty());
+/// ```
+#[derive(Clone)]
+pub struct TraceArena {
+    buffer: Arc<ArenaBuffer>,
+}
+
+impl TraceArena {
+    /// Constructs an arena with room for [`DEFAULT_ARENA_CAPACITY`] frames.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_ARENA_CAPACITY)
+    }
+
+    /// Constructs an arena with room for `capacity` frames.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            buffer: Arc::new(ArenaBuffer::with_capacity(capacity)),
+        }
+    }
+
+    /// Makes this the arena that new [`ArenaStack`]s on the current thread
+    /// are allocated from, until the returned guard is dropped.
+    ///
+    /// Arenas can be entered while another one is entered; the innermost one
+    /// is used.
+    pub fn enter(&self) -> ArenaGuard {
+        let _ = CURRENT_ARENA.try_with(|arenas| arenas.borrow_mut().push(self.clone()));
+        ArenaGuard {
+            buffer: Arc::as_ptr(&self.buffer),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns the number of frames the arena has room for.
+    pub fn capacity(&self) -> usize {
+        self.buffer.layout.size() / mem::size_of::<ArenaFrame>()
+    }
+
+    /// Returns the number of frames' worth of the arena that is in use,
+    /// including the room stacks keep to grow and the frames of dropped stacks
+    /// that were not reclaimed yet.
+    pub fn len(&self) -> usize {
+        self.buffer.used.load(Ordering::Relaxed) / mem::size_of::<ArenaFrame>()
+    }
+
+    /// Returns `true` if no part of the arena is in use.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reclaims the whole arena, keeping its buffer for reuse.
+    ///
+    /// This only succeeds, returning `true`, if no stack allocated in the
+    /// arena is alive and the arena is neither cloned nor entered.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use propagate::trace::{ArenaStack, TraceArena};
+    ///
+    /// let mut arena = TraceArena::with_capacity(16);
+    /// let mut handle_request = |arena: &mut TraceArena| {
+    ///     let (first, second) = {
+    ///         let _entered = arena.enter();
+    ///         (ArenaStack::new(), ArenaStack::new())
+    ///     };
+    ///     let frames = first.locations().as_ptr();
+    ///     drop(first);
+    ///     drop(second);
+    ///     assert!(!arena.is_empty());
+    ///     assert!(arena.reset());
+    ///     frames
+    /// };
+    ///
+    /// // Each request allocates its frames from the start of the same buffer.
+    /// let frames = handle_request(&mut arena);
+    /// for _ in 0..100 {
+    ///     assert_eq!(handle_request(&mut arena), frames);
+    /// }
+    /// assert!(arena.is_empty());
+    /// ```
+    pub fn reset(&mut self) -> bool {
+        match Arc::get_mut(&mut self.buffer) {
+            Some(buffer) => {
+                *buffer.used.get_mut() = 0;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the arena entered on the current thread, if any.
+    fn current() -> Option<Self> {
+        CURRENT_ARENA
+            .try_with(|arenas| arenas.borrow().last().cloned())
+            .ok()
+            .flatten()
+    }
+}
+
+impl Default for TraceArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for TraceArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TraceArena")
+            .field("len", &self.len())
+            .field("capacity", &self.capacity())
+            .finish()
+    }
+}
+
+/// Leaves the arena entered by [`TraceArena::enter()`] when dropped.
+#[must_use = "the arena is left as soon as the guard is dropped"]
+#[derive(Debug)]
+pub struct ArenaGuard {
+    buffer: *const ArenaBuffer,
+    // The arena is entered on the thread that entered it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ArenaGuard {
+    fn drop(&mut self) {
+        let _ = CURRENT_ARENA.try_with(|arenas| {
+            let mut arenas = arenas.borrow_mut();
+            let entered = arenas
+                .iter()
+                .rposition(|arena| Arc::as_ptr(&arena.buffer) == self.buffer);
+            if let Some(index) = entered {
+                arenas.remove(index);
+            }
+        });
+    }
+}
+
+/// Allocates from an arena if there is one and it has room, and from the
+/// global allocator otherwise.
+#[derive(Clone)]
+struct ArenaAlloc(Option<TraceArena>);
+
+impl ArenaAlloc {
+    fn buffer(&self) -> Option<&ArenaBuffer> {
+        self.0.as_ref().map(|arena| &*arena.buffer)
+    }
+}
+
+unsafe impl Allocator for ArenaAlloc {
+    fn allocate(&self, layout: Layout) -> std::result::Result<NonNull<[u8]>, AllocError> {
+        match self.buffer().and_then(|buffer| buffer.bump(layout)) {
+            Some(block) => std::result::Result::Ok(block),
+            None => Global.allocate(layout),
+        }
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        match self.buffer() {
+            Some(buffer) => match buffer.offset_of(ptr) {
+                Some(offset) => {
+                    buffer.resize_last(offset, offset + layout.size(), offset);
+                }
+                None => Global.deallocate(ptr, layout),
+            },
+            None => Global.deallocate(ptr, layout),
+        }
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> std::result::Result<NonNull<[u8]>, AllocError> {
+        if let Some(buffer) = self.buffer() {
+            if let Some(offset) = buffer.offset_of(ptr) {
+                let aligned = (ptr.as_ptr() as usize).is_multiple_of(new_layout.align());
+                let old_end = offset + old_layout.size();
+                if aligned && buffer.resize_last(offset, old_end, offset + new_layout.size()) {
+                    let block = ptr::slice_from_raw_parts_mut(ptr.as_ptr(), new_layout.size());
+                    return std::result::Result::Ok(NonNull::new_unchecked(block));
+                }
+            }
+        }
+
+        let block = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(ptr.as_ptr(), block.as_ptr() as *mut u8, old_layout.size());
+        self.deallocate(ptr, old_layout);
+        std::result::Result::Ok(block)
+    }
+}
+
+/// A stack of locations whose frames are allocated in a [`TraceArena`].
+///
+/// A new stack is allocated in the arena entered on the current thread (see
+/// [`TraceArena::enter()`]), and keeps the arena alive until it is dropped.
+/// If no arena is entered, or once the arena is full, the frames are allocated
+/// on the heap like those of an [`ErrorTrace`].
+///
+/// See [`TraceArena`] for an example.
+#[derive(Clone)]
+pub struct ArenaStack {
+    locations: Vec<ArenaFrame, ArenaAlloc>,
+}
+
+impl Default for ArenaStack {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            locations: Vec::new_in(ArenaAlloc(TraceArena::current())),
+        }
+    }
+}
+
+impl Traced for ArenaStack {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        self.locations.push(location);
+    }
+
+    fn frames(&self) -> Option<Frames<'_>> {
+        Some(Box::new(
+            self.locations
+                .iter()
+                .map(|&location| Cow::Owned(location.into())),
+        ))
+    }
+}
+
+impl Append for ArenaStack {
+    fn append(&mut self, other: Self) {
+        self.locations.extend_from_slice(&other.locations);
+    }
+}
+
+impl ArenaStack {
+    /// Constructs a new stack with the caller at the top.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        let mut stack = Self::default();
+        stack.trace(panic::Location::caller());
+        stack
+    }
+
+    /// Returns the traced locations, oldest first.
+    #[inline]
+    pub fn locations(&self) -> &[&'static panic::Location<'static>] {
+        &self.locations
+    }
+
+    /// Returns the arena the stack allocates its frames from, if any.
+    pub fn arena(&self) -> Option<&TraceArena> {
+        self.locations.allocator().0.as_ref()
+    }
+
+    /// Returns `true` if the frames of the stack are stored in `arena`.
+    ///
+    /// This is `false` if the stack was allocated in another arena, or if
+    /// `arena` was full when the stack last grew.
+    pub fn in_arena(&self, arena: &TraceArena) -> bool {
+        let ptr = NonNull::from(&*self.locations).cast::<u8>();
+        self.locations.capacity() != 0 && arena.buffer.offset_of(ptr).is_some()
+    }
+}
+
+impl PartialEq for ArenaStack {
+    fn eq(&self, other: &Self) -> bool {
+        self.locations() == other.locations()
+    }
+}
+
+impl Eq for ArenaStack {}
+
+impl fmt::Debug for ArenaStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArenaStack")
+            .field("locations", &self.locations())
+            .finish()
+    }
+}
+
+impl From<ArenaStack> for ErrorTrace {
+    fn from(stack: ArenaStack) -> Self {
+        Self(
+            stack
+                .locations()
+                .iter()
+                .map(|&location| CodeLocation::from(location))
+                .collect(),
+        )
+    }
+}
+
+/// Renders the stack with one frame per line. Column numbers are included
+/// with the `+` flag (see [Formatting Flags](crate::fmt#formatting-flags)).
+impl fmt::Display for ArenaStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::fmt::fmt_locations(self.locations(), f)
+    }
+}
+
+/*
+  _____ _                    _ ____  _             _
+ |_   _(_)_ __ ___   ___  __| / ___|| |_ __ _  ___| | __
+   | | | | '_ ` _ \ / _ \/ _` \___ \| __/ _` |/ __| |/ /
+   | | | | | | | | |  __/ (_| |___) | || (_| | (__|   <
+   |_| |_|_| |_| |_|\___|\__,_|____/ \__\__,_|\___|_|\_\
+
+ FIGLET: TimedStack
+*/
+
+/// A stack that records when its error was created, in addition to the
+/// frames of an inner stack.
+///
+/// Errors that sit in queues or go through retries can surface long after
+/// they were created. The [`age()`][TimedStack::age] of the error tells how
+/// long, and is shown after the frames when the stack is displayed, e.g. when
+/// the error is returned from `main`:
+///
+/// ```txt
+/// Return Trace:
+///    0: src/worker.rs:12
+///    1: src/main.rs:40
+///    error created 3.2s before being reported
+/// ```
+///
+/// Only errors using this stack type pay for reading the clock.
+///
+/// # Example
+///
+/// ```
+/// use propagate::TimedStack;
+///
+/// fn gives_error() -> propagate::Result<(), &'static str, TimedStack> {
+///     propagate::Result::new_err("Nothing here")
+/// }
+///
+/// let traced = gives_error().traced_err().unwrap();
+/// assert_eq!(traced.stack().stack().0.len(), 1);
+/// assert!(traced.age().is_some());
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct TimedStack<S = ErrorTrace> {
+    stack: S,
+    created: Instant,
+}
+
+impl<S: Default> Default for TimedStack<S> {
+    fn default() -> Self {
+        Self {
+            stack: S::default(),
+            created: Instant::now(),
+        }
+    }
+}
+
+impl<S: Traced> Traced for TimedStack<S> {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        self.stack.trace(location);
+    }
+
+    #[inline]
+    fn trace_boundary(&mut self, location: &'static panic::Location, label: &str) {
+        self.stack.trace_boundary(location, label);
+    }
+
+    #[inline]
+    fn trace_context(&mut self, location: &'static panic::Location, context: &str) {
+        self.stack.trace_context(location, context);
+    }
+
+    #[inline]
+    fn correlation_id(&self) -> Option<u64> {
+        self.stack.correlation_id()
+    }
+
+    #[inline]
+    fn created_at(&self) -> Option<Instant> {
+        Some(self.created)
+    }
+
+    #[inline]
+    fn frames(&self) -> Option<Frames<'_>> {
+        self.stack.frames()
+    }
+}
+
+/// Keeps the earlier of the two creation times.
+impl<S: Append> Append for TimedStack<S> {
+    #[inline]
+    fn append(&mut self, other: Self) {
+        self.stack.append(other.stack);
+        self.created = self.created.min(other.created);
+    }
+}
+
+impl<S: Traced + Default> TimedStack<S> {
+    /// Constructs a new stack created now, with the caller at the top.
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        let mut stack = Self::default();
+        stack.trace(panic::Location::caller());
+        stack
+    }
+}
+
+impl<S> TimedStack<S> {
+    /// Returns the inner stack.
+    #[inline]
+    pub const fn stack(&self) -> &S {
+        &self.stack
+    }
+
+    /// Returns the inner stack, consuming `self`.
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.stack
+    }
+
+    /// Returns how long ago the error was created.
+    #[inline]
+    pub fn age(&self) -> Duration {
+        self.created.elapsed()
+    }
+}
+
+/// Renders the inner stack, followed by the age of the error.
+impl<S: fmt::Display> fmt::Display for TimedStack<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.stack, f)?;
+        write!(
+            f,
+            "\n   error created {:.1?} before being reported",
+            self.age()
+        )
+    }
+}
+
+/*
+  ____
+ | __ )  _____  __
+ |  _ \ / _ \ \/ /
+ | |_) | (_) >  <
+ |____/ \___/_/\_\
+
+ FIGLET: Box<S>
+*/
+
+/// Stores a stack behind a pointer.
+///
+/// The error variant of a [`Result`][crate::Result] holds its stack inline, so
+/// a large stack (such as the `Vec` inside [`ErrorTrace`]) makes every result
+/// larger, even on the success path. Boxing the stack keeps the size of the
+/// result close to that of a [`std::result::Result`], at the cost of an extra
+/// allocation when an error is created. See [`BoxedResult`].
+///
+/// [`BoxedResult`]: crate::result::BoxedResult
+impl<S: Traced + ?Sized> Traced for Box<S> {
+    #[inline]
+    fn trace(&mut self, location: &'static panic::Location) {
+        (**self).trace(location);
+    }
+
+    #[inline]
+    fn trace_boundary(&mut self, location: &'static panic::Location, label: &str) {
+        (**self).trace_boundary(location, label);
+    }
+
+    #[inline]
+    fn trace_context(&mut self, location: &'static panic::Location, context: &str) {
+        (**self).trace_context(location, context);
+    }
+
+    #[inline]
+    fn correlation_id(&self) -> Option<u64> {
+        (**self).correlation_id()
+    }
+
+    #[inline]
+    fn created_at(&self) -> Option<Instant> {
+        (**self).created_at()
+    }
+
+    #[inline]
+    fn frames(&self) -> Option<Frames<'_>> {
+        (**self).frames()
+    }
+}
+
+impl<S: Append> Append for Box<S> {
+    #[inline]
+    fn append(&mut self, other: Self) {
+        (**self).append(*other);
+    }
+}
+
+/*
+  ____              _____                       _
+ |  _ \ _   _ _ __ |_   _| __ __ _  ___ ___  __| |
+ | | | | | | | '_ \  | || '__/ _` |/ __/ _ \/ _` |
+ | |_| | |_| | | | | | || | | (_| | (_|  __/ (_| |
+ |____/ \__, |_| |_| |_||_|  \__,_|\___\___|\__,_|
+        |___/
+ FIGLET: DynTraced
+*/
+
+/// A stack type that can be chosen at runtime.
+///
+/// This is implemented for every [`Traced`] type that can be displayed,
+/// debugged, and shared between threads. A `Box<dyn DynTraced>` can be used as
+/// the stack of a [`Result`][crate::Result] (see [`DynResult`]), so the kind
+/// of stack can be selected from configuration (e.g., a full [`ErrorTrace`] in
+/// staging and a [`CountingStack`] in production) without compiling generic
+/// code once per stack type.
+///
+/// New stacks are created by the factory installed with
+/// [`set_dyn_stack_factory()`], which creates an [`ErrorTrace`] by default.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::trace::{self, DynResult, DynTraced};
+/// use propagate::CountingStack;
+///
+/// fn counting() -> Box<dyn DynTraced> {
+///     Box::new(CountingStack::default())
+/// }
+///
+/// trace::set_dyn_stack_factory(counting);
+///
+/// let result: DynResult<(), &str> = propagate::Result::new_err("Nothing here");
+/// let (_, stack) = result.err_trace().unwrap();
+/// assert!(stack.to_string().contains("propagated 0 time(s)"));
+///
+/// trace::reset_dyn_stack_factory();
+/// ```
+///
+/// [`DynResult`]: crate::trace::DynResult
+pub trait DynTraced: Traced + fmt::Display + fmt::Debug + Send + Sync {}
+
+impl<S: Traced + fmt::Display + fmt::Debug + Send + Sync> DynTraced for S {}
+
+/// A [`Result`][crate::Result] whose stack type is chosen at runtime.
+pub type DynResult<T, E> = crate::Result<T, E, Box<dyn DynTraced>>;
+
+/// A function that creates an empty stack for a new error.
+pub type DynStackFactory = fn() -> Box<dyn DynTraced>;
+
+static DYN_STACK_FACTORY: RwLock<Option<DynStackFactory>> = RwLock::new(None);
+
+/// Installs `factory` to create the stacks of new [`DynResult`] errors,
+/// replacing any previously installed factory.
+pub fn set_dyn_stack_factory(factory: DynStackFactory) {
+    *DYN_STACK_FACTORY.write().unwrap_or_else(|e| e.into_inner()) = Some(factory);
+}
+
+/// Restores the default factory, which creates an empty [`ErrorTrace`], or an
+/// empty [`TimedStack`] if [`TraceConfig::with_timestamps()`] is set.
+pub fn reset_dyn_stack_factory() {
+    *DYN_STACK_FACTORY.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// Creates an empty stack using the installed factory.
+impl Default for Box<dyn DynTraced> {
+    fn default() -> Self {
+        let factory = *DYN_STACK_FACTORY.read().unwrap_or_else(|e| e.into_inner());
+        match factory {
+            Some(factory) => factory(),
+            None if installed_config().is_some_and(|config| config.timestamps) => {
+                Box::new(TimedStack::<ErrorTrace>::default())
+            }
+            None => Box::new(ErrorTrace::default()),
+        }
+    }
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Folds `bytes` into a running 64-bit FNV-1a hash, which starts at
+/// [`FNV_OFFSET_BASIS`].
+///
+/// Unlike the hashers in the standard library, this is guaranteed to produce
+/// the same output across runs, platforms, and compiler versions.
+pub(crate) fn fnv1a<'a>(hash: u64, bytes: impl IntoIterator<Item = &'a u8>) -> u64 {
+    bytes.into_iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Folds a location into a running 64-bit FNV-1a fingerprint.
+pub(crate) fn fold_fingerprint(hash: u64, file: &str, line: u32) -> u64 {
+    fnv1a(
+        hash,
+        file.as_bytes()
+            .iter()
+            .chain(&[0xff])
+            .chain(&line.to_le_bytes()),
+    )
 }
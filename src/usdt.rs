@@ -0,0 +1,99 @@
+//! Static tracepoints for error creation and propagation.
+//!
+//! With the `usdt` feature, every traced error fires a [USDT] probe of the
+//! `propagate` provider, which can be attached to with `bpftrace`, `perf` or
+//! SystemTap to investigate a running program without changing its logging
+//! configuration or restarting it. The probes are:
+//!
+//! | Probe        | Fired when                                                 |
+//! |--------------|------------------------------------------------------------|
+//! | `created`    | a new trace is started ([`EventKind::Created`])            |
+//! | `propagated` | an error passes through `?` ([`EventKind::Propagated`])    |
+//! | `converted`  | an error becomes a std result ([`EventKind::Converted`])   |
+//! | `handled`    | an error is handled ([`EventKind::Handled`])               |
+//!
+//! Each probe has the same arguments, which describe the location of the
+//! event:
+//!
+//! * `arg0`: a pointer to the file name, which is *not* nul-terminated,
+//! * `arg1`: the length of the file name in bytes,
+//! * `arg2`: the line number,
+//! * `arg3`: the column number.
+//!
+//! The arguments are only computed while a tracer is attached to the probe,
+//! so an unused probe costs a single branch. The probes fire regardless of
+//! [`capture_enabled()`], so errors can be followed even in a binary that
+//! does not record traces.
+//!
+//! # Examples
+//!
+//! Printing the origin of every error created by a running process:
+//!
+//! ```text
+//! bpftrace -p $PID -e 'usdt:*:propagate:created {
+//!     printf("%s:%d\n", str(arg0, arg1), arg2);
+//! }'
+//! ```
+//!
+//! Counting the `?` hops of errors by location:
+//!
+//! ```text
+//! bpftrace -p $PID -e 'usdt:*:propagate:propagated {
+//!     @hops[str(arg0, arg1), arg2] = count();
+//! }'
+//! ```
+//!
+//! Available with the `usdt` feature, on the platforms supported by the
+//! [`probe`] crate; the probes are no-ops elsewhere.
+//!
+//! [USDT]: https://www.brendangregg.com/blog/2015-07-03/hacking-linux-usdt-ftrace.html
+//! [`EventKind::Created`]: crate::observe::EventKind::Created
+//! [`EventKind::Propagated`]: crate::observe::EventKind::Propagated
+//! [`EventKind::Converted`]: crate::observe::EventKind::Converted
+//! [`EventKind::Handled`]: crate::observe::EventKind::Handled
+//! [`capture_enabled()`]: crate::trace::capture_enabled
+//! [`probe`]: https://docs.rs/probe
+
+use crate::observe::EventKind;
+
+use std::panic;
+
+/// Fires the probe for `kind` with `location` as its arguments.
+#[inline]
+pub(crate) fn fire(kind: EventKind, location: &'static panic::Location<'static>) {
+    let file = location.file();
+    match kind {
+        EventKind::Created => probe::probe_lazy!(
+            propagate,
+            created,
+            file.as_ptr(),
+            file.len(),
+            location.line(),
+            location.column()
+        ),
+        EventKind::Propagated => probe::probe_lazy!(
+            propagate,
+            propagated,
+            file.as_ptr(),
+            file.len(),
+            location.line(),
+            location.column()
+        ),
+        EventKind::Converted => probe::probe_lazy!(
+            propagate,
+            converted,
+            file.as_ptr(),
+            file.len(),
+            location.line(),
+            location.column()
+        ),
+        EventKind::Handled => probe::probe_lazy!(
+            propagate,
+            handled,
+            file.as_ptr(),
+            file.len(),
+            location.line(),
+            location.column()
+        ),
+    };
+}
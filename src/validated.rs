@@ -0,0 +1,267 @@
+//! Accumulating every error instead of stopping at the first.
+//!
+//! A [`propagate::Result`] short-circuits: the `?` operator returns the first
+//! error it sees. When checking user input such as a form or a configuration
+//! file, it is usually more helpful to report every problem at once. A
+//! [`Validated`] value collects each error along with its own trace, and can be
+//! turned back into a result once all checks have run.
+//!
+//! # Examples
+//!
+//! ```
+//! use propagate::Validated;
+//!
+//! fn check_port(port: u32) -> propagate::Result<u16, String> {
+//!     if port > u16::MAX as u32 {
+//!         return propagate::Result::new_err(format!("port {} is out of range", port));
+//!     }
+//!     propagate::Ok(port as u16)
+//! }
+//!
+//! fn check_host(host: &str) -> propagate::Result<String, String> {
+//!     if host.is_empty() {
+//!         return propagate::Result::new_err("host is empty".to_string());
+//!     }
+//!     propagate::Ok(host.to_string())
+//! }
+//!
+//! let config = Validated::from(check_host(""))
+//!     .and(check_port(70000).into())
+//!     .into_result();
+//!
+//! let errors = config.unwrap_err();
+//! assert_eq!(errors.len(), 2);
+//! ```
+//!
+//! [`propagate::Result`]: crate::Result
+
+use crate::error::ErrorSet;
+use crate::result::Result::{self, Err, Ok};
+use crate::trace::{ErrorTrace, Traced};
+
+use std::iter::FromIterator;
+use std::panic;
+
+use self::Validated::{Invalid, Valid};
+
+/// Either a valid value, or every error encountered while producing it.
+///
+/// See the [module documentation](self) for more information.
+#[derive(PartialEq, Eq, Debug, Hash)]
+pub enum Validated<T, E, S = ErrorTrace> {
+    /// Contains the valid value.
+    Valid(T),
+    /// Contains every error, each with its own trace.
+    Invalid(ErrorSet<E, S>),
+}
+
+impl<T, E, S> Validated<T, E, S> {
+    /// Returns `true` if the value is [`Valid`].
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use propagate::Validated;
+    ///
+    /// let x: Validated<u32, &str> = Validated::Valid(2);
+    /// assert!(x.is_valid());
+    /// ```
+    #[inline]
+    pub const fn is_valid(&self) -> bool {
+        matches!(self, Valid(_))
+    }
+
+    /// Returns `true` if the value is [`Invalid`].
+    #[inline]
+    pub const fn is_invalid(&self) -> bool {
+        !self.is_valid()
+    }
+
+    /// Returns an iterator over the errors collected so far.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use propagate::{Result, Validated};
+    ///
+    /// let x: Validated<u32, &str> = Result::new_err("too small").into();
+    /// assert_eq!(x.errors().collect::<Vec<_>>(), vec![&"too small"]);
+    /// ```
+    #[inline]
+    pub fn errors(&self) -> impl Iterator<Item = &E> {
+        let errors = match self {
+            Valid(_) => None,
+            Invalid(errors) => Some(errors.errors()),
+        };
+        errors.into_iter().flatten()
+    }
+
+    /// Maps a `Validated<T, E, S>` to `Validated<U, E, S>` by applying a
+    /// function to a contained [`Valid`] value.
+    #[inline]
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Validated<U, E, S> {
+        match self {
+            Valid(t) => Valid(f(t)),
+            Invalid(errors) => Invalid(errors),
+        }
+    }
+
+    /// Combines `self` with another validated value, applying `f` to both
+    /// contained [`Valid`] values.
+    ///
+    /// If either value is [`Invalid`], the errors of both are kept, those of
+    /// `self` first.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use propagate::{Result, Validated};
+    ///
+    /// let x: Validated<u32, &str> = Validated::Valid(2);
+    /// let y: Validated<u32, &str> = Validated::Valid(3);
+    /// assert_eq!(x.zip_with(y, |a, b| a + b), Validated::Valid(5));
+    ///
+    /// let x: Validated<u32, &str> = Result::new_err("first").into();
+    /// let y: Validated<u32, &str> = Result::new_err("second").into();
+    /// let z = x.zip_with(y, |a, b| a + b);
+    /// assert_eq!(z.errors().collect::<Vec<_>>(), vec![&"first", &"second"]);
+    /// ```
+    #[inline]
+    pub fn zip_with<U, R, F>(self, other: Validated<U, E, S>, f: F) -> Validated<R, E, S>
+    where
+        F: FnOnce(T, U) -> R,
+    {
+        match (self, other) {
+            (Valid(t), Valid(u)) => Valid(f(t, u)),
+            (Valid(_), Invalid(errors)) | (Invalid(errors), Valid(_)) => Invalid(errors),
+            (Invalid(mut errors), Invalid(more)) => {
+                errors.extend(more);
+                Invalid(errors)
+            }
+        }
+    }
+
+    /// Combines `self` with another validated value into a tuple.
+    ///
+    /// This is [`zip_with`](Validated::zip_with) with a function that pairs
+    /// its arguments.
+    #[inline]
+    pub fn and<U>(self, other: Validated<U, E, S>) -> Validated<(T, U), E, S> {
+        self.zip_with(other, |t, u| (t, u))
+    }
+
+    /// Converts `self` into a [`Result`], consuming `self`.
+    ///
+    /// If `self` is [`Invalid`], the returned error holds every collected
+    /// error, and a new trace is started at the caller.
+    ///
+    /// # Examples
+    ///
+    /// Basic usage:
+    ///
+    /// ```
+    /// use propagate::{Result, Validated};
+    ///
+    /// let x: Validated<u32, &str> = Validated::Valid(2);
+    /// assert_eq!(x.into_result().ok(), Some(2));
+    ///
+    /// let x: Validated<u32, &str> = Result::new_err("too small").into();
+    /// let (errors, trace) = x.into_result().err_trace().unwrap();
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(trace.0.len(), 1);
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn into_result(self) -> Result<T, ErrorSet<E, S>, S>
+    where
+        S: Traced + Default,
+    {
+        match self {
+            Valid(t) => Ok(t),
+            Invalid(errors) => {
+                let trace =
+                    crate::result::new_trace::<ErrorSet<E, S>, S>(panic::Location::caller());
+                Err(errors, trace)
+            }
+        }
+    }
+}
+
+impl<T, E, S> From<Result<T, E, S>> for Validated<T, E, S> {
+    #[inline]
+    fn from(result: Result<T, E, S>) -> Self {
+        match result {
+            Ok(t) => Valid(t),
+            Err(err, trace) => Invalid(ErrorSet(vec![(err, trace)])),
+        }
+    }
+}
+
+/// Collects every error from an iterator of results, or every value if there
+/// were no errors.
+///
+/// # Examples
+///
+/// ```
+/// use propagate::{Result, Validated};
+///
+/// let results: Vec<Result<u32, &str>> = vec![
+///     propagate::Ok(1),
+///     Result::new_err("two"),
+///     Result::new_err("three"),
+/// ];
+/// let all: Validated<Vec<u32>, &str> = results.into_iter().collect();
+/// assert_eq!(all.errors().count(), 2);
+/// ```
+impl<A, T, E, S> FromIterator<Result<A, E, S>> for Validated<T, E, S>
+where
+    T: FromIterator<A>,
+{
+    fn from_iter<I: IntoIterator<Item = Result<A, E, S>>>(iter: I) -> Self {
+        let mut errors = ErrorSet::new();
+        let oks: T = iter
+            .into_iter()
+            .filter_map(|result| match result {
+                Ok(a) => Some(a),
+                Err(err, trace) => {
+                    errors.push(err, trace);
+                    None
+                }
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Valid(oks)
+        } else {
+            Invalid(errors)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::CodeLocation;
+
+    #[test]
+    fn keeps_each_error_trace() {
+        let first_at = CodeLocation::here().down_by(1);
+        let first: Result<u32, &str> = Result::new_err("first");
+        let second_at = CodeLocation::here().down_by(1);
+        let second: Result<u32, &str> = Result::new_err("second");
+
+        let validated = Validated::from(first).and(second.into());
+        let here = CodeLocation::here().down_by(1);
+        let (errors, trace) = validated.into_result().err_trace().unwrap();
+
+        assert_eq!(trace.0, vec![here]);
+        let traces: Vec<_> = errors.traces().map(|t| t.0.clone()).collect();
+        assert_eq!(traces, vec![vec![first_at], vec![second_at]]);
+    }
+}
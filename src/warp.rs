@@ -0,0 +1,207 @@
+//! Returning traced errors from [`warp`] filters.
+//!
+//! A [`TracedError`] implements warp's [`Reject`] trait, so it can be turned
+//! into a [`Rejection`] with `into()`. A filter can call functions returning
+//! [`propagate::Result`] and reject with the traced error using
+//! [`Result::into_rejection()`]. The [`recover()`] function can then be
+//! installed with [`Filter::recover()`] to log the error together with
+//! its propagation trace, and reply with an internal server error. The
+//! message of the error is never sent to the client, since it may describe
+//! the internals of the server; [`recover_with()`] chooses the body of the
+//! reply and how the error is logged.
+//!
+//! Available with the `warp` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use propagate::ErrorTrace;
+//! use warp::Filter;
+//!
+//! fn lookup(id: u32) -> propagate::Result<String, String> {
+//!     if id == 0 {
+//!         return propagate::Result::new_err("no such user".to_string());
+//!     }
+//!     propagate::Ok(format!("user {}", id))
+//! }
+//!
+//! # async fn run() {
+//! let users = warp::path!("users" / u32)
+//!     .and_then(|id| async move { lookup(id).into_rejection() })
+//!     .recover(propagate::warp::recover::<String, ErrorTrace>);
+//!
+//! warp::serve(users).run(([127, 0, 0, 1], 3030)).await;
+//! # }
+//! ```
+//!
+//! [`propagate::Result`]: crate::Result
+//! [`Reject`]: ::warp::reject::Reject
+//! [`Rejection`]: ::warp::Rejection
+//! [`Filter::recover()`]: ::warp::Filter::recover
+
+use crate::error::TracedError;
+use crate::result::Result::{self, Err, Ok};
+
+use std::fmt;
+use std::future::{self, Ready};
+
+use ::warp::http::StatusCode;
+use ::warp::reject::{Reject, Rejection};
+use ::warp::reply::{self, WithStatus};
+
+impl<E, S> Reject for TracedError<E, S>
+where
+    E: fmt::Debug + Send + Sync + 'static,
+    S: fmt::Debug + Send + Sync + 'static,
+{
+}
+
+impl<T, E, S> Result<T, E, S>
+where
+    E: fmt::Debug + Send + Sync + 'static,
+    S: fmt::Debug + Send + Sync + 'static,
+{
+    /// Converts `self` into the result expected from a warp filter, rejecting
+    /// with the [`TracedError`] if `self` is an error.
+    ///
+    /// Available with the `warp` feature.
+    #[inline]
+    pub fn into_rejection(self) -> std::result::Result<T, Rejection> {
+        match self {
+            Ok(t) => std::result::Result::Ok(t),
+            Err(err, trace) => std::result::Result::Err(TracedError::new(err, trace).into()),
+        }
+    }
+}
+
+/// The body of the replies sent by [`recover()`].
+pub const INTERNAL_ERROR_BODY: &str = "Internal Server Error";
+
+/// Handles a rejection caused by a `TracedError<E, S>`, logging the error
+/// and its trace with [`log_error()`] and replying with
+/// `500 Internal Server Error`.
+///
+/// The body of the reply is [`INTERNAL_ERROR_BODY`], not the message of the
+/// error. Other rejections are passed on unchanged. See the
+/// [module documentation](self) for an example.
+pub async fn recover<E, S>(
+    rejection: Rejection,
+) -> std::result::Result<WithStatus<&'static str>, Rejection>
+where
+    E: fmt::Display + fmt::Debug + Send + Sync + 'static,
+    S: fmt::Display + fmt::Debug + Send + Sync + 'static,
+{
+    handle(rejection, INTERNAL_ERROR_BODY, log_error::<E, S>)
+}
+
+/// Returns a handler like [`recover()`] that replies with `body` and passes
+/// the error to `log` instead.
+///
+/// # Examples
+///
+/// ```no_run
+/// use propagate::{ErrorTrace, TracedError};
+/// use warp::Filter;
+///
+/// fn report(error: &TracedError<String>) {
+///     // Send the error to the monitoring system...
+/// #   let _ = error;
+/// }
+///
+/// # async fn run() {
+/// let users = warp::path!("users" / u32)
+///     .and_then(|id: u32| async move {
+///         propagate::Result::<String, String>::new_err(format!("no user {}", id))
+///             .into_rejection()
+///     })
+///     .recover(propagate::warp::recover_with("Something went wrong", report));
+///
+/// warp::serve(users).run(([127, 0, 0, 1], 3030)).await;
+/// # }
+/// ```
+pub fn recover_with<E, S>(
+    body: &'static str,
+    log: fn(&TracedError<E, S>),
+) -> impl Fn(Rejection) -> Ready<std::result::Result<WithStatus<&'static str>, Rejection>>
+       + Clone
+       + Send
+       + Sync
+       + 'static
+where
+    E: fmt::Debug + Send + Sync + 'static,
+    S: fmt::Debug + Send + Sync + 'static,
+{
+    move |rejection| future::ready(handle(rejection, body, log))
+}
+
+/// Logs `error` and its trace at the `error` level of the [`log`] crate, with
+/// the `propagate::warp` target.
+///
+/// This is the logger used by [`recover()`].
+///
+/// [`log`]: https://docs.rs/log
+pub fn log_error<E, S>(error: &TracedError<E, S>)
+where
+    E: fmt::Display,
+    S: fmt::Display,
+{
+    log::error!(
+        target: "propagate::warp",
+        "Error: {}\nReturn Trace: {}",
+        error.error(),
+        error.stack()
+    );
+}
+
+/// Passes the `TracedError<E, S>` that caused `rejection`, if any, to `log`,
+/// and replies with `body`.
+fn handle<E, S>(
+    rejection: Rejection,
+    body: &'static str,
+    log: fn(&TracedError<E, S>),
+) -> std::result::Result<WithStatus<&'static str>, Rejection>
+where
+    E: fmt::Debug + Send + Sync + 'static,
+    S: fmt::Debug + Send + Sync + 'static,
+{
+    match rejection.find::<TracedError<E, S>>() {
+        Some(traced) => {
+            log(traced);
+            std::result::Result::Ok(reply::with_status(body, StatusCode::INTERNAL_SERVER_ERROR))
+        }
+        None => std::result::Result::Err(rejection),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ErrorTrace;
+
+    use std::sync::Mutex;
+
+    use ::warp::Reply;
+
+    static LOGGED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn remember(error: &TracedError<String>) {
+        LOGGED.lock().unwrap().push(error.error().clone());
+    }
+
+    #[test]
+    fn replies_without_the_error_message() {
+        let error = TracedError::new("password file missing".to_string(), ErrorTrace::new());
+        let rejection = ::warp::reject::custom(error);
+
+        let reply = handle(rejection, INTERNAL_ERROR_BODY, remember).unwrap();
+        let response = reply.into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            format!("{:?}", response.body()),
+            format!("{:?}", ::warp::hyper::Body::from(INTERNAL_ERROR_BODY))
+        );
+        assert_eq!(*LOGGED.lock().unwrap(), vec!["password file missing"]);
+
+        assert!(handle(::warp::reject::not_found(), INTERNAL_ERROR_BODY, remember).is_err());
+    }
+}